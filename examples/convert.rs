@@ -12,10 +12,17 @@ fn main() -> Result<(), Error> {
     // Parse the file as BPMN and convert it to a Petri net, then convert the Petri net to PNML
     let bpmn: Bpmn = parse_xml(file).map_err(Error::Parse)?;
     let petri_net: ECNet = bpmn.into();
-    let pnml: Pnml = petri_net.into();
 
-    // Print the PNML
-    println!("{}", pnml);
+    // Print the net in the requested format, PNML by default
+    let stdout = std::io::stdout().lock();
+    match std::env::args().nth(2).as_deref() {
+        Some("dot") => petri_net.write_dot(stdout).map_err(Error::IO)?,
+        Some("svg") => petri_net.write_svg(stdout).map_err(Error::IO)?,
+        _ => {
+            let pnml: Pnml = petri_net.into();
+            println!("{}", pnml);
+        }
+    }
 
     Ok(())
 }
\ No newline at end of file
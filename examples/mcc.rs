@@ -0,0 +1,30 @@
+use std::fs::File;
+use std::io::BufReader;
+use petri_nets::{Error, PTNet, Pnml, PropertySet, parse_xml};
+
+fn main() -> Result<(), Error> {
+    // Read the model and the property file from the command line arguments
+    let mut args = std::env::args().skip(1);
+    let model_file = args.next().ok_or(Error::NoInputFile)?;
+    let property_file = args.next().ok_or(Error::NoInputFile)?;
+
+    // Parse the model as PNML and convert it to a Petri net
+    let file = File::open(&model_file).map(BufReader::new).map_err(Error::IO)?;
+    let pnml: Pnml = parse_xml(file).map_err(Error::Parse)?;
+    let petri_net: PTNet = pnml.try_into().map_err(Error::InvalidPnml)?;
+
+    // Parse the properties
+    let file = File::open(&property_file).map(BufReader::new).map_err(Error::IO)?;
+    let property_set: PropertySet = parse_xml(file).map_err(Error::Parse)?;
+
+    // Print one line per property in the format of the Model Checking Contest,
+    // reporting the properties which could not be answered on stderr
+    for answer in petri_net.answer_all(&property_set) {
+        match answer {
+            Ok(answer) => println!("{}", answer),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+
+    Ok(())
+}
@@ -1,32 +1,23 @@
-use std::fs::File;
-use std::io::BufReader;
-use petri_nets::{Bpmn, ECNet, Error, parse_xml, Pnml};
+use petri_nets::{Error, load_file};
 
 fn main() -> Result<(), Error> {
     // Read the input file from the command line arguments
     let input_file = std::env::args().nth(1).ok_or(Error::NoInputFile)?;
 
-    // Open the file
-    let file = File::open(&input_file).map(BufReader::new).map_err(Error::IO)?;
-
-    // Parse the file either as BPMN or PNML, and convert it to a Petri net
-    let petri_net: ECNet = {
-        if input_file.ends_with(".bpmn") {
-            let bpmn: Bpmn = parse_xml(file).map_err(Error::Parse)?;
-            bpmn.into()
-        } else if input_file.ends_with(".pnml") {
-            let pnml: Pnml = parse_xml(file).map_err(Error::Parse)?;
-            pnml.into()
-        } else {
-            return Err(Error::UnsupportedFileExt);
-        }
-    };
+    // Load the file in whichever format it is, as an EC net or PT net depending on what it needs
+    let petri_net = load_file(&input_file)?.net;
 
     // Perform reachability analysis on the Petri net
     let reachability_analysis = petri_net.reachability_analysis();
 
-    // Print the reachability analysis
-    println!("{}", reachability_analysis);
+    // Print the reachability analysis, or export its graph in the requested format
+    let stdout = std::io::stdout().lock();
+    match std::env::args().nth(2).as_deref() {
+        Some("dot") => reachability_analysis.write_dot(stdout).map_err(Error::IO)?,
+        Some("graphml") => reachability_analysis.write_graphml(stdout).map_err(Error::IO)?,
+        Some("json") => reachability_analysis.write_json(stdout).map_err(Error::IO)?,
+        _ => println!("{}", reachability_analysis),
+    }
 
     Ok(())
 }
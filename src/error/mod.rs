@@ -4,18 +4,33 @@ use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
 pub enum Error {
     NoInputFile, // Error when no input file is provided
-    UnsupportedFileExt, // Error when the file extension is not supported
+    UnrecognizedFormat(Vec<crate::load::Format>), // Error when a file is in none of the formats which were tried
     IO(std::io::Error), // Error when an IO operation fails
     Parse(quick_xml::de::DeError), // Error when parsing XML fails
+    InvalidPnml(crate::petri_net::PnmlError), // Error when a PNML file does not describe a valid net of the requested kind
+    InvalidLola(String), // Error when a LoLA file is malformed or does not describe a valid net of the requested kind
+    InvalidTina(String), // Error when a TINA file is malformed, uses unsupported features or does not describe a valid net of the requested kind
+    InvalidCheckpoint(String), // Error when a checkpoint is malformed or was taken from a different net
+    UnsupportedProperty(String), // Error when a property refers to unknown elements or uses unsupported operators
+    UnsafeNet(String), // Error when an analysis which requires a safe net finds a place with more than one token
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             Error::NoInputFile => write!(f, "No input file provided. Exiting..."),
-            Error::UnsupportedFileExt => write!(f, "Unsupported file extension. Supported file extensions are: .bpmn, .pnml"),
+            Error::UnrecognizedFormat(formats) => {
+                let formats: Vec<String> = formats.iter().map(ToString::to_string).collect();
+                write!(f, "Unrecognized format. Tried: {}", formats.join(", "))
+            }
             Error::IO(e) => write!(f, "Error opening file: {}", e),
             Error::Parse(e) => write!(f, "Error parsing XML: {}", e),
+            Error::InvalidPnml(e) => write!(f, "Invalid PNML: {}", e),
+            Error::InvalidLola(e) => write!(f, "Invalid LoLA file: {}", e),
+            Error::InvalidTina(e) => write!(f, "Invalid TINA file: {}", e),
+            Error::InvalidCheckpoint(e) => write!(f, "Invalid checkpoint: {}", e),
+            Error::UnsupportedProperty(e) => write!(f, "Unsupported property: {}", e),
+            Error::UnsafeNet(e) => write!(f, "Net is not safe: {}", e),
         }
     }
 }
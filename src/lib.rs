@@ -1,9 +1,11 @@
 pub mod error;
 pub mod bpmn;
 pub mod petri_net;
+pub mod load;
 
 pub use quick_xml::de::from_reader as parse_xml;
 
 pub use error::*;
+pub use load::{load, load_file, Format, Loaded};
 pub use crate::bpmn::Bpmn;
-pub use crate::petri_net::{PetriNet, ECNet, PTNet, Pnml};
+pub use crate::petri_net::{AnyNet, PetriNet, ECNet, PTNet, Pnml, PropertySet};
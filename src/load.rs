@@ -0,0 +1,170 @@
+//! This module loads a net from a file in any of the supported formats.
+//! The format is recognized by the content of the file rather than by its name:
+//! BPMN diagrams have a `<definitions>` root element and PNML files a `<pnml>` root element,
+//! with or without a namespace prefix, LoLA files start with a `PLACE` section,
+//! and TINA files with a line which declares the net, a transition or a place.
+//!
+//! PNML, LoLA and TINA files are read as PT nets and loaded as EC nets only if their weights and capacities are all 1,
+//! so the kind of a net does not depend on its format. LoLA and TINA have no capacities, so their nets are always PT nets.
+//!
+//! The loaded net comes with a source map from its places and transitions back to the IDs
+//! of the elements of the file they were created from.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::petri_net::{is_lola, is_tina, OriginalIds};
+use crate::{parse_xml, AnyNet, Bpmn, Error, PTNet, Pnml};
+
+/// A format which nets can be loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bpmn,
+    Pnml,
+    Lola,
+    Tina,
+}
+
+impl Format {
+    /// Every supported format, in the order in which they are tried
+    pub const ALL: [Format; 4] = [Format::Bpmn, Format::Pnml, Format::Lola, Format::Tina];
+
+    /// Recognize the format of the content of a file
+    pub fn detect(content: &str) -> Option<Format> {
+        Format::ALL.into_iter().find(|format| format.matches(content))
+    }
+    /// Returns true if the content looks like a file of this format
+    fn matches(self, content: &str) -> bool {
+        match self {
+            Format::Bpmn => root_element(content) == Some("definitions"),
+            Format::Pnml => root_element(content) == Some("pnml"),
+            Format::Lola => is_lola(content),
+            Format::Tina => is_tina(content),
+        }
+    }
+}
+
+/// Formats are displayed with what identifies them, e.g. BPMN (<definitions>)
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Format::Bpmn => write!(f, "BPMN (<definitions>)"),
+            Format::Pnml => write!(f, "PNML (<pnml>)"),
+            Format::Lola => write!(f, "LoLA (PLACE section)"),
+            Format::Tina => write!(f, "TINA (net, tr or pl lines)"),
+        }
+    }
+}
+
+/// The local name of the root element of an XML document, skipping the XML declaration,
+/// processing instructions, comments and the document type declaration before it
+fn root_element(content: &str) -> Option<&str> {
+    let mut rest = content.trim_start_matches('\u{feff}');
+    loop {
+        rest = rest.trim_start().strip_prefix('<')?;
+        let end = if rest.starts_with('?') {
+            rest.find("?>")? + 2
+        } else if rest.starts_with("!--") {
+            rest.find("-->")? + 3
+        } else if rest.starts_with('!') {
+            rest.find('>')? + 1
+        } else {
+            let name = &rest[..rest.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?];
+            return Some(name.rsplit(':').next().unwrap_or(name));
+        };
+        rest = &rest[end..];
+    }
+}
+
+/// A net loaded from a file, together with the format of the file
+#[derive(Debug, Clone)]
+pub struct Loaded {
+    pub format: Format,
+    pub net: AnyNet,
+}
+
+impl Loaded {
+    /// The IDs of the elements of the file which the places, transitions and arcs were created from
+    pub fn source_map(&self) -> &OriginalIds {
+        &self.net.annotations().ids
+    }
+}
+
+/// Load a net from a reader, recognizing the format by the content
+pub fn load<R: Read>(mut reader: R) -> Result<Loaded, Error> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content).map_err(Error::IO)?;
+    let format = Format::detect(&content).ok_or(Error::UnrecognizedFormat(Format::ALL.to_vec()))?;
+    let net = match format {
+        Format::Bpmn => parse_xml::<_, Bpmn>(content.as_bytes()).map_err(Error::Parse)?.into(),
+        Format::Pnml => parse_xml::<_, Pnml>(content.as_bytes())
+            .map_err(Error::Parse)?
+            .try_into()
+            .map_err(Error::InvalidPnml)?,
+        Format::Lola => PTNet::read_lola(content.as_bytes())?.into(),
+        Format::Tina => PTNet::read_tina(content.as_bytes())?.into(),
+    };
+    Ok(Loaded { format, net })
+}
+
+/// Load a net from a file, recognizing the format by the content
+pub fn load_file(path: impl AsRef<Path>) -> Result<Loaded, Error> {
+    let file = File::open(path).map(BufReader::new).map_err(Error::IO)?;
+    load(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(root_element("<?xml version=\"1.0\"?>\n<!-- a comment -->\n<bpmn:definitions id=\"d\">"), Some("definitions"));
+        assert_eq!(root_element("<!DOCTYPE pnml>\n<pnml/>"), Some("pnml"));
+        assert_eq!(root_element("PLACE p;"), None);
+
+        // BPMN elements map to the places and transitions created for them
+        let loaded = load(include_str!("../models/choice.bpmn").as_bytes())?;
+        assert_eq!(loaded.format, Format::Bpmn);
+        assert_eq!(loaded.net.kind(), "EC");
+        let approve = loaded.source_map().find_transition("approve").expect("approve should be mapped");
+        assert_eq!(loaded.source_map().transitions[&approve], "approve");
+
+        let loaded = load(include_str!("../models/weighted.pnml").as_bytes())?;
+        assert_eq!(loaded.format, Format::Pnml);
+        assert_eq!(loaded.net.kind(), "PT");
+        assert!(loaded.source_map().find_place("buffer").is_some());
+
+        // Places without a capacity are unbounded, so a net without them is a PT net in every format
+        let loaded = load(include_str!("../models/unbounded.pnml").as_bytes())?;
+        assert_eq!(loaded.format, Format::Pnml);
+        assert_eq!(loaded.net.kind(), "PT");
+        let loaded = load(include_str!("../models/confusion.pnml").as_bytes())?;
+        assert_eq!(loaded.net.kind(), "PT");
+        let loaded = load(include_str!("../models/toggle.pnml").as_bytes())?;
+        assert_eq!(loaded.net.kind(), "EC");
+
+        let loaded = load(include_str!("../models/mutex.lola").as_bytes())?;
+        assert_eq!(loaded.format, Format::Lola);
+        assert_eq!(loaded.net.kind(), "PT");
+        assert!(loaded.source_map().find_transition("enter").is_some());
+        let loaded = load("PLACE p, q; MARKING p; TRANSITION t CONSUME p; PRODUCE q;".as_bytes())?;
+        assert_eq!(loaded.net.kind(), "PT");
+
+        let loaded = load(include_str!("../models/handover.net").as_bytes())?;
+        assert_eq!(loaded.format, Format::Tina);
+        assert_eq!(loaded.net.kind(), "PT");
+        assert!(loaded.source_map().find_place("channel").is_some());
+        let loaded = load("tr t p -> q\npl p (1)".as_bytes())?;
+        assert_eq!(loaded.net.kind(), "PT");
+
+        let error = load("<html></html>".as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unrecognized format. Tried: BPMN (<definitions>), PNML (<pnml>), LoLA (PLACE section), TINA (net, tr or pl lines)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! This module keeps the parts of a PNML file which the Petri net does not model,
+//! such as graphics, tool-specific data and elements of unknown extensions,
+//! so that a net can be loaded from an editor and saved back without losing them.
+//!
+//! They are stored as generic XML elements in a side table of the net, attached to the place, transition or arc
+//! they were found in. For every such element, the table holds a copy of it which contains only what was not
+//! interpreted: its remaining attributes and child elements, and the remains of its labels
+//! (e.g. a `name` element with only the `graphics` of the name).
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Formatter, Result as FmtResult};
+
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Arc, PlaceId, TransitionId};
+
+/// A generic XML element
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlElement>,
+    pub text: Option<String>,
+}
+
+impl XmlElement {
+    /// Create an empty element with the given name
+    pub fn new(name: impl Into<String>) -> Self {
+        XmlElement { name: name.into(), ..Default::default() }
+    }
+    /// The first child element with the given name
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+    /// The value of an attribute
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /// Returns true if the element has neither attributes, nor children, nor text
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.children.is_empty() && self.text.is_none()
+    }
+}
+
+/// Visits the content of an element as the quick-xml deserializer presents it:
+/// attributes are keys starting with @, text is the key $text, and every other key is a child element
+struct ContentVisitor;
+
+impl<'de> Visitor<'de> for ContentVisitor {
+    type Value = XmlElement;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "an XML element")
+    }
+    fn visit_unit<E>(self) -> Result<XmlElement, E> {
+        Ok(XmlElement::default())
+    }
+    fn visit_str<E>(self, text: &str) -> Result<XmlElement, E> {
+        Ok(XmlElement { text: Some(text.to_string()), ..Default::default() })
+    }
+    fn visit_string<E>(self, text: String) -> Result<XmlElement, E> {
+        Ok(XmlElement { text: Some(text), ..Default::default() })
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<XmlElement, A::Error> {
+        let mut element = XmlElement::default();
+        while let Some(key) = map.next_key::<String>()? {
+            if let Some(attribute) = key.strip_prefix('@') {
+                element.attributes.push((attribute.to_string(), map.next_value()?));
+            } else if key == "$text" {
+                element.text = Some(map.next_value()?);
+            } else {
+                let mut child: XmlElement = map.next_value()?;
+                child.name = key;
+                element.children.push(child);
+            }
+        }
+        Ok(element)
+    }
+}
+
+/// The name of a deserialized element is left empty, since it is the key under which its parent holds it
+impl<'de> Deserialize<'de> for XmlElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ContentVisitor)
+    }
+}
+
+/// The name of a serialized element is not written, since its parent writes it as the key of the element
+impl Serialize for XmlElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let (true, true, Some(text)) = (self.attributes.is_empty(), self.children.is_empty(), &self.text) {
+            return serializer.serialize_str(text);
+        }
+        let mut map = serializer.serialize_map(None)?;
+        for (attribute, value) in &self.attributes {
+            map.serialize_entry(&format!("@{}", attribute), value)?;
+        }
+        for child in &self.children {
+            map.serialize_entry(&child.name, child)?;
+        }
+        if let Some(text) = &self.text {
+            map.serialize_entry("$text", text)?;
+        }
+        map.end()
+    }
+}
+
+/// The attributes and child elements of a PNML element which its serde struct does not know.
+/// It is flattened into the struct, so serde hands it everything the struct's own fields do not consume.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Extra {
+    pub(super) attributes: Vec<(String, String)>,
+    pub(super) children: Vec<XmlElement>,
+}
+
+impl Extra {
+    pub(super) fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.children.is_empty()
+    }
+    /// Turn the unknown content into an element with the given name
+    pub(super) fn into_element(self, name: &str) -> XmlElement {
+        XmlElement { name: name.to_string(), attributes: self.attributes, children: self.children, text: None }
+    }
+}
+
+impl From<XmlElement> for Extra {
+    fn from(element: XmlElement) -> Self {
+        Extra { attributes: element.attributes, children: element.children }
+    }
+}
+
+impl<'de> Deserialize<'de> for Extra {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ContentVisitor).map(Into::into)
+    }
+}
+
+impl Serialize for Extra {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (attribute, value) in &self.attributes {
+            map.serialize_entry(&format!("@{}", attribute), value)?;
+        }
+        for child in &self.children {
+            map.serialize_entry(&child.name, child)?;
+        }
+        map.end()
+    }
+}
+
+/// The uninterpreted content of a PNML file, attached to the elements of the net it was found in
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    /// The child elements of the net which are not places, transitions or arcs, e.g. its name or tool-specific data
+    pub net: Vec<XmlElement>,
+    /// The first page of the net with its ID and elements other than nodes and arcs, if the net was organized in pages
+    pub page: Option<XmlElement>,
+    pub places: HashMap<PlaceId, XmlElement>,
+    pub transitions: HashMap<TransitionId, XmlElement>,
+    pub arcs: HashMap<Arc, XmlElement>,
+    /// The IDs the elements had in the file
+    pub ids: OriginalIds,
+}
+
+/// The IDs which places, transitions and arcs had in the file the net was loaded from,
+/// e.g. the IDs of the BPMN elements which places and transitions were created for
+#[derive(Debug, Clone, Default)]
+pub struct OriginalIds {
+    pub places: HashMap<PlaceId, String>,
+    pub transitions: HashMap<TransitionId, String>,
+    pub arcs: HashMap<Arc, String>,
+}
+
+impl OriginalIds {
+    /// The place which had the given ID in the file
+    pub fn find_place(&self, original: &str) -> Option<PlaceId> {
+        self.places.iter().find(|(_, id)| *id == original).map(|(&place, _)| place)
+    }
+    /// The transition which had the given ID in the file
+    pub fn find_transition(&self, original: &str) -> Option<TransitionId> {
+        self.transitions.iter().find(|(_, id)| *id == original).map(|(&transition, _)| transition)
+    }
+}
+
+/// Hands out the IDs of exported elements: the ID from the file the net was loaded from if there is one,
+/// and otherwise the generated one, made unique among all IDs in use
+pub(super) struct IdAllocator {
+    used: HashSet<String>,
+}
+
+impl IdAllocator {
+    /// Start with the IDs which are already in use
+    pub(super) fn new<'a>(used: impl IntoIterator<Item = &'a String>) -> Self {
+        IdAllocator { used: used.into_iter().cloned().collect() }
+    }
+    pub(super) fn allocate(&mut self, original: Option<&String>, generated: String) -> String {
+        if let Some(original) = original {
+            return original.clone();
+        }
+        let mut id = generated.clone();
+        let mut suffix = 1;
+        while !self.used.insert(id.clone()) {
+            id = format!("{}_{}", generated, suffix);
+            suffix += 1;
+        }
+        id
+    }
+}
+
+impl Annotations {
+    /// Returns true if nothing but the net itself, with the IDs of its elements, was found in the file
+    pub fn is_empty(&self) -> bool {
+        self.net.is_empty() && self.page.is_none() && self.places.is_empty() && self.transitions.is_empty() && self.arcs.is_empty()
+    }
+    /// Returns true if any place or transition comes with its own graphics
+    pub fn has_graphics(&self) -> bool {
+        self.places
+            .values()
+            .chain(self.transitions.values())
+            .any(|element| element.child("graphics").is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::petri_net::{Arc, Lookup, PlaceId, TransitionId};
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/editor.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let annotations = &net.annotations;
+        assert_eq!(annotations.net.iter().map(|element| element.name.as_str()).collect::<Vec<_>>(), ["name", "toolspecific"]);
+        let place = &annotations.places[&PlaceId(0)];
+        assert_eq!(place.child("comment").and_then(|comment| comment.attribute("author")), Some("someone"));
+        assert!(place.child("name").and_then(|name| name.child("graphics")).is_some());
+        assert!(place.child("initialMarking").and_then(|marking| marking.child("graphics")).is_some());
+        let priority = annotations.transitions[&TransitionId(0)].child("toolspecific").and_then(|tool| tool.child("priority"));
+        assert_eq!(priority.and_then(|priority| priority.text.as_deref()), Some("3"));
+        assert_eq!(annotations.arcs.len(), 1);
+
+        // Saving keeps everything in place, and the graphics of the editor replace the automatic layout
+        let xml = Pnml::from(net.clone()).to_string();
+        for fragment in [
+            "<text>Saved by an editor</text>",
+            r#"<grid size="10" visible="true"/>"#,
+            r#"<offset x="0" y="-25"/>"#,
+            r#"<offset x="5" y="5"/>"#,
+            r#"<dimension x="40" y="40"/>"#,
+            r#"<fill color="yellow"/>"#,
+            "<priority>3</priority>",
+            r#"<position x="250" y="80"/>"#,
+        ] {
+            assert!(xml.contains(fragment), "{} is missing from\n{}", fragment, xml);
+        }
+        assert_eq!(xml.matches("<position ").count(), 4);
+
+        // Loading the saved file again yields the same net and the same file
+        let reloaded: PTNet = parse_xml::<_, Pnml>(xml.as_bytes())?.try_into()?;
+        assert_eq!(reloaded.initial_marking, net.initial_marking);
+        assert_eq!(reloaded.arcs, net.arcs);
+        assert_eq!(Pnml::from(reloaded).to_string(), xml);
+        Ok(())
+    }
+
+    #[test]
+    fn test_original_ids() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/ids.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        // The elements are numbered in order of appearance, and arcs may refer to elements further down
+        assert_eq!(net.places.len(), 2);
+        assert_eq!(net.transitions.len(), 2);
+        assert_eq!(net.arcs.len(), 4);
+        assert!(net.arcs.contains(&Arc::PlaceTransition(PlaceId(0), TransitionId(0))));
+        assert!(net.arcs.contains(&Arc::TransitionPlace(TransitionId(1), PlaceId(0))));
+        assert_eq!(net.reachability_analysis().rows.len(), 2);
+
+        // Elements can be found by the IDs of the file as well as by the internal ones,
+        // unless the ID of one element in the file is the internal ID of another
+        assert_eq!(net.find_place("p-busy"), Lookup::Found(PlaceId(1)));
+        assert_eq!(net.find_transition("t_start"), Lookup::Found(TransitionId(0)));
+        assert_eq!(net.find_transition("T1"), Lookup::Found(TransitionId(1)));
+        assert_eq!(net.find_transition("T0"), Lookup::Ambiguous);
+        assert!(net.parse_ctl("EF fireable(T0)").is_err());
+
+        // Saving keeps the IDs of the file
+        let xml = Pnml::from(net.clone()).to_string();
+        for fragment in [r#"<place id="p-idle">"#, r#"<transition id="t_start">"#, r#"id="grant" source="t_start" target="p-busy""#] {
+            assert!(xml.contains(fragment), "{} is missing from\n{}", fragment, xml);
+        }
+        let reloaded: PTNet = parse_xml::<_, Pnml>(xml.as_bytes())?.try_into()?;
+        assert_eq!(reloaded.arcs, net.arcs);
+
+        // Elements without an ID from a file get generated ones which do not collide with those of the file
+        let mut renamed = net.clone();
+        renamed.annotations.ids.arcs.remove(&Arc::PlaceTransition(PlaceId(1), TransitionId(1)));
+        let xml = Pnml::from(renamed).to_string();
+        assert!(xml.contains(r#"id="a_p-busy_T0_1" source="p-busy" target="T0""#), "{}", xml);
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! This module loads nets whose kind is only known at runtime.
+//!
+//! A file is loaded as an EC net only if that does not change its behaviour, i.e. if all of its arcs have
+//! a weight of 1 and all of its places declare a capacity of 1. The kind is decided from the structure alone,
+//! so that loading a large net does not explore its state space. A place without a capacity is unbounded,
+//! so any net with such a place is loaded as a PT net, even if it happens to be safe. Loading a file as an `ECNet`
+//! fails if an EC net cannot represent its weights, capacities or initial marking; `AnyNet` picks the kind which can.
+//!
+//! The analyses of both kinds are available on `AnyNet` and dispatched to the net inside.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{Result as IoResult, Write};
+
+use super::{
+    Annotations, Answer, Arc, CapacityFn, Ctl, CtlResult, ECNet, FixedCapacity, FixedWeight, Ltl,
+    LtlResult, Marking, PTNet, PetriNet, Place, PlaceBound, Pnml, PnmlError, PropertySet, ReachabilityAnalysis, Symmetries,
+    Transition, VariableCapacity, VariableWeight, WeightFn,
+};
+use crate::{Bpmn, Error};
+
+/// Evaluate an expression on the net or analysis inside, whatever its kind
+macro_rules! dispatch {
+    ($value:expr, $inner:ident => $body:expr) => {
+        match $value {
+            Self::EC($inner) => $body,
+            Self::PT($inner) => $body,
+        }
+    };
+}
+
+/// A Petri net which is either an EC net or a PT net
+#[derive(Debug, Clone)]
+pub enum AnyNet {
+    EC(ECNet),
+    PT(PTNet),
+}
+
+impl AnyNet {
+    /// The kind of the net, i.e. "EC" or "PT"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AnyNet::EC(_) => "EC",
+            AnyNet::PT(_) => "PT",
+        }
+    }
+    pub fn id(&self) -> &str {
+        dispatch!(self, net => &net.id)
+    }
+    pub fn places(&self) -> &[Place] {
+        dispatch!(self, net => &net.places)
+    }
+    pub fn transitions(&self) -> &[Transition] {
+        dispatch!(self, net => &net.transitions)
+    }
+    pub fn arcs(&self) -> &[Arc] {
+        dispatch!(self, net => &net.arcs)
+    }
+    pub fn initial_marking(&self) -> &Marking {
+        dispatch!(self, net => &net.initial_marking)
+    }
+    pub fn annotations(&self) -> &Annotations {
+        dispatch!(self, net => &net.annotations)
+    }
+    /// Perform a reachability analysis on the net
+    pub fn reachability_analysis(&self) -> AnyAnalysis<'_> {
+        match self {
+            AnyNet::EC(net) => AnyAnalysis::EC(net.reachability_analysis()),
+            AnyNet::PT(net) => AnyAnalysis::PT(net.reachability_analysis()),
+        }
+    }
+    /// Check whether the CTL formula holds in the initial marking
+    pub fn check_ctl(&self, formula: &Ctl) -> CtlResult {
+        dispatch!(self, net => net.check_ctl(formula))
+    }
+    /// Check whether every run of the net satisfies the LTL formula
+    pub fn check_ltl(&self, formula: &Ltl) -> LtlResult {
+        dispatch!(self, net => net.check_ltl(formula))
+    }
+    /// Answer every property of a property file of the Model Checking Contest
+    pub fn answer_all(&self, property_set: &PropertySet) -> Vec<Result<Answer, Error>> {
+        dispatch!(self, net => net.answer_all(property_set))
+    }
+    /// The symmetries of the net
+    pub fn symmetries(&self) -> Symmetries {
+        dispatch!(self, net => net.symmetries())
+    }
+    /// Write the net in the Graphviz DOT format
+    pub fn write_dot<Out: Write>(&self, out: Out) -> IoResult<()> {
+        dispatch!(self, net => net.write_dot(out))
+    }
+    /// Write the net as a self-contained SVG image
+    pub fn write_svg<Out: Write>(&self, out: Out) -> IoResult<()> {
+        dispatch!(self, net => net.write_svg(out))
+    }
+}
+
+/// A PT net behaves like an EC net if all of its weights and capacities are 1
+fn needs_pt_semantics(net: &PTNet) -> bool {
+    net.arcs.iter().any(|arc| net.weights.get_or_default(arc).0 != 1)
+        || net.places.iter().any(|place| net.capacities.get_or_default(&place.id).0 != 1)
+}
+
+/// Describe something in a file which the kind of net being loaded cannot represent, e.g. a weight of 2 in an EC net.
+/// The message is shared by all formats, since a PT net can represent anything they describe.
+pub(super) fn unsupported(what: impl Display) -> String {
+    format!("{} is not supported by this kind of net, load it as a PT net", what)
+}
+
+/// Load a PNML file as an EC net if that suffices, and as a PT net otherwise
+impl TryFrom<Pnml> for AnyNet {
+    type Error = PnmlError;
+
+    fn try_from(pnml: Pnml) -> Result<Self, PnmlError> {
+        PTNet::try_from(pnml).map(AnyNet::from)
+    }
+}
+
+/// Turn a PT net into an EC net if it behaves the same as one
+impl From<PTNet> for AnyNet {
+    fn from(net: PTNet) -> Self {
+        if needs_pt_semantics(&net) {
+            return AnyNet::PT(net);
+        }
+        AnyNet::EC(PetriNet {
+            id: net.id,
+            places: net.places,
+            transitions: net.transitions,
+            arcs: net.arcs,
+            capacities: FixedCapacity,
+            weights: FixedWeight,
+            initial_marking: net.initial_marking,
+            annotations: net.annotations,
+        })
+    }
+}
+
+/// Nets converted from BPMN are EC nets
+impl From<Bpmn> for AnyNet {
+    fn from(bpmn: Bpmn) -> Self {
+        AnyNet::EC(bpmn.into())
+    }
+}
+
+impl From<AnyNet> for Pnml {
+    fn from(net: AnyNet) -> Self {
+        match net {
+            AnyNet::EC(net) => net.into(),
+            AnyNet::PT(net) => net.into(),
+        }
+    }
+}
+
+/// The reachability analysis of an `AnyNet`
+#[derive(Debug)]
+pub enum AnyAnalysis<'net> {
+    EC(ReachabilityAnalysis<'net, FixedCapacity<1>, FixedWeight<1>>),
+    PT(ReachabilityAnalysis<'net, VariableCapacity<{ usize::MAX }>, VariableWeight<1>>),
+}
+
+impl AnyAnalysis<'_> {
+    /// The maximal number of tokens on each place
+    pub fn place_bounds(&self) -> Vec<PlaceBound> {
+        dispatch!(self, analysis => analysis.place_bounds())
+    }
+    /// Check whether the CTL formula holds in the initial marking
+    pub fn check_ctl(&self, formula: &Ctl) -> CtlResult {
+        dispatch!(self, analysis => analysis.check_ctl(formula))
+    }
+    /// Write the reachability graph in the Graphviz DOT format
+    pub fn write_dot<Out: Write>(&self, out: Out) -> IoResult<()> {
+        dispatch!(self, analysis => analysis.write_dot(out))
+    }
+    /// Write the reachability graph in the GraphML format
+    pub fn write_graphml<Out: Write>(&self, out: Out) -> IoResult<()> {
+        dispatch!(self, analysis => analysis.write_graphml(out))
+    }
+    /// Write the reachability graph as a JSON document
+    pub fn write_json<Out: Write>(&self, out: Out) -> IoResult<()> {
+        dispatch!(self, analysis => analysis.write_json(out))
+    }
+}
+
+impl Display for AnyAnalysis<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        dispatch!(self, analysis => Display::fmt(analysis, f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::{Bound, Problem, Tokens};
+    use crate::parse_xml;
+
+    #[test]
+    fn test_any_net() -> Result<(), Box<dyn std::error::Error>> {
+        // A net without weights whose places have a capacity of 1 is loaded as an EC net
+        let pnml: Pnml = parse_xml(include_str!("../../models/toggle.pnml").as_bytes())?;
+        let net: AnyNet = pnml.try_into()?;
+        assert_eq!(net.kind(), "EC");
+        let pnml: Pnml = parse_xml(include_str!("../../models/toggle.pnml").as_bytes())?;
+        let pt: PTNet = pnml.try_into()?;
+        assert_eq!(net.reachability_analysis().to_string(), pt.reachability_analysis().to_string());
+
+        // Places without a capacity are unbounded, so even a safe net without them is loaded as a PT net
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: AnyNet = pnml.try_into()?;
+        assert_eq!(net.kind(), "PT");
+
+        // A net with weights and several tokens on a place needs a PT net
+        let load = || parse_xml::<_, Pnml>(include_str!("../../models/weighted.pnml").as_bytes());
+        let net: AnyNet = load()?.try_into()?;
+        assert_eq!(net.kind(), "PT");
+        assert_eq!(net.places().len(), 2);
+        assert_eq!(net.reachability_analysis().place_bounds().len(), 2);
+        let mut dot = Vec::new();
+        net.write_dot(&mut dot)?;
+        assert!(String::from_utf8(dot)?.contains("label=\"2\""));
+
+        // The same holds for a net whose places are in fact unbounded
+        let pnml: Pnml = parse_xml(include_str!("../../models/unbounded.pnml").as_bytes())?;
+        let net: AnyNet = pnml.try_into()?;
+        assert_eq!(net.kind(), "PT");
+        let AnyNet::PT(pt) = &net else { unreachable!() };
+        assert!(pt.exceeds_bound(1).is_some_and(|violation| violation.bound == Bound::Bounded(Tokens(2))));
+
+        // Asking for an EC net fails instead of dropping the weights
+        let error = ECNet::try_from(load()?).unwrap_err();
+        assert!(error.problems().contains(&("take".to_string(), Problem::UnsupportedWeight(2))));
+        assert!(error.problems().contains(&("buffer".to_string(), Problem::ExceedsCapacity { tokens: 4, capacity: 1 })));
+        assert!(error.to_string().contains("load it as a PT net"));
+        Ok(())
+    }
+}
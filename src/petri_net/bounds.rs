@@ -0,0 +1,107 @@
+//! This module reports the bound of every place in a Petri net together with a witness,
+//! a firing sequence from the initial marking to a marking in which the place holds that many tokens.
+//!
+//! Since the reachability graph is explored breadth-first, every witness is a shortest firing sequence
+//! to the first marking in which the place reached its bound.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::observer::Observer;
+use super::reachability::{Boundedness, Predecessors};
+use super::{Bound, CapacityFn, Exploration, PetriNet, PlaceId, ReachabilityAnalysis, Tokens, Trace, WeightFn};
+
+/// The bound of a single place and a trace which reaches it
+#[derive(Debug, Clone)]
+pub struct PlaceBound {
+    pub place: PlaceId,
+    pub bound: Bound,
+    pub witness: Trace,
+}
+
+/// Displayed as the place, its bound and the witness, e.g. P1: 1-Bounded, reached by M000 T0->M001
+impl Display for PlaceBound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}, reached by {}", self.place, self.bound, self.witness)
+    }
+}
+
+/// Collect the bound and witness of every place
+fn place_bounds(boundedness: &Boundedness, predecessors: &Predecessors) -> Vec<PlaceBound> {
+    boundedness.bounds
+        .iter()
+        .zip(&boundedness.witnesses)
+        .enumerate()
+        .map(|(index, (&bound, &witness))| PlaceBound {
+            place: PlaceId(index),
+            bound,
+            witness: predecessors.trace(witness),
+        })
+        .collect()
+}
+
+impl<C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'_, C, W> {
+    /// The bound of every place (ID = index), each with a shortest trace reaching it
+    pub fn place_bounds(&self) -> Vec<PlaceBound> {
+        place_bounds(&self.boundedness, &self.predecessors)
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn, O: Observer> Exploration<'_, C, W, O> {
+    /// The bound of every place as far as it is known from the markings discovered so far,
+    /// each with a shortest trace reaching it
+    pub fn place_bounds(&self) -> Vec<PlaceBound> {
+        place_bounds(&self.boundedness, &self.predecessors)
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Find a place which can hold more than k tokens, along with a trace to a marking in which it does.
+    /// The exploration stops as soon as such a place is discovered,
+    /// so this terminates even for unbounded nets, which exceed every k eventually.
+    pub fn exceeds_bound(&self, k: usize) -> Option<PlaceBound> {
+        let exceeds = |bound: &Bound| *bound > Bound::Bounded(Tokens(k));
+        let mut exploration = self.explore();
+        loop {
+            if let Some(index) = exploration.boundedness.bounds.iter().position(exceeds) {
+                return exploration.place_bounds().into_iter().nth(index);
+            }
+            exploration.next()?;
+        }
+    }
+    /// Returns true if no place can ever hold more than k tokens
+    pub fn is_k_bounded(&self, k: usize) -> bool {
+        self.exceeds_bound(k).is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::Arc;
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_place_bounds() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let bounds: Vec<String> = net.reachability_analysis().place_bounds().iter().map(ToString::to_string).collect();
+        assert_eq!(bounds, [
+            "P0: 1-Bounded, reached by M000",
+            "P1: 1-Bounded, reached by M000 T0->M001",
+            "P2: 1-Bounded, reached by M000 T0->M001",
+            "P3: 1-Bounded, reached by M000 T0->M001 T1->M002",
+            "P4: 1-Bounded, reached by M000 T0->M001 T2->M003",
+            "P5: 1-Bounded, reached by M000 T0->M001 T1->M002 T2->M004 T3->M005",
+        ]);
+        assert!(net.is_k_bounded(1));
+        assert!(!net.is_k_bounded(0));
+
+        // Without its input arcs, the join can fire over and over again
+        let mut unbounded = net.clone();
+        unbounded.arcs.retain(|arc| !matches!(arc, Arc::PlaceTransition(_, transition) if transition.0 == 3));
+        let violation = unbounded.exceeds_bound(1).expect("net should be unbounded");
+        assert_eq!(violation.bound, Bound::Bounded(Tokens(2)));
+        assert!(!violation.witness.steps.is_empty());
+        Ok(())
+    }
+}
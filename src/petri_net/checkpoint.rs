@@ -0,0 +1,366 @@
+//! This module allows an in-progress reachability exploration to be saved to disk and resumed later.
+//!
+//! A checkpoint contains everything the exploration needs to continue:
+//! the markings discovered so far (the visited set), the markings which are still waiting to be explored (the frontier),
+//! and the boundedness and liveness gathered so far.
+//! The rows which were already yielded by the exploration are not part of the checkpoint;
+//! a resumed exploration continues with the first marking of the frontier.
+//!
+//! Checkpoints are stored in a simple line-based text format:
+//!
+//! ```text
+//! petri-nets-checkpoint 2
+//! net <net id>
+//! places <number of places>
+//! transitions <number of transitions>
+//! explored <number of explored markings>
+//! edges <number of edges found>
+//! elapsed <milliseconds spent exploring>
+//! boundedness <bound of P0> <bound of P1> ... (U for unbounded)
+//! witnesses <marking ID in which P0 reached its bound> <marking ID for P1> ...
+//! liveness <liveness class of T0> <liveness class of T1> ...
+//! markings <number of markings>
+//! <place>:<tokens> <place>:<tokens> ... (one line per marking in order of their IDs)
+//! predecessors <marking ID>:<transition> ... (one entry per marking in order of their IDs, - for the initial marking)
+//! frontier <marking ID> <marking ID> ...
+//! ```
+//!
+//! Version 1 of the format lacked the witnesses and predecessors, so checkpoints of that version are rejected.
+
+use std::fs::{rename, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::observer::Observer;
+use super::reachability::{Markings, Predecessors};
+use super::{
+    Bound, Boundedness, CapacityFn, Exploration, Live, Liveness, Marking, MarkingFn, MarkingId, PetriNet, PlaceId,
+    Tokens, TransitionId, WeightFn,
+};
+use crate::Error;
+
+const HEADER: &str = "petri-nets-checkpoint 2";
+
+/// A snapshot of an in-progress exploration
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    net_id: String,
+    places: usize,
+    transitions: usize,
+    explored: usize,
+    edges: usize,
+    elapsed: Duration,
+    boundedness: Boundedness,
+    liveness: Liveness,
+    /// All markings discovered so far, indexed by their ID
+    markings: Vec<Marking>,
+    predecessors: Predecessors,
+    /// The IDs of the markings which still need to be explored, in exploration order
+    frontier: Vec<MarkingId>,
+}
+
+impl<'net, C: CapacityFn, W: WeightFn, O: Observer> Exploration<'net, C, W, O> {
+    /// Take a snapshot of the exploration from which it can be resumed later
+    pub fn checkpoint(&self) -> Checkpoint {
+        let mut markings = vec![Marking::default(); self.markings.markings.len()];
+        for (marking, id) in &self.markings.markings {
+            markings[id.0] = marking.clone();
+        }
+        Checkpoint {
+            net_id: self.petri_net.id.clone(),
+            places: self.petri_net.places.len(),
+            transitions: self.petri_net.transitions.len(),
+            explored: self.explored,
+            edges: self.edges,
+            elapsed: self.start_time.elapsed(),
+            boundedness: self.boundedness.clone(),
+            liveness: self.liveness.clone(),
+            markings,
+            predecessors: self.predecessors.clone(),
+            frontier: self.queue.iter().map(|(id, _)| *id).collect(),
+        }
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Resume an exploration of this Petri net from a checkpoint
+    pub fn resume(&self, checkpoint: Checkpoint) -> Result<Exploration<'_, C, W>, Error> {
+        self.resume_with(checkpoint, ())
+    }
+    /// Resume an exploration of this Petri net from a checkpoint while notifying the given observer
+    pub fn resume_with<O: Observer>(&self, checkpoint: Checkpoint, observer: O) -> Result<Exploration<'_, C, W, O>, Error> {
+        if checkpoint.net_id != self.id
+            || checkpoint.places != self.places.len()
+            || checkpoint.transitions != self.transitions.len()
+        {
+            return Err(Error::InvalidCheckpoint(format!("checkpoint was taken from a different net than {}", self.id)));
+        }
+        let mut exploration = Exploration::new(self, observer);
+        exploration.markings = Markings::default();
+        for marking in &checkpoint.markings {
+            exploration.markings.remember(marking.clone());
+        }
+        exploration.queue = checkpoint.frontier
+            .iter()
+            .map(|&id| (id, checkpoint.markings[id.0].clone()))
+            .collect();
+        exploration.boundedness = checkpoint.boundedness;
+        exploration.liveness = checkpoint.liveness;
+        exploration.predecessors = checkpoint.predecessors;
+        exploration.explored = checkpoint.explored;
+        exploration.edges = checkpoint.edges;
+        // The initial marking was reported before the checkpoint was taken
+        exploration.started = true;
+        // Pretend that the time spent before the checkpoint was spent in this exploration
+        exploration.start_time = Instant::now().checked_sub(checkpoint.elapsed).unwrap_or_else(Instant::now);
+        Ok(exploration)
+    }
+}
+
+impl Checkpoint {
+    /// The number of markings which still need to be explored
+    pub fn frontier(&self) -> usize {
+        self.frontier.len()
+    }
+    /// The number of markings discovered before the checkpoint was taken
+    pub fn discovered(&self) -> usize {
+        self.markings.len()
+    }
+    /// Write the checkpoint in its textual format
+    pub fn write_to<Out: Write>(&self, mut out: Out) -> std::io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "net {}", self.net_id)?;
+        writeln!(out, "places {}", self.places)?;
+        writeln!(out, "transitions {}", self.transitions)?;
+        writeln!(out, "explored {}", self.explored)?;
+        writeln!(out, "edges {}", self.edges)?;
+        writeln!(out, "elapsed {}", self.elapsed.as_millis())?;
+        write!(out, "boundedness")?;
+        for bound in &self.boundedness.bounds {
+            match bound {
+                Bound::Bounded(tokens) => write!(out, " {}", tokens)?,
+                Bound::Unbounded => write!(out, " U")?,
+            }
+        }
+        writeln!(out)?;
+        write!(out, "witnesses")?;
+        for witness in &self.boundedness.witnesses {
+            write!(out, " {}", witness.0)?;
+        }
+        writeln!(out)?;
+        write!(out, "liveness")?;
+        for &live in &self.liveness.0 {
+            write!(out, " {}", live as usize)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "markings {}", self.markings.len())?;
+        for marking in &self.markings {
+            let mut separator = "";
+            for (place_id, tokens) in marking.iter() {
+                write!(out, "{}{}:{}", separator, place_id.0, tokens)?;
+                separator = " ";
+            }
+            writeln!(out)?;
+        }
+        write!(out, "predecessors")?;
+        for predecessor in &self.predecessors.0 {
+            match predecessor {
+                Some((marking_id, transition_id)) => write!(out, " {}:{}", marking_id.0, transition_id.0)?,
+                None => write!(out, " -")?,
+            }
+        }
+        writeln!(out)?;
+        write!(out, "frontier")?;
+        for id in &self.frontier {
+            write!(out, " {}", id.0)?;
+        }
+        writeln!(out)?;
+        out.flush()
+    }
+    /// Read a checkpoint from its textual format
+    pub fn read_from<In: BufRead>(input: In) -> Result<Self, Error> {
+        let mut lines = Lines(input.lines().enumerate());
+        let invalid = |number: usize, message: &str| Error::InvalidCheckpoint(format!("line {}: {}", number, message));
+        let number = |(line, value): (usize, String)| -> Result<usize, Error> {
+            value.parse().map_err(|_| invalid(line, "expected a number"))
+        };
+
+        let (line, header) = lines.next_line()?;
+        if header != HEADER {
+            return Err(match header.strip_prefix("petri-nets-checkpoint ") {
+                Some(version) => Error::InvalidCheckpoint(format!("line {}: unsupported checkpoint version {}", line, version)),
+                None => invalid(line, "not a checkpoint"),
+            });
+        }
+        let (_, net_id) = lines.field("net")?;
+        let places = number(lines.field("places")?)?;
+        let transitions = number(lines.field("transitions")?)?;
+        let explored = number(lines.field("explored")?)?;
+        let edges = number(lines.field("edges")?)?;
+        let elapsed = Duration::from_millis(number(lines.field("elapsed")?)? as u64);
+
+        let (line, value) = lines.field("boundedness")?;
+        let boundedness = value
+            .split_whitespace()
+            .map(|bound| match bound {
+                "U" => Ok(Bound::Unbounded),
+                tokens => tokens.parse().map(|tokens| Bound::Bounded(Tokens(tokens))),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .filter(|bounds| bounds.len() == places)
+            .ok_or_else(|| invalid(line, "expected one bound per place"))?;
+
+        let (line, value) = lines.field("witnesses")?;
+        let witnesses = value
+            .split_whitespace()
+            .map(|id| id.parse().ok().map(MarkingId))
+            .collect::<Option<Vec<_>>>()
+            .filter(|witnesses| witnesses.len() == places)
+            .ok_or_else(|| invalid(line, "expected one marking ID per place"))?;
+
+        let (line, value) = lines.field("liveness")?;
+        let liveness = value
+            .split_whitespace()
+            .map(|live| match live {
+                "0" => Some(Live::L0),
+                "1" => Some(Live::L1),
+                "2" => Some(Live::L2),
+                "3" => Some(Live::L3),
+                "4" => Some(Live::L4),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|lives| lives.len() == transitions)
+            .ok_or_else(|| invalid(line, "expected one liveness class per transition"))?;
+
+        let count = number(lines.field("markings")?)?;
+        let mut markings = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (line, value) = lines.next_line()?;
+            let mut marking = Marking::default();
+            for entry in value.split_whitespace() {
+                let (place, tokens) = entry
+                    .split_once(':')
+                    .and_then(|(place, tokens)| Some((place.parse().ok()?, tokens.parse().ok()?)))
+                    .filter(|&(place, _)| place < places)
+                    .ok_or_else(|| invalid(line, "expected <place>:<tokens>"))?;
+                marking.set(PlaceId(place), Tokens(tokens));
+            }
+            markings.push(marking);
+        }
+        if witnesses.iter().any(|witness| witness.0 >= markings.len()) {
+            return Err(Error::InvalidCheckpoint("witnesses must be discovered markings".to_string()));
+        }
+
+        let (line, value) = lines.field("predecessors")?;
+        let predecessors = value
+            .split_whitespace()
+            .map(|predecessor| match predecessor {
+                "-" => Some(None),
+                entry => {
+                    let (marking_id, transition_id) = entry.split_once(':')?;
+                    let marking_id = marking_id.parse().ok().filter(|&id| id < markings.len())?;
+                    let transition_id = transition_id.parse().ok().filter(|&id| id < transitions)?;
+                    Some(Some((MarkingId(marking_id), TransitionId(transition_id))))
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|predecessors| predecessors.len() == markings.len())
+            .ok_or_else(|| invalid(line, "expected one <marking>:<transition> or - per marking"))?;
+
+        let (line, value) = lines.field("frontier")?;
+        let frontier = value
+            .split_whitespace()
+            .map(|id| id.parse().ok().filter(|&id| id < markings.len()).map(MarkingId))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid(line, "expected IDs of discovered markings"))?;
+
+        Ok(Checkpoint {
+            net_id,
+            places,
+            transitions,
+            explored,
+            edges,
+            elapsed,
+            boundedness: Boundedness { bounds: boundedness, witnesses },
+            liveness: Liveness(liveness),
+            markings,
+            predecessors: Predecessors(predecessors),
+            frontier,
+        })
+    }
+    /// Save the checkpoint to a file.
+    /// The checkpoint is written to a temporary file first, so that an interrupted save never destroys the previous checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let file = File::create(&temporary).map(BufWriter::new).map_err(Error::IO)?;
+        self.write_to(file).map_err(Error::IO)?;
+        rename(&temporary, path).map_err(Error::IO)
+    }
+    /// Load a checkpoint from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).map(BufReader::new).map_err(Error::IO)?;
+        Checkpoint::read_from(file)
+    }
+}
+
+/// The lines of a checkpoint, numbered for error messages
+struct Lines<L>(std::iter::Enumerate<L>);
+
+impl<L: Iterator<Item = std::io::Result<String>>> Lines<L> {
+    /// Get the next line and its number, failing if the input ended prematurely
+    fn next_line(&mut self) -> Result<(usize, String), Error> {
+        match self.0.next() {
+            Some((number, line)) => Ok((number + 1, line.map_err(Error::IO)?)),
+            None => Err(Error::InvalidCheckpoint("unexpected end of file".to_string())),
+        }
+    }
+    /// Get the value of the next line, which must start with the given keyword
+    fn field(&mut self, keyword: &str) -> Result<(usize, String), Error> {
+        let (number, line) = self.next_line()?;
+        match line.strip_prefix(keyword) {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => Ok((number, rest.trim_start().to_string())),
+            _ => Err(Error::InvalidCheckpoint(format!("line {}: expected {}", number, keyword))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, ECNet, Pnml};
+
+    #[test]
+    fn test_resume() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let complete = net.reachability_analysis();
+
+        // Explore a few markings, save a checkpoint, and resume from it
+        let mut exploration = net.explore();
+        let mut rows: Vec<_> = exploration.by_ref().take(2).collect();
+        let mut buffer = Vec::new();
+        exploration.checkpoint().write_to(&mut buffer)?;
+        let checkpoint = Checkpoint::read_from(buffer.as_slice())?;
+
+        // Checkpoints of the previous version of the format are rejected
+        let outdated = String::from_utf8(buffer)?.replacen(HEADER, "petri-nets-checkpoint 1", 1);
+        let error = Checkpoint::read_from(outdated.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid checkpoint: line 1: unsupported checkpoint version 1");
+
+        let mut resumed = net.resume(checkpoint)?;
+        rows.extend(resumed.by_ref());
+        let resumed = resumed.into_analysis(rows);
+
+        // The resumed analysis is indistinguishable from an uninterrupted one
+        assert_eq!(resumed.rows, complete.rows);
+        assert_eq!(format!("{:?}", resumed.boundedness), format!("{:?}", complete.boundedness));
+        assert_eq!(format!("{:?}", resumed.liveness), format!("{:?}", complete.liveness));
+        assert_eq!(resumed.to_string(), complete.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! This module detects conflicts between transitions, violations of persistence, and confusion.
+//!
+//! - Two transitions are in structural conflict if they share an input place.
+//! - Two transitions are in (behavioral) conflict in a marking if both are enabled, but not concurrently,
+//!   so firing one of them is a choice against the other.
+//! - A net is persistent if firing an enabled transition never disables another enabled transition.
+//!   Persistent nets are free of choices, which is the usual requirement for speed-independent circuits.
+//! - A marking is confused if firing a transition changes the set of transitions
+//!   another concurrently enabled transition is in conflict with.
+//!   How the conflict is resolved then depends on the order in which independent transitions fire.
+//!
+//! Every behavioral finding comes with the first marking (in breadth-first order) in which it occurs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::reachability::TransitionIO;
+use super::{Arc, CapacityFn, Continuation, Marking, MarkingId, PetriNet, PlaceId, ReachabilityAnalysis, TransitionId, WeightFn};
+
+/// Two transitions which share input places
+/// Displayed as e.g. T1 # T2 on P1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuralConflict {
+    pub transitions: (TransitionId, TransitionId),
+    pub places: Vec<PlaceId>,
+}
+
+impl Display for StructuralConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} # {} on ", self.transitions.0, self.transitions.1)?;
+        let mut separator = "";
+        for place in &self.places {
+            write!(f, "{}{}", separator, place)?;
+            separator = ", ";
+        }
+        Ok(())
+    }
+}
+
+/// Two transitions which are both enabled in a marking, but cannot fire concurrently
+/// Displayed as e.g. T1 # T2 in M001
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub transitions: (TransitionId, TransitionId),
+    pub marking: MarkingId,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} # {} in {}", self.transitions.0, self.transitions.1, self.marking)
+    }
+}
+
+/// A transition which disables another transition by firing
+/// Displayed as e.g. T1 disables T2 in M001
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistenceViolation {
+    pub fired: TransitionId,
+    pub disabled: TransitionId,
+    pub marking: MarkingId,
+}
+
+impl Display for PersistenceViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} disables {} in {}", self.fired, self.disabled, self.marking)
+    }
+}
+
+/// A transition whose conflicts change when a concurrently enabled transition fires
+/// Displayed as e.g. T0 changes the conflicts of T1 in M000 from {} to {T2}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confusion {
+    pub fired: TransitionId,
+    pub transition: TransitionId,
+    pub marking: MarkingId,
+    /// The transitions in conflict with `transition` before `fired` fires
+    pub before: Vec<TransitionId>,
+    /// The transitions in conflict with `transition` after `fired` fired
+    pub after: Vec<TransitionId>,
+}
+
+impl Display for Confusion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        struct Set<'a>(&'a [TransitionId]);
+        impl Display for Set<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "{{")?;
+                let mut separator = "";
+                for transition in self.0 {
+                    write!(f, "{}{}", separator, transition)?;
+                    separator = ", ";
+                }
+                write!(f, "}}")
+            }
+        }
+        write!(
+            f,
+            "{} changes the conflicts of {} in {} from {} to {}",
+            self.fired,
+            self.transition,
+            self.marking,
+            Set(&self.before),
+            Set(&self.after)
+        )
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Find all pairs of transitions which share input places
+    pub fn structural_conflicts(&self) -> Vec<StructuralConflict> {
+        let mut shared: BTreeMap<(TransitionId, TransitionId), Vec<PlaceId>> = BTreeMap::new();
+        for place in &self.places {
+            let consumers: Vec<TransitionId> = self.arcs
+                .iter()
+                .filter_map(|arc| match *arc {
+                    Arc::PlaceTransition(source, target) if source == place.id => Some(target),
+                    _ => None,
+                })
+                .collect();
+            for (i, &a) in consumers.iter().enumerate() {
+                for &b in &consumers[i + 1..] {
+                    shared.entry((a.min(b), a.max(b))).or_default().push(place.id);
+                }
+            }
+        }
+        shared
+            .into_iter()
+            .map(|(transitions, places)| StructuralConflict { transitions, places })
+            .collect()
+    }
+    /// The transitions which are enabled in the marking but cannot fire concurrently with the given one
+    fn conflict_set(&self, transition_io: &[TransitionIO], transition: TransitionId, enabled: &[TransitionId], marking: &Marking) -> Vec<TransitionId> {
+        enabled
+            .iter()
+            .copied()
+            .filter(|&other| other != transition && !self.concurrently_enabled(transition_io, transition, other, marking))
+            .collect()
+    }
+}
+
+/// The explored rows of a reachability analysis, looked up by the ID of their marking
+type Rows<'a> = HashMap<MarkingId, (&'a Marking, Vec<TransitionId>)>;
+
+impl<C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'_, C, W> {
+    fn rows_by_id(&self) -> Rows<'_> {
+        self.rows
+            .iter()
+            .map(|(id, marking, continuations)| {
+                let enabled = continuations.iter().map(|continuation| continuation.0).collect();
+                (*id, (marking, enabled))
+            })
+            .collect()
+    }
+    /// Find all pairs of transitions which are in conflict in some marking, each with the first such marking
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let transition_io = self.petri_net.transition_io();
+        let mut conflicts = BTreeMap::new();
+        for (id, marking, continuations) in &self.rows {
+            for (i, &Continuation(a, _)) in continuations.iter().enumerate() {
+                for &Continuation(b, _) in &continuations[i + 1..] {
+                    let pair = (a.min(b), a.max(b));
+                    if !conflicts.contains_key(&pair) && !self.petri_net.concurrently_enabled(&transition_io, a, b, marking) {
+                        conflicts.insert(pair, *id);
+                    }
+                }
+            }
+        }
+        conflicts
+            .into_iter()
+            .map(|(transitions, marking)| Conflict { transitions, marking })
+            .collect()
+    }
+    /// Find all transitions which disable another enabled transition by firing,
+    /// each with the first marking in which they do
+    pub fn persistence_violations(&self) -> Vec<PersistenceViolation> {
+        let rows = self.rows_by_id();
+        let mut violations = BTreeMap::new();
+        for (id, _, continuations) in &self.rows {
+            for &Continuation(fired, target) in continuations {
+                // Markings which were never explored (e.g. in an aborted analysis) cannot be judged
+                let Some((_, enabled_after)) = rows.get(&target) else { continue };
+                for &Continuation(other, _) in continuations {
+                    if other != fired && !enabled_after.contains(&other) {
+                        violations.entry((fired, other)).or_insert(*id);
+                    }
+                }
+            }
+        }
+        violations
+            .into_iter()
+            .map(|((fired, disabled), marking)| PersistenceViolation { fired, disabled, marking })
+            .collect()
+    }
+    /// Returns true if no transition can ever disable another enabled transition by firing
+    pub fn is_persistent(&self) -> bool {
+        self.persistence_violations().is_empty()
+    }
+    /// Find all pairs of concurrently enabled transitions where firing one changes the conflicts of the other,
+    /// each with the first marking in which this happens
+    pub fn confusions(&self) -> Vec<Confusion> {
+        let net = self.petri_net;
+        let transition_io = net.transition_io();
+        let rows = self.rows_by_id();
+        let mut confusions: BTreeMap<(TransitionId, TransitionId), Confusion> = BTreeMap::new();
+        for (id, marking, continuations) in &self.rows {
+            let enabled: Vec<TransitionId> = continuations.iter().map(|continuation| continuation.0).collect();
+            for &Continuation(fired, target) in continuations {
+                let Some((marking_after, enabled_after)) = rows.get(&target) else { continue };
+                for &transition in &enabled {
+                    if transition == fired
+                        || confusions.contains_key(&(fired, transition))
+                        || !net.concurrently_enabled(&transition_io, fired, transition, marking)
+                    {
+                        continue;
+                    }
+                    let before = net.conflict_set(&transition_io, transition, &enabled, marking);
+                    let after = net.conflict_set(&transition_io, transition, enabled_after, marking_after);
+                    if before != after {
+                        let confusion = Confusion { fired, transition, marking: *id, before, after };
+                        confusions.insert((fired, transition), confusion);
+                    }
+                }
+            }
+        }
+        confusions.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Display;
+    use crate::{parse_xml, PTNet, Pnml};
+
+    fn to_strings<T: Display>(items: Vec<T>) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let analysis = net.reachability_analysis();
+        assert!(net.structural_conflicts().is_empty());
+        assert!(analysis.conflicts().is_empty());
+        assert!(analysis.is_persistent());
+        assert!(analysis.confusions().is_empty());
+
+        // Preparing does not conflict with taking, but enables a use which does
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let analysis = net.reachability_analysis();
+        assert_eq!(to_strings(net.structural_conflicts()), ["T1 # T2 on P1"]);
+        assert_eq!(to_strings(analysis.conflicts()), ["T1 # T2 in M001"]);
+        assert_eq!(to_strings(analysis.persistence_violations()), ["T1 disables T2 in M001", "T2 disables T1 in M001"]);
+        assert_eq!(to_strings(analysis.confusions()), ["T0 changes the conflicts of T1 in M000 from {} to {T2}"]);
+        Ok(())
+    }
+}
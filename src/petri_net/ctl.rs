@@ -0,0 +1,379 @@
+//! This module checks Computation Tree Logic (CTL) formulas against the reachability graph of a Petri net.
+//!
+//! The checker uses the classic labeling algorithm: for every subformula, the set of markings
+//! in which it holds is computed bottom-up, using backward fixpoint iterations for the temporal operators.
+//! Paths are maximal, so a deadlocked marking ends a path instead of having a successor.
+//! This means that `EX f` is false and `AX f` is true in a deadlock, while `EG f` and `AF f` reduce to `f`.
+//!
+//! If the result of a check can be demonstrated by a single path, a witness (for true existential formulas)
+//! or a counterexample (for false universal formulas) is returned as a trace from the initial marking.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::proposition::Proposition;
+use super::{CapacityFn, Continuation, Marking, MarkingId, PetriNet, ReachabilityAnalysis, Trace, TransitionId, WeightFn};
+
+/// A CTL formula over atomic propositions about markings
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ctl {
+    True,
+    False,
+    Atom(Proposition),
+    Not(Box<Ctl>),
+    And(Box<Ctl>, Box<Ctl>),
+    Or(Box<Ctl>, Box<Ctl>),
+    Implies(Box<Ctl>, Box<Ctl>),
+    /// The formula holds in some successor
+    EX(Box<Ctl>),
+    /// The formula holds in all successors
+    AX(Box<Ctl>),
+    /// The formula eventually holds on some path
+    EF(Box<Ctl>),
+    /// The formula eventually holds on all paths
+    AF(Box<Ctl>),
+    /// The formula holds globally on some path
+    EG(Box<Ctl>),
+    /// The formula holds globally on all paths
+    AG(Box<Ctl>),
+    /// On some path, the first formula holds until the second one does
+    EU(Box<Ctl>, Box<Ctl>),
+    /// On all paths, the first formula holds until the second one does
+    AU(Box<Ctl>, Box<Ctl>),
+}
+
+/// Formulas are displayed in the syntax of the query language, e.g. AG (EF (P3 >= 1))
+impl Display for Ctl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Ctl::True => write!(f, "true"),
+            Ctl::False => write!(f, "false"),
+            Ctl::Atom(proposition) => write!(f, "{}", proposition),
+            Ctl::Not(inner) => write!(f, "!({})", inner),
+            Ctl::And(left, right) => write!(f, "({} && {})", left, right),
+            Ctl::Or(left, right) => write!(f, "({} || {})", left, right),
+            Ctl::Implies(left, right) => write!(f, "({} -> {})", left, right),
+            Ctl::EX(inner) => write!(f, "EX ({})", inner),
+            Ctl::AX(inner) => write!(f, "AX ({})", inner),
+            Ctl::EF(inner) => write!(f, "EF ({})", inner),
+            Ctl::AF(inner) => write!(f, "AF ({})", inner),
+            Ctl::EG(inner) => write!(f, "EG ({})", inner),
+            Ctl::AG(inner) => write!(f, "AG ({})", inner),
+            Ctl::EU(left, right) => write!(f, "E[{} U {}]", left, right),
+            Ctl::AU(left, right) => write!(f, "A[{} U {}]", left, right),
+        }
+    }
+}
+
+/// The result of checking a CTL formula in the initial marking
+#[derive(Debug, Clone)]
+pub struct CtlResult {
+    /// Whether the formula holds in the initial marking
+    pub holds: bool,
+    /// A witness if the formula holds, or a counterexample if it does not, where a single path suffices
+    pub trace: Option<Trace>,
+}
+
+impl Display for CtlResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match (self.holds, &self.trace) {
+            (true, Some(trace)) => write!(f, "true (witness: {})", trace),
+            (false, Some(trace)) => write!(f, "false (counterexample: {})", trace),
+            (holds, None) => write!(f, "{}", holds),
+        }
+    }
+}
+
+/// The reachability graph in a form suitable for the labeling algorithm.
+/// Markings are referred to by their index in the rows of the reachability analysis.
+struct Graph<'a> {
+    ids: Vec<MarkingId>,
+    markings: Vec<&'a Marking>,
+    enabled: Vec<Vec<TransitionId>>,
+    /// The outgoing edges of every marking as (transition, target index) pairs
+    successors: Vec<Vec<(TransitionId, usize)>>,
+    /// The sources of the incoming edges of every marking, one entry per edge
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl<'a> Graph<'a> {
+    fn new<C: CapacityFn, W: WeightFn>(analysis: &'a ReachabilityAnalysis<'_, C, W>) -> Self {
+        let index: HashMap<MarkingId, usize> = analysis.rows
+            .iter()
+            .enumerate()
+            .map(|(index, (id, _, _))| (*id, index))
+            .collect();
+        let mut predecessors = vec![Vec::new(); analysis.rows.len()];
+        let mut successors = Vec::with_capacity(analysis.rows.len());
+        for (source, (_, _, continuations)) in analysis.rows.iter().enumerate() {
+            // Edges into markings which were never explored (e.g. in an aborted analysis) are left out
+            let edges: Vec<_> = continuations
+                .iter()
+                .filter_map(|Continuation(transition, target)| Some((*transition, *index.get(target)?)))
+                .collect();
+            for &(_, target) in &edges {
+                predecessors[target].push(source);
+            }
+            successors.push(edges);
+        }
+        Graph {
+            ids: analysis.rows.iter().map(|(id, _, _)| *id).collect(),
+            markings: analysis.rows.iter().map(|(_, marking, _)| marking).collect(),
+            enabled: analysis.rows
+                .iter()
+                .map(|(_, _, continuations)| continuations.iter().map(|continuation| continuation.0).collect())
+                .collect(),
+            successors,
+            predecessors,
+        }
+    }
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+    fn is_deadlock(&self, state: usize) -> bool {
+        self.successors[state].is_empty()
+    }
+    /// Compute the set of markings in which the formula holds
+    fn satisfying(&self, formula: &Ctl) -> Vec<bool> {
+        let not = |set: Vec<bool>| set.into_iter().map(|b| !b).collect::<Vec<_>>();
+        let zip = |a: Vec<bool>, b: Vec<bool>, op: fn(bool, bool) -> bool| {
+            a.into_iter().zip(b).map(|(a, b)| op(a, b)).collect::<Vec<_>>()
+        };
+        match formula {
+            Ctl::True => vec![true; self.len()],
+            Ctl::False => vec![false; self.len()],
+            Ctl::Atom(proposition) => (0..self.len())
+                .map(|state| proposition.holds(self.markings[state], &self.enabled[state]))
+                .collect(),
+            Ctl::Not(inner) => not(self.satisfying(inner)),
+            Ctl::And(left, right) => zip(self.satisfying(left), self.satisfying(right), |a, b| a && b),
+            Ctl::Or(left, right) => zip(self.satisfying(left), self.satisfying(right), |a, b| a || b),
+            Ctl::Implies(left, right) => zip(self.satisfying(left), self.satisfying(right), |a, b| !a || b),
+            Ctl::EX(inner) => {
+                let inner = self.satisfying(inner);
+                (0..self.len())
+                    .map(|state| self.successors[state].iter().any(|&(_, target)| inner[target]))
+                    .collect()
+            }
+            Ctl::AX(inner) => {
+                let inner = self.satisfying(inner);
+                (0..self.len())
+                    .map(|state| self.successors[state].iter().all(|&(_, target)| inner[target]))
+                    .collect()
+            }
+            Ctl::EF(inner) => self.until(&vec![true; self.len()], self.satisfying(inner)),
+            Ctl::AG(inner) => not(self.until(&vec![true; self.len()], not(self.satisfying(inner)))),
+            Ctl::EG(inner) => self.globally(self.satisfying(inner)),
+            Ctl::AF(inner) => not(self.globally(not(self.satisfying(inner)))),
+            Ctl::EU(left, right) => self.until(&self.satisfying(left), self.satisfying(right)),
+            Ctl::AU(left, right) => {
+                // A[f U g] = !E[!g U (!f && !g)] && !EG !g
+                let not_left = not(self.satisfying(left));
+                let not_right = not(self.satisfying(right));
+                let neither = zip(not_left, not_right.clone(), |a, b| a && b);
+                let escape = self.until(&not_right, neither);
+                let never = self.globally(not_right);
+                zip(escape, never, |a, b| !a && !b)
+            }
+        }
+    }
+    /// Compute E[left U right] by searching backwards from the markings satisfying `right`
+    fn until(&self, left: &[bool], right: Vec<bool>) -> Vec<bool> {
+        let mut result = right;
+        let mut worklist: Vec<usize> = (0..self.len()).filter(|&state| result[state]).collect();
+        while let Some(state) = worklist.pop() {
+            for &predecessor in &self.predecessors[state] {
+                if !result[predecessor] && left[predecessor] {
+                    result[predecessor] = true;
+                    worklist.push(predecessor);
+                }
+            }
+        }
+        result
+    }
+    /// Compute EG inner as the greatest set of `inner` markings which are deadlocks or have a successor in the set
+    fn globally(&self, inner: Vec<bool>) -> Vec<bool> {
+        let mut result = inner;
+        // Count the edges of every marking which stay inside the set
+        let mut inside: Vec<usize> = (0..self.len())
+            .map(|state| self.successors[state].iter().filter(|&&(_, target)| result[target]).count())
+            .collect();
+        let mut worklist: Vec<usize> = (0..self.len())
+            .filter(|&state| result[state] && !self.is_deadlock(state) && inside[state] == 0)
+            .collect();
+        for &state in &worklist {
+            result[state] = false;
+        }
+        while let Some(state) = worklist.pop() {
+            for &predecessor in &self.predecessors[state] {
+                inside[predecessor] -= 1;
+                if result[predecessor] && inside[predecessor] == 0 {
+                    result[predecessor] = false;
+                    worklist.push(predecessor);
+                }
+            }
+        }
+        result
+    }
+    /// Find a shortest path from `start` to a marking in `target` which only passes through markings in `allowed`
+    fn path(&self, start: usize, allowed: &[bool], target: &[bool]) -> Option<(Vec<Continuation>, usize)> {
+        let mut parents: HashMap<usize, (usize, TransitionId)> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        let mut end = None;
+        while let Some(state) = queue.pop_front() {
+            if target[state] {
+                end = Some(state);
+                break;
+            }
+            if !allowed[state] {
+                continue;
+            }
+            for &(transition, successor) in &self.successors[state] {
+                if successor != start && !parents.contains_key(&successor) {
+                    parents.insert(successor, (state, transition));
+                    queue.push_back(successor);
+                }
+            }
+        }
+        let end = end?;
+        let mut steps = Vec::new();
+        let mut state = end;
+        while let Some(&(parent, transition)) = parents.get(&state) {
+            steps.push(Continuation(transition, self.ids[state]));
+            state = parent;
+        }
+        steps.reverse();
+        Some((steps, end))
+    }
+    /// Follow markings of an EG set from `start` until a marking repeats or a deadlock is reached
+    fn lasso(&self, start: usize, set: &[bool]) -> Trace {
+        let mut visited = HashMap::from([(start, 0)]);
+        let mut steps = Vec::new();
+        let mut state = start;
+        while let Some(&(transition, successor)) = self.successors[state].iter().find(|&&(_, target)| set[target]) {
+            steps.push(Continuation(transition, self.ids[successor]));
+            if let Some(&loop_start) = visited.get(&successor) {
+                return Trace { start: self.ids[start], steps, loop_start: Some(loop_start) };
+            }
+            visited.insert(successor, steps.len());
+            state = successor;
+        }
+        Trace { start: self.ids[start], steps, loop_start: None }
+    }
+    /// Produce a path starting in `state` which demonstrates that the formula evaluates to `expected` there,
+    /// if such a path exists and the formula can be demonstrated by a single path
+    fn trace(&self, formula: &Ctl, state: usize, expected: bool) -> Option<Trace> {
+        let not = |set: Vec<bool>| set.into_iter().map(|b| !b).collect::<Vec<_>>();
+        match (formula, expected) {
+            (Ctl::Not(inner), _) => self.trace(inner, state, !expected),
+            (Ctl::EX(inner), true) | (Ctl::AX(inner), false) => {
+                let inner_set = self.satisfying(inner);
+                let &(transition, successor) = self.successors[state]
+                    .iter()
+                    .find(|&&(_, target)| inner_set[target] == expected)?;
+                let step = Trace { start: self.ids[state], steps: vec![Continuation(transition, self.ids[successor])], loop_start: None };
+                Some(self.extend(step, inner, successor, expected))
+            }
+            (Ctl::EF(inner), true) | (Ctl::AG(inner), false) => {
+                let inner_set = self.satisfying(inner);
+                let target = if expected { inner_set } else { not(inner_set) };
+                self.finite_trace(state, &vec![true; self.len()], &target, inner, expected)
+            }
+            (Ctl::EU(left, right), true) => {
+                let target = self.satisfying(right);
+                self.finite_trace(state, &self.satisfying(left), &target, right, true)
+            }
+            (Ctl::EG(inner), true) => Some(self.lasso(state, &self.globally(self.satisfying(inner)))),
+            (Ctl::AF(inner), false) => Some(self.lasso(state, &self.globally(not(self.satisfying(inner))))),
+            (Ctl::AU(left, right), false) => {
+                let never = self.globally(not(self.satisfying(right)));
+                if never[state] {
+                    // The right formula never holds on some path
+                    Some(self.lasso(state, &never))
+                } else {
+                    // Some path violates the left formula before the right one holds
+                    let not_right = not(self.satisfying(right));
+                    let neither = not(self.satisfying(&Ctl::Or(left.clone(), right.clone())));
+                    self.finite_trace(state, &not_right, &neither, left, false)
+                }
+            }
+            _ => None,
+        }
+    }
+    /// Find a path to a target marking and extend it with a trace for the subformula that holds there
+    fn finite_trace(&self, state: usize, allowed: &[bool], target: &[bool], formula: &Ctl, expected: bool) -> Option<Trace> {
+        let (steps, end) = self.path(state, allowed, target)?;
+        let trace = Trace { start: self.ids[state], steps, loop_start: None };
+        Some(self.extend(trace, formula, end, expected))
+    }
+    /// Append a trace for the subformula in the last marking of the trace, if there is one
+    fn extend(&self, mut trace: Trace, formula: &Ctl, end: usize, expected: bool) -> Trace {
+        if let Some(suffix) = self.trace(formula, end, expected) {
+            trace.loop_start = suffix.loop_start.map(|loop_start| loop_start + trace.steps.len());
+            trace.steps.extend(suffix.steps);
+        }
+        trace
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'_, C, W> {
+    /// Check whether the CTL formula holds in the initial marking.
+    /// The analysis should cover the whole reachability graph, otherwise the result only describes the explored part.
+    pub fn check_ctl(&self, formula: &Ctl) -> CtlResult {
+        let graph = Graph::new(self);
+        if graph.len() == 0 {
+            return CtlResult { holds: false, trace: None };
+        }
+        let holds = graph.satisfying(formula)[0];
+        let trace = graph.trace(formula, 0, holds);
+        CtlResult { holds, trace }
+    }
+    /// Returns the IDs of all markings in which the CTL formula holds
+    pub fn satisfying(&self, formula: &Ctl) -> Vec<MarkingId> {
+        let graph = Graph::new(self);
+        graph.satisfying(formula)
+            .into_iter()
+            .zip(&graph.ids)
+            .filter_map(|(holds, &id)| holds.then_some(id))
+            .collect()
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Check whether the CTL formula holds in the initial marking, exploring the whole reachability graph
+    pub fn check_ctl(&self, formula: &Ctl) -> CtlResult {
+        self.reachability_analysis().check_ctl(formula)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::proposition::{Comparison, Expression};
+    use crate::petri_net::parallel_net;
+
+    fn tokens(place: &str, comparison: Comparison, constant: usize) -> Box<Ctl> {
+        let place = place.parse().unwrap();
+        Box::new(Ctl::Atom(Proposition::Compare(Expression::Tokens(vec![place]), comparison, Expression::Constant(constant))))
+    }
+
+    #[test]
+    fn test_check_ctl() {
+        let net = parallel_net();
+        // The joined place can always be reached again
+        let result = net.check_ctl(&Ctl::AG(Box::new(Ctl::EF(tokens("P5", Comparison::Equal, 1)))));
+        assert!(result.holds);
+        // Both branches are never active at once with the join place
+        let result = net.check_ctl(&Ctl::AG(Box::new(Ctl::Or(tokens("P1", Comparison::Equal, 0), tokens("P5", Comparison::Equal, 0)))));
+        assert!(result.holds);
+        // The left branch does not always finish first, and the counterexample shows why
+        let result = net.check_ctl(&Ctl::AG(Box::new(Ctl::Implies(tokens("P4", Comparison::Equal, 1), tokens("P3", Comparison::Equal, 1)))));
+        assert!(!result.holds);
+        let trace = result.trace.expect("AG should have a counterexample");
+        assert_eq!(trace.firing_sequence().map(|t| t.to_string()).collect::<Vec<_>>(), ["T0", "T2"]);
+        // There is an infinite path which keeps cycling
+        let result = net.check_ctl(&Ctl::EG(Box::new(Ctl::True)));
+        assert!(result.holds);
+        assert!(result.trace.expect("EG should have a witness").loop_start.is_some());
+    }
+}
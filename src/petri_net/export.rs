@@ -0,0 +1,220 @@
+//! This module writes the reachability graph of an analysis in formats which other tools can read:
+//!
+//! - Graphviz DOT, for rendering the state space. The initial marking is drawn with a double border
+//!   and deadlocks are drawn in red.
+//! - GraphML, for graph editors and libraries such as yEd, Gephi or NetworkX.
+//! - JSON, for post-processing in scripts.
+//!
+//! Every format contains one node per marking and one edge per continuation.
+//! If the analysis was aborted, the markings which were discovered but not explored yet
+//! are included without their tokens, and are marked as unexplored.
+
+use std::collections::BTreeSet;
+use std::io::{Result as IoResult, Write};
+
+use quick_xml::escape::escape;
+
+use super::{CapacityFn, Continuation, Marking, MarkingId, ReachabilityAnalysis, WeightFn};
+
+impl<C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'_, C, W> {
+    /// The markings which were discovered as the target of a continuation, but never explored
+    fn unexplored(&self) -> BTreeSet<MarkingId> {
+        let explored: BTreeSet<MarkingId> = self.rows.iter().map(|(id, _, _)| *id).collect();
+        self.rows
+            .iter()
+            .flat_map(|(_, _, continuations)| continuations.iter().map(|continuation| continuation.1))
+            .filter(|id| !explored.contains(id))
+            .collect()
+    }
+    /// Describe the tokens of a marking as e.g. P0: 1, P3: 2
+    fn describe(marking: &Marking) -> String {
+        let tokens: Vec<String> = marking.iter().map(|(place, tokens)| format!("{}: {}", place, tokens)).collect();
+        tokens.join(", ")
+    }
+    /// Write the reachability graph in the Graphviz DOT format
+    pub fn write_dot<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let net = self.petri_net;
+        writeln!(out, "digraph \"{}\" {{", escape_dot(&net.id))?;
+        writeln!(out, "    node [shape=box];")?;
+        for (id, marking, continuations) in &self.rows {
+            let mut attributes = vec![format!("label=\"{}\\n{}\"", id, escape_dot(&Self::describe(marking)))];
+            if id.0 == 0 {
+                attributes.push("peripheries=2".to_string());
+            }
+            if continuations.is_empty() {
+                attributes.push("color=red".to_string());
+                attributes.push("fontcolor=red".to_string());
+            }
+            writeln!(out, "    {} [{}];", id, attributes.join(", "))?;
+        }
+        for id in self.unexplored() {
+            writeln!(out, "    {} [label=\"{}\", style=dashed];", id, id)?;
+        }
+        for (id, _, continuations) in &self.rows {
+            for &Continuation(transition, target) in continuations {
+                let name = &net.transitions[transition.0].name;
+                writeln!(out, "    {} -> {} [label=\"{} {}\"];", id, target, transition, escape_dot(name))?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+    /// Write the reachability graph in the GraphML format
+    pub fn write_graphml<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let net = self.petri_net;
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(out, r#"  <key id="marking" for="node" attr.name="marking" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <key id="initial" for="node" attr.name="initial" attr.type="boolean"><default>false</default></key>"#)?;
+        writeln!(out, r#"  <key id="deadlock" for="node" attr.name="deadlock" attr.type="boolean"><default>false</default></key>"#)?;
+        writeln!(out, r#"  <key id="explored" for="node" attr.name="explored" attr.type="boolean"><default>true</default></key>"#)?;
+        writeln!(out, r#"  <key id="transition" for="edge" attr.name="transition" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <key id="name" for="edge" attr.name="name" attr.type="string"/>"#)?;
+        writeln!(out, r#"  <graph id="{}" edgedefault="directed">"#, escape(&net.id))?;
+        for (id, marking, continuations) in &self.rows {
+            writeln!(out, r#"    <node id="{}">"#, id)?;
+            writeln!(out, r#"      <data key="marking">{}</data>"#, escape(&Self::describe(marking)))?;
+            if id.0 == 0 {
+                writeln!(out, r#"      <data key="initial">true</data>"#)?;
+            }
+            if continuations.is_empty() {
+                writeln!(out, r#"      <data key="deadlock">true</data>"#)?;
+            }
+            writeln!(out, r#"    </node>"#)?;
+        }
+        for id in self.unexplored() {
+            writeln!(out, r#"    <node id="{}">"#, id)?;
+            writeln!(out, r#"      <data key="explored">false</data>"#)?;
+            writeln!(out, r#"    </node>"#)?;
+        }
+        for (id, _, continuations) in &self.rows {
+            for &Continuation(transition, target) in continuations {
+                writeln!(out, r#"    <edge source="{}" target="{}">"#, id, target)?;
+                writeln!(out, r#"      <data key="transition">{}</data>"#, transition)?;
+                writeln!(out, r#"      <data key="name">{}</data>"#, escape(&net.transitions[transition.0].name))?;
+                writeln!(out, r#"    </edge>"#)?;
+            }
+        }
+        writeln!(out, r#"  </graph>"#)?;
+        writeln!(out, r#"</graphml>"#)
+    }
+    /// Write the reachability graph as a JSON document of the form
+    /// {"net": ..., "places": [...], "transitions": [...], "markings": [...], "edges": [...]}
+    pub fn write_json<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let net = self.petri_net;
+        writeln!(out, "{{")?;
+        writeln!(out, "  \"net\": {},", json_string(&net.id))?;
+        let places: Vec<String> = net.places
+            .iter()
+            .map(|place| format!("{{\"id\": \"{}\", \"name\": {}}}", place.id, json_string(&place.name)))
+            .collect();
+        writeln!(out, "  \"places\": [{}],", places.join(", "))?;
+        let transitions: Vec<String> = net.transitions
+            .iter()
+            .map(|transition| format!("{{\"id\": \"{}\", \"name\": {}}}", transition.id, json_string(&transition.name)))
+            .collect();
+        writeln!(out, "  \"transitions\": [{}],", transitions.join(", "))?;
+        let mut markings: Vec<String> = self.rows
+            .iter()
+            .map(|(id, marking, continuations)| {
+                let tokens: Vec<String> = marking.iter().map(|(place, tokens)| format!("\"{}\": {}", place, tokens)).collect();
+                format!(
+                    "{{\"id\": \"{}\", \"tokens\": {{{}}}, \"initial\": {}, \"deadlock\": {}, \"explored\": true}}",
+                    id,
+                    tokens.join(", "),
+                    id.0 == 0,
+                    continuations.is_empty()
+                )
+            })
+            .collect();
+        markings.extend(self.unexplored().into_iter().map(|id| {
+            format!("{{\"id\": \"{}\", \"tokens\": null, \"initial\": false, \"deadlock\": false, \"explored\": false}}", id)
+        }));
+        write_json_array(&mut out, "markings", &markings, ",")?;
+        let edges: Vec<String> = self.rows
+            .iter()
+            .flat_map(|(id, _, continuations)| {
+                continuations.iter().map(move |Continuation(transition, target)| {
+                    format!("{{\"source\": \"{}\", \"transition\": \"{}\", \"target\": \"{}\"}}", id, transition, target)
+                })
+            })
+            .collect();
+        write_json_array(&mut out, "edges", &edges, "")?;
+        writeln!(out, "}}")
+    }
+}
+
+/// Write a JSON array member with one element per line
+fn write_json_array<Out: Write>(out: &mut Out, key: &str, elements: &[String], terminator: &str) -> IoResult<()> {
+    if elements.is_empty() {
+        return writeln!(out, "  \"{}\": []{}", key, terminator);
+    }
+    writeln!(out, "  \"{}\": [", key)?;
+    for (index, element) in elements.iter().enumerate() {
+        let separator = if index + 1 < elements.len() { "," } else { "" };
+        writeln!(out, "    {}{}", element, separator)?;
+    }
+    writeln!(out, "  ]{}", terminator)
+}
+
+/// Quote a string for JSON, escaping quotes, backslashes and control characters
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            control if control.is_control() => quoted.push_str(&format!("\\u{:04x}", control as u32)),
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Escape a string for use inside a quoted DOT identifier or label
+pub(super) fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_export() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let analysis = net.reachability_analysis();
+
+        let mut dot = Vec::new();
+        analysis.write_dot(&mut dot)?;
+        let dot = String::from_utf8(dot)?;
+        assert!(dot.starts_with("digraph \""));
+        assert!(dot.contains("M000 [label=\"M000\\nP0: 1, P1: 1\", peripheries=2];"));
+        assert!(dot.contains("M000 -> M001 [label=\"T0 prepare\"];"));
+        assert!(dot.contains("color=red"));
+
+        let mut graphml = Vec::new();
+        analysis.write_graphml(&mut graphml)?;
+        let graphml = String::from_utf8(graphml)?;
+        assert_eq!(graphml.matches("<node ").count(), analysis.rows.len());
+        assert!(graphml.contains(r#"<data key="deadlock">true</data>"#));
+        assert!(graphml.contains(r#"<data key="name">use</data>"#));
+
+        let mut json = Vec::new();
+        analysis.write_json(&mut json)?;
+        let json = String::from_utf8(json)?;
+        assert!(json.contains(r#"{"id": "M000", "tokens": {"P0": 1, "P1": 1}, "initial": true, "deadlock": false, "explored": true}"#));
+        assert!(json.contains(r#"{"source": "M000", "transition": "T0", "target": "M001"}"#));
+        let edges: usize = analysis.rows.iter().map(|(_, _, continuations)| continuations.len()).sum();
+        assert_eq!(json.matches("\"source\"").count(), edges);
+
+        assert_eq!(json_string("a \"b\"\n\\"), r#""a \"b\"\n\\""#);
+        Ok(())
+    }
+}
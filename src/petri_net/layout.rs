@@ -0,0 +1,341 @@
+//! This module computes a layered (Sugiyama-style) layout of a Petri net,
+//! which draws the flow of tokens from left to right. It proceeds in the usual four phases:
+//!
+//! 1. Cycles are broken by reversing the arcs which lead back to a node on the current path of a depth-first search.
+//!    The search starts at the initially marked places, so the arcs which close loops are the ones reversed.
+//! 2. Every node is assigned to the layer after the last of its predecessors (longest path layering).
+//!    Arcs which span several layers are split into chains of dummy nodes, one per layer they pass.
+//! 3. The nodes within each layer are ordered by the barycenter heuristic, sweeping back and forth over the layers,
+//!    and the order with the fewest crossings is kept.
+//! 4. Layers are placed at a fixed distance from each other and centered vertically.
+//!    The dummy nodes become the bend points of their arcs.
+//!
+//! The layout is used to draw nets as SVG and for the graphics of PNML files.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::{Arc, CapacityFn, MarkingFn, PetriNet, PlaceId, TransitionId, WeightFn};
+
+/// The horizontal distance between two layers
+pub const LAYER_SPACING: f64 = 100.0;
+/// The vertical distance between two nodes of a layer
+pub const NODE_SPACING: f64 = 80.0;
+/// The margin around the drawing, which leaves room for the names of the nodes
+pub const MARGIN: f64 = 60.0;
+/// The number of sweeps of the crossing reduction
+const SWEEPS: usize = 12;
+
+/// A point of the drawing, in the coordinate system of SVG and PNML (the y axis points down)
+/// Displayed as (x, y)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// The positions of the centers of all places and transitions, and the bend points of the arcs
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    places: Vec<Position>,
+    transitions: Vec<Position>,
+    bends: HashMap<Arc, Vec<Position>>,
+    width: f64,
+    height: f64,
+}
+
+impl Layout {
+    /// The position of the center of a place
+    pub fn place(&self, place: PlaceId) -> Position {
+        self.places[place.0]
+    }
+    /// The position of the center of a transition
+    pub fn transition(&self, transition: TransitionId) -> Position {
+        self.transitions[transition.0]
+    }
+    /// The points an arc passes through between its source and target, in this order
+    pub fn bends(&self, arc: &Arc) -> &[Position] {
+        self.bends.get(arc).map_or(&[], Vec::as_slice)
+    }
+    /// The width of the drawing, including the margins
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+    /// The height of the drawing, including the margins
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+}
+
+/// The graph which is laid out: the places (0..P), the transitions (P..P+T), and then the dummy nodes
+struct Graph {
+    /// The successors of every node, along arcs which were reversed where necessary to make the graph acyclic
+    successors: Vec<Vec<usize>>,
+    /// The predecessors of every node, in the same acyclic graph
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    fn add_node(&mut self) -> usize {
+        self.successors.push(Vec::new());
+        self.predecessors.push(Vec::new());
+        self.successors.len() - 1
+    }
+    fn add_edge(&mut self, source: usize, target: usize) {
+        self.successors[source].push(target);
+        self.predecessors[target].push(source);
+    }
+}
+
+/// Find the edges which close cycles, by a depth-first search from the given nodes and then all others
+fn back_edges(successors: &[Vec<usize>], starts: &[usize]) -> Vec<(usize, usize)> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        OnPath,
+        Done,
+    }
+    let mut states = vec![State::New; successors.len()];
+    let mut back_edges = Vec::new();
+    for &start in starts.iter().chain(&(0..successors.len()).collect::<Vec<_>>()) {
+        if states[start] != State::New {
+            continue;
+        }
+        states[start] = State::OnPath;
+        // The nodes on the current path and the index of the next successor to visit from each
+        let mut path = vec![(start, 0)];
+        while let Some((node, next)) = path.last_mut() {
+            let node = *node;
+            match successors[node].get(*next) {
+                Some(&successor) => {
+                    *next += 1;
+                    match states[successor] {
+                        State::New => {
+                            states[successor] = State::OnPath;
+                            path.push((successor, 0));
+                        }
+                        State::OnPath => back_edges.push((node, successor)),
+                        State::Done => {}
+                    }
+                }
+                None => {
+                    states[node] = State::Done;
+                    path.pop();
+                }
+            }
+        }
+    }
+    back_edges
+}
+
+/// Count the crossings between the edges from one layer to the next
+fn crossings(graph: &Graph, upper: &[usize], lower: &[usize], positions: &[usize]) -> usize {
+    let edges: Vec<(usize, usize)> = upper
+        .iter()
+        .flat_map(|&node| graph.successors[node].iter().map(move |&successor| (positions[node], positions[successor])))
+        .collect();
+    debug_assert!(edges.iter().all(|&(_, target)| target < lower.len()));
+    let mut count = 0;
+    for (i, &(a_source, a_target)) in edges.iter().enumerate() {
+        for &(b_source, b_target) in &edges[i + 1..] {
+            if (a_source < b_source && a_target > b_target) || (a_source > b_source && a_target < b_target) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Reorder a layer by the average position of the neighbors of each node in the adjacent layer.
+/// Nodes without neighbors there keep their position.
+fn reorder(layer: &mut [usize], neighbors: &[Vec<usize>], positions: &mut [usize]) {
+    let mut keyed: Vec<(f64, usize)> = layer
+        .iter()
+        .map(|&node| {
+            let adjacent = &neighbors[node];
+            let key = match adjacent.is_empty() {
+                true => positions[node] as f64,
+                false => adjacent.iter().map(|&neighbor| positions[neighbor] as f64).sum::<f64>() / adjacent.len() as f64,
+            };
+            (key, node)
+        })
+        .collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (index, (_, node)) in keyed.into_iter().enumerate() {
+        layer[index] = node;
+        positions[node] = index;
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Compute a layered layout of the net, which draws the flow of tokens from left to right
+    pub fn layout(&self) -> Layout {
+        let (places, transitions) = (self.places.len(), self.transitions.len());
+        let nodes = places + transitions;
+        let ends = |arc: &Arc| match *arc {
+            Arc::PlaceTransition(place, transition) => (place.0, places + transition.0),
+            Arc::TransitionPlace(transition, place) => (places + transition.0, place.0),
+        };
+
+        // Phase 1: Break the cycles
+        let mut successors = vec![Vec::new(); nodes];
+        for arc in &self.arcs {
+            let (source, target) = ends(arc);
+            successors[source].push(target);
+        }
+        let starts: Vec<usize> = self.places
+            .iter()
+            .filter(|place| self.initial_marking.get(&place.id).0 > 0)
+            .map(|place| place.id.0)
+            .collect();
+        let reversed = back_edges(&successors, &starts);
+        let oriented = |arc: &Arc| {
+            let (source, target) = ends(arc);
+            match reversed.contains(&(source, target)) {
+                true => (target, source),
+                false => (source, target),
+            }
+        };
+
+        // Phase 2: Assign the layers by longest paths, in topological order
+        let mut graph = Graph { successors: vec![Vec::new(); nodes], predecessors: vec![Vec::new(); nodes] };
+        for arc in &self.arcs {
+            let (source, target) = oriented(arc);
+            if source != target {
+                graph.add_edge(source, target);
+            }
+        }
+        let mut layer_of = vec![0; nodes];
+        let mut remaining: Vec<usize> = graph.predecessors.iter().map(Vec::len).collect();
+        let mut ready: Vec<usize> = (0..nodes).filter(|&node| remaining[node] == 0).collect();
+        while let Some(node) = ready.pop() {
+            for &successor in &graph.successors[node] {
+                layer_of[successor] = layer_of[successor].max(layer_of[node] + 1);
+                remaining[successor] -= 1;
+                if remaining[successor] == 0 {
+                    ready.push(successor);
+                }
+            }
+        }
+
+        // Split the arcs which span several layers into chains of dummy nodes
+        let mut chains: HashMap<Arc, Vec<usize>> = HashMap::new();
+        let mut split = Graph { successors: vec![Vec::new(); nodes], predecessors: vec![Vec::new(); nodes] };
+        for arc in &self.arcs {
+            let (source, target) = oriented(arc);
+            if source == target || chains.contains_key(arc) {
+                continue;
+            }
+            let mut chain = Vec::new();
+            let mut previous = source;
+            for layer in layer_of[source] + 1..layer_of[target] {
+                let dummy = split.add_node();
+                layer_of.push(layer);
+                split.add_edge(previous, dummy);
+                chain.push(dummy);
+                previous = dummy;
+            }
+            split.add_edge(previous, target);
+            if oriented(arc) != ends(arc) {
+                chain.reverse();
+            }
+            chains.insert(*arc, chain);
+        }
+        let graph = split;
+
+        // Phase 3: Order the nodes within the layers
+        let layer_count = layer_of.iter().max().map_or(0, |&max| max + 1);
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+        let mut positions = vec![0; layer_of.len()];
+        for (node, &layer) in layer_of.iter().enumerate() {
+            positions[node] = layers[layer].len();
+            layers[layer].push(node);
+        }
+        let total_crossings = |layers: &[Vec<usize>], positions: &[usize]| {
+            layers.windows(2).map(|pair| crossings(&graph, &pair[0], &pair[1], positions)).sum::<usize>()
+        };
+        let mut best = (total_crossings(&layers, &positions), layers.clone(), positions.clone());
+        for sweep in 0..SWEEPS {
+            if best.0 == 0 {
+                break;
+            }
+            if sweep % 2 == 0 {
+                for layer in layers.iter_mut().skip(1) {
+                    reorder(layer, &graph.predecessors, &mut positions);
+                }
+            } else {
+                for layer in layers.iter_mut().rev().skip(1) {
+                    reorder(layer, &graph.successors, &mut positions);
+                }
+            }
+            let count = total_crossings(&layers, &positions);
+            if count < best.0 {
+                best = (count, layers.clone(), positions.clone());
+            }
+        }
+        let (_, layers, positions) = best;
+
+        // Phase 4: Assign the coordinates
+        let tallest = layers.iter().map(Vec::len).max().unwrap_or(0);
+        let coordinates: Vec<Position> = layer_of
+            .iter()
+            .zip(&positions)
+            .map(|(&layer, &position)| {
+                let offset = (tallest - layers[layer].len()) as f64 * NODE_SPACING / 2.0;
+                Position {
+                    x: MARGIN + layer as f64 * LAYER_SPACING,
+                    y: MARGIN + offset + position as f64 * NODE_SPACING,
+                }
+            })
+            .collect();
+        Layout {
+            places: coordinates[..places].to_vec(),
+            transitions: coordinates[places..nodes].to_vec(),
+            bends: chains
+                .into_iter()
+                .map(|(arc, chain)| (arc, chain.into_iter().map(|dummy| coordinates[dummy]).collect()))
+                .collect(),
+            width: 2.0 * MARGIN + layer_count.saturating_sub(1) as f64 * LAYER_SPACING,
+            height: 2.0 * MARGIN + tallest.saturating_sub(1) as f64 * NODE_SPACING,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, Bpmn, ECNet, PTNet, Pnml};
+
+    #[test]
+    fn test_layout() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let layout = net.layout();
+        // The net flows from the marked place through the fork, the branches and the join,
+        // and the arc which closes the loop bends back to the start
+        let columns: Vec<f64> = (0..net.places.len()).map(|index| layout.place(PlaceId(index)).x).collect();
+        assert_eq!(columns, [60.0, 260.0, 260.0, 460.0, 460.0, 660.0]);
+        assert_eq!(layout.transition(TransitionId(0)).x, 160.0);
+        let closing = net.arcs.iter().find(|arc| matches!(arc, Arc::TransitionPlace(_, place) if place.0 == 0)).unwrap();
+        assert_eq!(layout.bends(closing).len(), 6);
+        assert!(layout.bends(closing).windows(2).all(|pair| pair[0].x > pair[1].x));
+        // The branches do not overlap
+        assert_ne!(layout.place(PlaceId(1)), layout.place(PlaceId(2)));
+        assert_eq!(layout.width(), 2.0 * MARGIN + 7.0 * LAYER_SPACING);
+
+        // Nets converted from BPMN get positions in their PNML graphics
+        let bpmn: Bpmn = parse_xml(include_str!("../../models/choice.bpmn").as_bytes())?;
+        let net: ECNet = bpmn.into();
+        let pnml: Pnml = net.into();
+        let xml = pnml.to_string();
+        assert_eq!(xml.matches("<graphics>").count(), xml.matches("<place ").count() + xml.matches("<transition ").count());
+        assert!(xml.contains(r#"<position x="60" y="#));
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+//! This module reads and writes nets in the textual format of [LoLA](https://theo.informatik.uni-rostock.de/theo-forschung/tools/lola/),
+//! so that the same models can be fed to both tools:
+//!
+//! ```text
+//! { net mutex }
+//! PLACE
+//!     idle,
+//!     busy,
+//!     mutex;
+//!
+//! MARKING
+//!     idle: 2,
+//!     mutex: 1;
+//!
+//! TRANSITION enter
+//!     CONSUME idle: 1, mutex: 1;
+//!     PRODUCE busy: 1;
+//! ```
+//!
+//! Comments are enclosed in braces or start with `//`. A comment `{ net <id> }` before the places gives the ID of the net.
+//! Places and transitions are named by their identifiers, which are kept as their original IDs,
+//! and a weight or number of tokens may be left out if it is 1.
+//!
+//! `SAFE` declarations only tell LoLA a bound it may rely on, so they are read but not interpreted,
+//! and capacities are not written. Fairness assumptions of transitions (`WEAK FAIR`, `STRONG FAIR`) are ignored.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Result as IoResult, Write};
+use std::iter::Peekable;
+use std::path::Path;
+
+use super::annotations::IdAllocator;
+use super::any::unsupported;
+use super::{
+    Annotations, Arc, Capacity, CapacityFn, Marking, MarkingFn, PetriNet, Place, PlaceId, Tokens, Transition,
+    TransitionId, Weight, WeightFn,
+};
+use crate::Error;
+
+const KEYWORDS: [&str; 9] = ["PLACE", "MARKING", "TRANSITION", "CONSUME", "PRODUCE", "SAFE", "STRONG", "WEAK", "FAIR"];
+
+/// Characters which end an identifier
+const DELIMITERS: &str = ",;:{}";
+
+/// Returns true if the text can be written as a LoLA identifier
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with("//")
+        && !text.chars().any(|c| c.is_whitespace() || DELIMITERS.contains(c))
+        && !KEYWORDS.contains(&text)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A keyword, identifier or number
+    Word(String),
+    /// Punctuation
+    Symbol(char),
+}
+
+/// The tokens of a LoLA file with their line numbers, and the ID of the net if a comment gave it
+struct Lexed {
+    tokens: Vec<(usize, Token)>,
+    id: Option<String>,
+}
+
+fn invalid(line: usize, message: impl AsRef<str>) -> Error {
+    Error::InvalidLola(format!("line {}: {}", line, message.as_ref()))
+}
+
+/// Split a LoLA file into tokens, dropping comments
+fn tokenize(text: &str) -> Result<Lexed, Error> {
+    let mut tokens = Vec::new();
+    let mut id = None;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '{' => {
+                let start = line;
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            comment.push(c);
+                        }
+                        None => return Err(invalid(start, "unterminated comment")),
+                    }
+                }
+                if tokens.is_empty() && id.is_none() {
+                    id = comment.trim().strip_prefix("net ").map(|id| id.trim().to_string());
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            ',' | ';' | ':' => tokens.push((line, Token::Symbol(c))),
+            '}' => return Err(invalid(line, "unexpected }")),
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && !DELIMITERS.contains(c)) {
+                    word.push(c);
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+        }
+    }
+    Ok(Lexed { tokens, id })
+}
+
+/// Returns true if the text looks like a LoLA file, i.e. starts with a PLACE section
+pub(crate) fn is_lola(text: &str) -> bool {
+    tokenize(text).is_ok_and(|tokens| matches!(tokens.tokens.first(), Some((_, Token::Word(word))) if word == "PLACE"))
+}
+
+/// Reads the tokens of a LoLA file
+struct Parser<I: Iterator<Item = (usize, Token)>> {
+    tokens: Peekable<I>,
+    /// The line of the last token, for errors at the end of the file
+    line: usize,
+}
+
+impl<I: Iterator<Item = (usize, Token)>> Parser<I> {
+    fn next(&mut self) -> Result<(usize, Token), Error> {
+        let (line, token) = self.tokens.next().ok_or_else(|| invalid(self.line, "unexpected end of file"))?;
+        self.line = line;
+        Ok((line, token))
+    }
+    /// Returns true if the next token is the given keyword
+    fn at_keyword(&mut self, keyword: &str) -> bool {
+        matches!(self.tokens.peek(), Some((_, Token::Word(word))) if word == keyword)
+    }
+    /// Returns true if the next token is the given symbol
+    fn at_symbol(&mut self, symbol: char) -> bool {
+        matches!(self.tokens.peek(), Some((_, Token::Symbol(c))) if *c == symbol)
+    }
+    fn keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        match self.next()? {
+            (_, Token::Word(word)) if word == keyword => Ok(()),
+            (line, _) => Err(invalid(line, format!("expected {}", keyword))),
+        }
+    }
+    fn symbol(&mut self, symbol: char) -> Result<(), Error> {
+        match self.next()? {
+            (_, Token::Symbol(c)) if c == symbol => Ok(()),
+            (line, _) => Err(invalid(line, format!("expected {}", symbol))),
+        }
+    }
+    fn identifier(&mut self) -> Result<(usize, String), Error> {
+        match self.next()? {
+            (line, Token::Word(word)) if !KEYWORDS.contains(&word.as_str()) => Ok((line, word)),
+            (line, _) => Err(invalid(line, "expected an identifier")),
+        }
+    }
+    fn number(&mut self) -> Result<usize, Error> {
+        match self.next()? {
+            (line, Token::Word(word)) => word.parse().map_err(|_| invalid(line, "expected a number")),
+            (line, _) => Err(invalid(line, "expected a number")),
+        }
+    }
+    /// A list of identifiers, each with an optional number, up to the closing semicolon
+    fn entries(&mut self) -> Result<Vec<(usize, String, usize)>, Error> {
+        let mut entries = Vec::new();
+        if self.at_symbol(';') {
+            self.next()?;
+            return Ok(entries);
+        }
+        loop {
+            let (line, identifier) = self.identifier()?;
+            let number = if self.at_symbol(':') {
+                self.next()?;
+                self.number()?
+            } else {
+                1
+            };
+            entries.push((line, identifier, number));
+            match self.next()? {
+                (_, Token::Symbol(',')) => continue,
+                (_, Token::Symbol(';')) => return Ok(entries),
+                (line, _) => return Err(invalid(line, "expected , or ;")),
+            }
+        }
+    }
+}
+
+impl<C, W> PetriNet<C, W>
+where
+    C: CapacityFn + FromIterator<(PlaceId, Capacity)>,
+    W: WeightFn + FromIterator<(Arc, Weight)>,
+{
+    /// Read a net in the LoLA format
+    pub fn read_lola<In: Read>(mut input: In) -> Result<Self, Error> {
+        let mut text = String::new();
+        input.read_to_string(&mut text).map_err(Error::IO)?;
+        let Lexed { tokens, id } = tokenize(&text)?;
+        let mut parser = Parser { tokens: tokens.into_iter().peekable(), line: 1 };
+        let mut annotations = Annotations::default();
+
+        // Places, in groups which may be declared SAFE
+        parser.keyword("PLACE")?;
+        let mut places = Vec::new();
+        let mut place_ids = HashMap::new();
+        while !parser.at_keyword("MARKING") {
+            if parser.at_keyword("SAFE") {
+                parser.next()?;
+                if !parser.at_symbol(':') {
+                    parser.number()?;
+                }
+                parser.symbol(':')?;
+            }
+            for (line, name, _) in parser.entries()? {
+                let id = PlaceId(places.len());
+                if place_ids.insert(name.clone(), id).is_some() {
+                    return Err(invalid(line, format!("place {} is declared twice", name)));
+                }
+                annotations.ids.places.insert(id, name.clone());
+                places.push(Place { id, name });
+            }
+        }
+        let place = |line: usize, name: &str| {
+            place_ids.get(name).copied().ok_or_else(|| invalid(line, format!("unknown place {}", name)))
+        };
+
+        parser.keyword("MARKING")?;
+        let capacities = C::from_iter(std::iter::empty());
+        let mut initial_marking = Marking::default();
+        for (line, name, tokens) in parser.entries()? {
+            let id = place(line, &name)?;
+            let tokens = initial_marking.get(&id).0 + tokens;
+            if tokens > capacities.get_or_default(&id).0 {
+                return Err(invalid(line, unsupported(format_args!("{} tokens", tokens))));
+            }
+            initial_marking.set(id, Tokens(tokens));
+        }
+
+        let mut transitions = Vec::new();
+        let mut transition_ids = HashMap::new();
+        let mut arcs = Vec::new();
+        let mut weights: Vec<(Arc, Weight)> = Vec::new();
+        while parser.tokens.peek().is_some() {
+            if parser.at_keyword("STRONG") || parser.at_keyword("WEAK") {
+                parser.next()?;
+                parser.keyword("FAIR")?;
+            }
+            parser.keyword("TRANSITION")?;
+            let (line, name) = parser.identifier()?;
+            let id = TransitionId(transitions.len());
+            if transition_ids.insert(name.clone(), id).is_some() {
+                return Err(invalid(line, format!("transition {} is declared twice", name)));
+            }
+            for (keyword, input) in [("CONSUME", true), ("PRODUCE", false)] {
+                parser.keyword(keyword)?;
+                for (line, name, weight) in parser.entries()? {
+                    let place = place(line, &name)?;
+                    let arc = if input { Arc::PlaceTransition(place, id) } else { Arc::TransitionPlace(id, place) };
+                    if weight == 0 {
+                        return Err(invalid(line, "weight must be at least 1"));
+                    }
+                    // An arc which is listed twice has the sum of the weights
+                    match weights.iter_mut().find(|(existing, _)| *existing == arc) {
+                        Some((_, existing)) => existing.0 += weight,
+                        None => {
+                            arcs.push(arc);
+                            weights.push((arc, Weight(weight)));
+                        }
+                    }
+                }
+            }
+            annotations.ids.transitions.insert(id, name.clone());
+            transitions.push(Transition { id, name });
+        }
+
+        let weight_fn: W = weights.iter().copied().collect();
+        if let Some((arc, weight)) = weights.iter().find(|(arc, weight)| weight_fn.get_or_default(arc) != *weight) {
+            let transition = match arc {
+                Arc::PlaceTransition(_, transition) | Arc::TransitionPlace(transition, _) => &transitions[transition.0].name,
+            };
+            return Err(Error::InvalidLola(format!("transition {}: {}", transition, unsupported(format_args!("weight {}", weight.0)))));
+        }
+
+        Ok(PetriNet {
+            id: id.unwrap_or_default(),
+            places,
+            transitions,
+            arcs,
+            capacities,
+            weights: weight_fn,
+            initial_marking,
+            annotations,
+        })
+    }
+    /// Load a net from a file in the LoLA format
+    pub fn load_lola<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).map(BufReader::new).map_err(Error::IO)?;
+        Self::read_lola(file)
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Write the net in the LoLA format.
+    /// Places and transitions are named by their original IDs if these are valid identifiers, and by their IDs otherwise.
+    pub fn write_lola<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let ids = &self.annotations.ids;
+        let valid = |id: &&String| is_identifier(id);
+        let mut allocator = IdAllocator::new(ids.places.values().chain(ids.transitions.values()).filter(valid));
+        let place_names: Vec<String> = self.places
+            .iter()
+            .map(|place| allocator.allocate(ids.places.get(&place.id).filter(valid), place.id.to_string()))
+            .collect();
+        let transition_names: Vec<String> = self.transitions
+            .iter()
+            .map(|transition| allocator.allocate(ids.transitions.get(&transition.id).filter(valid), transition.id.to_string()))
+            .collect();
+
+        if !self.id.is_empty() {
+            writeln!(out, "{{ net {} }}", self.id.replace('}', ")"))?;
+        }
+        writeln!(out, "PLACE")?;
+        writeln!(out, "    {};", place_names.join(",\n    "))?;
+        writeln!(out)?;
+        let marking: Vec<String> = self.initial_marking
+            .iter()
+            .map(|(place, tokens)| format!("{}: {}", place_names[place.0], tokens))
+            .collect();
+        writeln!(out, "MARKING")?;
+        writeln!(out, "    {};", marking.join(",\n    "))?;
+        for transition in &self.transitions {
+            let (mut consume, mut produce) = (Vec::new(), Vec::new());
+            for arc in &self.arcs {
+                let weight = self.weights.get_or_default(arc).0;
+                match *arc {
+                    Arc::PlaceTransition(place, id) if id == transition.id => {
+                        consume.push(format!("{}: {}", place_names[place.0], weight))
+                    }
+                    Arc::TransitionPlace(id, place) if id == transition.id => {
+                        produce.push(format!("{}: {}", place_names[place.0], weight))
+                    }
+                    _ => {}
+                }
+            }
+            writeln!(out)?;
+            writeln!(out, "TRANSITION {}", transition_names[transition.id.0])?;
+            writeln!(out, "    CONSUME {};", consume.join(", "))?;
+            writeln!(out, "    PRODUCE {};", produce.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, ECNet, PTNet, Pnml};
+
+    #[test]
+    fn test_lola() -> Result<(), Box<dyn std::error::Error>> {
+        let net = PTNet::read_lola(include_str!("../../models/mutex.lola").as_bytes())?;
+        assert_eq!(net.id, "mutex");
+        assert_eq!(net.places.len(), 3);
+        assert_eq!(net.transitions.len(), 2);
+        let enter = net.annotations.ids.find_transition("enter").unwrap();
+        let idle = net.annotations.ids.find_place("idle").unwrap();
+        assert_eq!(net.weights.get_or_default(&Arc::PlaceTransition(idle, enter)), Weight(2));
+        assert_eq!(net.initial_marking.get(&idle), Tokens(4));
+        assert_eq!(net.reachability_analysis().rows.len(), 5);
+
+        // Writing and reading the net again yields the same net
+        let mut lola = Vec::new();
+        net.write_lola(&mut lola)?;
+        let lola = String::from_utf8(lola)?;
+        assert!(lola.contains("TRANSITION enter\n    CONSUME idle: 2, mutex: 1;\n    PRODUCE busy: 1;"), "{}", lola);
+        let reread = PTNet::read_lola(lola.as_bytes())?;
+        assert_eq!(reread.arcs, net.arcs);
+        assert_eq!(reread.initial_marking, net.initial_marking);
+
+        // An EC net cannot hold the weights
+        let error = ECNet::read_lola(include_str!("../../models/mutex.lola").as_bytes()).unwrap_err();
+        assert!(matches!(error, Error::InvalidLola(_)));
+        let error = PTNet::read_lola("PLACE p;\nMARKING q;".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid LoLA file: line 2: unknown place q");
+
+        // Nets from other formats keep their original IDs where they are identifiers
+        let pnml: Pnml = parse_xml(include_str!("../../models/ids.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let mut lola = Vec::new();
+        net.write_lola(&mut lola)?;
+        let lola = String::from_utf8(lola)?;
+        assert!(lola.starts_with("{ net ids }\nPLACE\n    p-idle,\n    p-busy;\n"), "{}", lola);
+        let reread = ECNet::read_lola(lola.as_bytes())?;
+        assert_eq!(reread.arcs, net.arcs);
+        Ok(())
+    }
+}
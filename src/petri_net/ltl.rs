@@ -0,0 +1,563 @@
+//! This module checks Linear Temporal Logic (LTL) formulas against a Petri net.
+//!
+//! The check follows the automata-theoretic approach: the negation of the formula is translated into a
+//! Büchi automaton using the tableau construction of Gerth, Peled, Vardi and Wolper
+//! ([Simple On-the-fly Automatic Verification of Linear Temporal Logic, 1995](https://doi.org/10.1007/978-0-387-34892-6_1)),
+//! and the product of this automaton with the state space of the net is searched for an accepting cycle
+//! using a nested depth-first search. The product is built on the fly, so a violation can be found
+//! without exploring the whole state space.
+//!
+//! The states of the net seen by a formula are its markings together with the transition which was fired last,
+//! so formulas can talk about markings (e.g. `P1 >= 1`) as well as transition firings (e.g. `fired(T3)`).
+//! Runs which end in a deadlock are extended by stuttering in the deadlocked marking forever,
+//! during which no transition is fired.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::proposition::Proposition;
+use super::reachability::TransitionIO;
+use super::{CapacityFn, Marking, PetriNet, TransitionId, WeightFn};
+
+/// An LTL formula over atomic propositions about markings and transition firings
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ltl {
+    True,
+    False,
+    Atom(Proposition),
+    /// The transition was fired in the last step
+    Fired(TransitionId),
+    Not(Box<Ltl>),
+    And(Box<Ltl>, Box<Ltl>),
+    Or(Box<Ltl>, Box<Ltl>),
+    Implies(Box<Ltl>, Box<Ltl>),
+    /// The formula holds in the next state
+    Next(Box<Ltl>),
+    /// The formula holds eventually
+    Finally(Box<Ltl>),
+    /// The formula holds in every state
+    Globally(Box<Ltl>),
+    /// The first formula holds until the second one does, which must happen eventually
+    Until(Box<Ltl>, Box<Ltl>),
+    /// The second formula holds until and including the moment the first one does, which need not happen
+    Release(Box<Ltl>, Box<Ltl>),
+}
+
+/// Formulas are displayed in the syntax of the query language, e.g. G (fired(T1) -> F (fired(T2)))
+impl Display for Ltl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Ltl::True => write!(f, "true"),
+            Ltl::False => write!(f, "false"),
+            Ltl::Atom(proposition) => write!(f, "{}", proposition),
+            Ltl::Fired(transition) => write!(f, "fired({})", transition),
+            Ltl::Not(inner) => write!(f, "!({})", inner),
+            Ltl::And(left, right) => write!(f, "({} && {})", left, right),
+            Ltl::Or(left, right) => write!(f, "({} || {})", left, right),
+            Ltl::Implies(left, right) => write!(f, "({} -> {})", left, right),
+            Ltl::Next(inner) => write!(f, "X ({})", inner),
+            Ltl::Finally(inner) => write!(f, "F ({})", inner),
+            Ltl::Globally(inner) => write!(f, "G ({})", inner),
+            Ltl::Until(left, right) => write!(f, "({} U {})", left, right),
+            Ltl::Release(left, right) => write!(f, "({} R {})", left, right),
+        }
+    }
+}
+
+/// An infinite run of the net, consisting of a finite stem followed by a cycle which is repeated forever.
+/// An empty cycle means that the run ends in a deadlock.
+#[derive(Debug, Clone)]
+pub struct Lasso {
+    /// The transitions fired before the cycle, with their names
+    pub stem: Vec<(TransitionId, String)>,
+    /// The transitions fired in the cycle, with their names
+    pub cycle: Vec<(TransitionId, String)>,
+}
+
+/// Lassos are displayed as the transitions of the stem followed by the transitions of the cycle,
+/// e.g. T0 (split), T1 (work left) then forever T3 (join), T4 (restart)
+impl Display for Lasso {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        fn write_steps(f: &mut Formatter<'_>, steps: &[(TransitionId, String)]) -> FmtResult {
+            let mut separator = "";
+            for (transition, name) in steps {
+                if name.is_empty() {
+                    write!(f, "{}{}", separator, transition)?;
+                } else {
+                    write!(f, "{}{} ({})", separator, transition, name)?;
+                }
+                separator = ", ";
+            }
+            Ok(())
+        }
+        write_steps(f, &self.stem)?;
+        if !self.stem.is_empty() {
+            write!(f, " then ")?;
+        }
+        if self.cycle.is_empty() {
+            write!(f, "deadlock")
+        } else {
+            write!(f, "forever ")?;
+            write_steps(f, &self.cycle)
+        }
+    }
+}
+
+/// The result of checking an LTL formula against all runs of the net
+#[derive(Debug, Clone)]
+pub struct LtlResult {
+    /// Whether every run of the net satisfies the formula
+    pub holds: bool,
+    /// A run which violates the formula, if it does not hold
+    pub counterexample: Option<Lasso>,
+}
+
+impl Display for LtlResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.counterexample {
+            Some(counterexample) => write!(f, "{} (counterexample: {})", self.holds, counterexample),
+            None => write!(f, "{}", self.holds),
+        }
+    }
+}
+
+/// An atomic formula in negation normal form
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Atom {
+    Proposition(Proposition),
+    Fired(TransitionId),
+}
+
+/// A formula in negation normal form whose subformulas are referred to by their index in an arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    True,
+    False,
+    /// An atom (by index) which must hold (true) or must not hold (false)
+    Literal(usize, bool),
+    And(usize, usize),
+    Or(usize, usize),
+    Next(usize),
+    Until(usize, usize),
+    Release(usize, usize),
+}
+
+/// All subformulas of a formula in negation normal form, with structurally equal subformulas shared
+#[derive(Debug, Default)]
+struct Arena {
+    atoms: Vec<Atom>,
+    nodes: Vec<Node>,
+    index: HashMap<Node, usize>,
+}
+
+impl Arena {
+    fn intern(&mut self, node: Node) -> usize {
+        *self.index.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        })
+    }
+    fn atom(&mut self, atom: Atom) -> usize {
+        match self.atoms.iter().position(|existing| *existing == atom) {
+            Some(index) => index,
+            None => {
+                self.atoms.push(atom);
+                self.atoms.len() - 1
+            }
+        }
+    }
+    /// Convert a formula (negated if `negate` is set) into negation normal form
+    fn normalize(&mut self, formula: &Ltl, negate: bool) -> usize {
+        let node = match formula {
+            Ltl::True | Ltl::False => {
+                if (*formula == Ltl::True) != negate { Node::True } else { Node::False }
+            }
+            Ltl::Atom(proposition) => Node::Literal(self.atom(Atom::Proposition(proposition.clone())), !negate),
+            Ltl::Fired(transition) => Node::Literal(self.atom(Atom::Fired(*transition)), !negate),
+            Ltl::Not(inner) => return self.normalize(inner, !negate),
+            Ltl::And(left, right) | Ltl::Or(left, right) => {
+                let (left, right) = (self.normalize(left, negate), self.normalize(right, negate));
+                if matches!(formula, Ltl::And(..)) != negate { Node::And(left, right) } else { Node::Or(left, right) }
+            }
+            Ltl::Implies(left, right) => {
+                // a -> b is equivalent to !a || b
+                let (left, right) = (self.normalize(left, !negate), self.normalize(right, negate));
+                if negate { Node::And(left, right) } else { Node::Or(left, right) }
+            }
+            Ltl::Next(inner) => Node::Next(self.normalize(inner, negate)),
+            Ltl::Finally(inner) | Ltl::Globally(inner) => {
+                // F a = true U a and G a = false R a
+                let inner = self.normalize(inner, negate);
+                if matches!(formula, Ltl::Finally(_)) != negate {
+                    Node::Until(self.intern(Node::True), inner)
+                } else {
+                    Node::Release(self.intern(Node::False), inner)
+                }
+            }
+            Ltl::Until(left, right) | Ltl::Release(left, right) => {
+                let (left, right) = (self.normalize(left, negate), self.normalize(right, negate));
+                if matches!(formula, Ltl::Until(..)) != negate { Node::Until(left, right) } else { Node::Release(left, right) }
+            }
+        };
+        self.intern(node)
+    }
+}
+
+/// The ID used in the incoming sets of the tableau to denote the initial state
+const INIT: usize = usize::MAX;
+
+/// A node of the tableau under construction
+#[derive(Debug, Clone, Default)]
+struct TableauNode {
+    incoming: BTreeSet<usize>,
+    new: BTreeSet<usize>,
+    old: BTreeSet<usize>,
+    next: BTreeSet<usize>,
+}
+
+/// A generalized Büchi automaton whose states are labelled with the literals that must hold when entering them
+#[derive(Debug)]
+struct Automaton {
+    /// For every state, the states it can be entered from (INIT for initial states)
+    incoming: Vec<Vec<usize>>,
+    /// For every state, the successor states
+    successors: Vec<Vec<usize>>,
+    /// For every state, the literals (atom index and polarity) which must hold
+    labels: Vec<Vec<(usize, bool)>>,
+    /// For every acceptance set, which states belong to it
+    accepting: Vec<Vec<bool>>,
+}
+
+impl Automaton {
+    /// Translate a formula in negation normal form into a generalized Büchi automaton
+    fn new(arena: &Arena, root: usize) -> Self {
+        let mut finished: Vec<TableauNode> = Vec::new();
+        let mut todo = vec![TableauNode {
+            incoming: BTreeSet::from([INIT]),
+            new: BTreeSet::from([root]),
+            ..TableauNode::default()
+        }];
+        while let Some(mut node) = todo.pop() {
+            let Some(formula) = node.new.pop_first() else {
+                // The node is fully expanded: merge it with an equivalent node or add it to the automaton
+                match finished.iter_mut().find(|other| other.old == node.old && other.next == node.next) {
+                    Some(other) => other.incoming.extend(node.incoming),
+                    None => {
+                        todo.push(TableauNode {
+                            incoming: BTreeSet::from([finished.len()]),
+                            new: node.next.clone(),
+                            ..TableauNode::default()
+                        });
+                        finished.push(node);
+                    }
+                }
+                continue;
+            };
+            // Add the given formulas to the obligations of the node unless they are already fulfilled
+            let with = |mut node: TableauNode, formulas: &[usize]| {
+                node.new.extend(formulas.iter().filter(|formula| !node.old.contains(formula)));
+                node
+            };
+            node.old.insert(formula);
+            match arena.nodes[formula] {
+                Node::True => todo.push(node),
+                Node::False => {} // Contradiction, discard the node
+                Node::Literal(atom, positive) => {
+                    let contradicted = node.old
+                        .iter()
+                        .any(|&other| arena.nodes[other] == Node::Literal(atom, !positive));
+                    if !contradicted {
+                        todo.push(node);
+                    }
+                }
+                Node::And(left, right) => todo.push(with(node, &[left, right])),
+                Node::Or(left, right) => {
+                    todo.push(with(node.clone(), &[left]));
+                    todo.push(with(node, &[right]));
+                }
+                Node::Next(inner) => {
+                    node.next.insert(inner);
+                    todo.push(node);
+                }
+                Node::Until(left, right) => {
+                    let mut postponed = with(node.clone(), &[left]);
+                    postponed.next.insert(formula);
+                    todo.push(postponed);
+                    todo.push(with(node, &[right]));
+                }
+                Node::Release(left, right) => {
+                    let mut postponed = with(node.clone(), &[right]);
+                    postponed.next.insert(formula);
+                    todo.push(postponed);
+                    todo.push(with(node, &[left, right]));
+                }
+            }
+        }
+
+        let incoming: Vec<Vec<usize>> = finished.iter().map(|node| node.incoming.iter().copied().collect()).collect();
+        let mut successors = vec![Vec::new(); finished.len()];
+        for (state, sources) in incoming.iter().enumerate() {
+            for &source in sources.iter().filter(|&&source| source != INIT) {
+                successors[source].push(state);
+            }
+        }
+        let labels = finished
+            .iter()
+            .map(|node| {
+                node.old
+                    .iter()
+                    .filter_map(|&formula| match arena.nodes[formula] {
+                        Node::Literal(atom, positive) => Some((atom, positive)),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        // Every until formula must eventually be fulfilled: a U b is either not required or b holds
+        let mut accepting: Vec<Vec<bool>> = (0..arena.nodes.len())
+            .filter_map(|formula| match arena.nodes[formula] {
+                Node::Until(_, right) => Some(
+                    finished
+                        .iter()
+                        .map(|node| !node.old.contains(&formula) || node.old.contains(&right))
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect();
+        if accepting.is_empty() {
+            accepting.push(vec![true; finished.len()]);
+        }
+        Automaton { incoming, successors, labels, accepting }
+    }
+}
+
+/// A state of the product of the net and the (degeneralized) Büchi automaton:
+/// the index of a marking, the transition fired last, the automaton state and the acceptance counter
+type ProductState = (usize, Option<TransitionId>, usize, usize);
+
+/// The product of the state space of a net with a Büchi automaton, built on the fly
+struct Product<'a, C: CapacityFn, W: WeightFn> {
+    petri_net: &'a PetriNet<C, W>,
+    transition_io: Vec<TransitionIO>,
+    arena: &'a Arena,
+    automaton: &'a Automaton,
+    markings: Vec<Marking>,
+    marking_index: HashMap<Marking, usize, ahash::RandomState>,
+    /// The enabled transitions of each marking and the markings they lead to, computed on demand
+    marking_successors: Vec<Option<Vec<(TransitionId, usize)>>>,
+    states: Vec<ProductState>,
+    state_index: HashMap<ProductState, usize, ahash::RandomState>,
+}
+
+impl<'a, C: CapacityFn, W: WeightFn> Product<'a, C, W> {
+    fn marking(&mut self, marking: Marking) -> usize {
+        if let Some(&index) = self.marking_index.get(&marking) {
+            return index;
+        }
+        self.markings.push(marking.clone());
+        self.marking_successors.push(None);
+        self.marking_index.insert(marking, self.markings.len() - 1);
+        self.markings.len() - 1
+    }
+    fn marking_successors(&mut self, marking: usize) -> Vec<(TransitionId, usize)> {
+        if let Some(successors) = &self.marking_successors[marking] {
+            return successors.clone();
+        }
+        let successors: Vec<_> = self.petri_net
+            .fire_transitions(&self.transition_io, &self.markings[marking])
+            .into_iter()
+            .map(|(transition, successor)| (transition, self.marking(successor)))
+            .collect();
+        self.marking_successors[marking] = Some(successors.clone());
+        successors
+    }
+    fn state(&mut self, state: ProductState) -> usize {
+        *self.state_index.entry(state).or_insert_with(|| {
+            self.states.push(state);
+            self.states.len() - 1
+        })
+    }
+    /// Check whether the literals of an automaton state hold in a marking after firing a transition
+    fn satisfies(&mut self, marking: usize, fired: Option<TransitionId>, automaton_state: usize) -> bool {
+        let labels = &self.automaton.labels[automaton_state];
+        if labels.is_empty() {
+            return true;
+        }
+        let enabled: Vec<TransitionId> = self.marking_successors(marking).into_iter().map(|(t, _)| t).collect();
+        labels.iter().all(|&(atom, positive)| {
+            let holds = match &self.arena.atoms[atom] {
+                Atom::Proposition(proposition) => proposition.holds(&self.markings[marking], &enabled),
+                Atom::Fired(transition) => fired == Some(*transition),
+            };
+            holds == positive
+        })
+    }
+    fn is_accepting(&self, state: usize) -> bool {
+        let (_, _, automaton_state, counter) = self.states[state];
+        counter == 0 && self.automaton.accepting[0][automaton_state]
+    }
+    fn initial_states(&mut self) -> Vec<usize> {
+        let initial_marking = self.marking(self.petri_net.initial_marking.clone());
+        let mut initial_states = Vec::new();
+        for q in 0..self.automaton.incoming.len() {
+            if self.automaton.incoming[q].contains(&INIT) && self.satisfies(initial_marking, None, q) {
+                initial_states.push(self.state((initial_marking, None, q, 0)));
+            }
+        }
+        initial_states
+    }
+    /// The successors of a product state, with the transition fired to reach them (None when stuttering)
+    fn successors(&mut self, state: usize) -> Vec<(Option<TransitionId>, usize)> {
+        let (marking, _, q, counter) = self.states[state];
+        let sets = self.automaton.accepting.len();
+        let next_counter = if self.automaton.accepting[counter][q] { (counter + 1) % sets } else { counter };
+        let mut steps: Vec<(Option<TransitionId>, usize)> = self.marking_successors(marking)
+            .into_iter()
+            .map(|(transition, successor)| (Some(transition), successor))
+            .collect();
+        if steps.is_empty() {
+            // Deadlocks stutter forever
+            steps.push((None, marking));
+        }
+        let mut successors = Vec::new();
+        for (fired, successor) in steps {
+            for index in 0..self.automaton.successors[q].len() {
+                let next_q = self.automaton.successors[q][index];
+                if self.satisfies(successor, fired, next_q) {
+                    successors.push((fired, self.state((successor, fired, next_q, next_counter))));
+                }
+            }
+        }
+        successors
+    }
+}
+
+/// A frame of an iterative depth-first search: a state, the transition used to reach it, and its unvisited successors
+struct Frame {
+    state: usize,
+    fired: Option<TransitionId>,
+    successors: std::vec::IntoIter<(Option<TransitionId>, usize)>,
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Check whether every run of the net satisfies the LTL formula.
+    /// If not, a run which violates it is returned as a counterexample.
+    pub fn check_ltl(&self, formula: &Ltl) -> LtlResult {
+        let mut arena = Arena::default();
+        let root = arena.normalize(formula, true);
+        let automaton = Automaton::new(&arena, root);
+        let mut product = Product {
+            petri_net: self,
+            transition_io: self.transition_io(),
+            arena: &arena,
+            automaton: &automaton,
+            markings: Vec::new(),
+            marking_index: HashMap::default(),
+            marking_successors: Vec::new(),
+            states: Vec::new(),
+            state_index: HashMap::default(),
+        };
+        match nested_depth_first_search(&mut product) {
+            Some((stem, cycle)) => {
+                let named = |steps: Vec<Option<TransitionId>>| {
+                    steps
+                        .into_iter()
+                        .flatten() // Stuttering steps do not fire a transition
+                        .map(|transition| (transition, self.transitions[transition.0].name.clone()))
+                        .collect()
+                };
+                LtlResult { holds: false, counterexample: Some(Lasso { stem: named(stem), cycle: named(cycle) }) }
+            }
+            None => LtlResult { holds: true, counterexample: None },
+        }
+    }
+}
+
+/// Search the product for a reachable accepting cycle, using the nested depth-first search of
+/// Courcoubetis, Vardi, Wolper and Yannakakis. Returns the transitions of the stem and of the cycle.
+#[allow(clippy::type_complexity)]
+fn nested_depth_first_search<C: CapacityFn, W: WeightFn>(
+    product: &mut Product<'_, C, W>,
+) -> Option<(Vec<Option<TransitionId>>, Vec<Option<TransitionId>>)> {
+    let mut outer_visited = vec![];
+    let mut inner_visited = vec![];
+    let visit = |visited: &mut Vec<bool>, state: usize| -> bool {
+        if visited.len() <= state {
+            visited.resize(state + 1, false);
+        }
+        !std::mem::replace(&mut visited[state], true)
+    };
+    for initial in product.initial_states() {
+        if !visit(&mut outer_visited, initial) {
+            continue;
+        }
+        let successors = product.successors(initial).into_iter();
+        let mut outer = vec![Frame { state: initial, fired: None, successors }];
+        while let Some(frame) = outer.last_mut() {
+            if let Some((fired, successor)) = frame.successors.next() {
+                if visit(&mut outer_visited, successor) {
+                    let successors = product.successors(successor).into_iter();
+                    outer.push(Frame { state: successor, fired, successors });
+                }
+                continue;
+            }
+            // All successors are explored: if the state is accepting, look for a cycle back to it
+            let seed = frame.state;
+            if product.is_accepting(seed) && visit(&mut inner_visited, seed) {
+                let successors = product.successors(seed).into_iter();
+                let mut inner = vec![Frame { state: seed, fired: None, successors }];
+                while let Some(frame) = inner.last_mut() {
+                    let Some((fired, successor)) = frame.successors.next() else {
+                        inner.pop();
+                        continue;
+                    };
+                    if successor == seed {
+                        let stem = outer.iter().skip(1).map(|frame| frame.fired).collect();
+                        let cycle = inner.iter().skip(1).map(|frame| frame.fired).chain([fired]).collect();
+                        return Some((stem, cycle));
+                    }
+                    if visit(&mut inner_visited, successor) {
+                        let successors = product.successors(successor).into_iter();
+                        inner.push(Frame { state: successor, fired, successors });
+                    }
+                }
+            }
+            outer.pop();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::proposition::{Comparison, Expression};
+    use crate::petri_net::parallel_net;
+
+    fn fired(transition: &str) -> Box<Ltl> {
+        Box::new(Ltl::Fired(transition.parse().unwrap()))
+    }
+
+    #[test]
+    fn test_check_ltl() {
+        let net = parallel_net();
+        // Every split is eventually followed by a join
+        let response = Ltl::Globally(Box::new(Ltl::Implies(fired("T0"), Box::new(Ltl::Finally(fired("T3"))))));
+        assert!(net.check_ltl(&response).holds);
+        // The start place is marked infinitely often
+        let start = Box::new(Ltl::Atom(Proposition::Compare(
+            Expression::Tokens(vec!["P0".parse().unwrap()]),
+            Comparison::Equal,
+            Expression::Constant(1),
+        )));
+        assert!(net.check_ltl(&Ltl::Globally(Box::new(Ltl::Finally(start)))).holds);
+        // The left branch does not always work first
+        let order = Ltl::Globally(Box::new(Ltl::Implies(fired("T0"), Box::new(Ltl::Next(fired("T1"))))));
+        let result = net.check_ltl(&order);
+        assert!(!result.holds);
+        let counterexample = result.counterexample.expect("a violated formula has a counterexample");
+        let stem: Vec<_> = counterexample.stem.iter().map(|(t, _)| t.to_string()).collect();
+        assert_eq!(stem[..2], ["T0", "T2"]);
+    }
+}
@@ -0,0 +1,391 @@
+//! This module reads the property files of the [Model Checking Contest](https://mcc.lip6.fr)
+//! and answers them in the format expected by the contest.
+//!
+//! The properties of all categories share the same XML format, a `<property-set>` of `<property>` elements,
+//! each of which has an ID and a formula:
+//!
+//! - ReachabilityCardinality and ReachabilityFireability formulas are `EF` or `AG` formulas
+//! - UpperBounds formulas ask for the maximal number of tokens on a set of places (`<place-bound>`)
+//! - CTLCardinality and CTLFireability formulas are CTL formulas
+//! - LTLCardinality and LTLFireability formulas are path formulas under a single `<all-paths>`
+//!
+//! The category is not part of the formula, but the contest names it in the ID of the property,
+//! e.g. `Model-LTLFireability-00`. Formulas of the LTL categories are checked as LTL, where a run which ends
+//! in a deadlock stutters there forever. In the other categories, a formula in which every temporal operator
+//! is directly preceded by a path quantifier is checked as CTL, and any other formula with an `<all-paths>`
+//! at its root as LTL. The logics do not always agree on formulas which fit both: `A(X p)` holds in a deadlock
+//! as the CTL formula `AX p`, but as an LTL formula only if `p` holds there.
+//!
+//! Places and transitions are referred to by their ID, or by their name if no element has that ID.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use serde::Deserialize;
+
+use super::proposition::{Comparison, Expression, Proposition};
+use super::{CapacityFn, Ctl, Lookup, Ltl, PetriNet, PlaceId, ReachabilityAnalysis, TransitionId, WeightFn};
+use crate::Error;
+
+/// The techniques reported with each answer
+const TECHNIQUES: &str = "EXPLICIT SEQUENTIAL_PROCESSING";
+
+/// A set of properties to be checked against a model, as found in the property files of the contest
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "property-set")]
+pub struct PropertySet {
+    #[serde(rename = "property", default)]
+    pub properties: Vec<Property>,
+}
+
+/// A single property with its formula
+#[derive(Debug, Clone, Deserialize)]
+pub struct Property {
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    formula: Operand,
+}
+
+/// A formula resolved against a Petri net
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyKind {
+    Ctl(Ctl),
+    Ltl(Ltl),
+    /// The maximal total number of tokens on these places in any reachable marking
+    UpperBounds(Vec<PlaceId>),
+}
+
+/// The answer to a property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    True,
+    False,
+    Bound(usize),
+}
+
+/// The answer to a property, displayed in the format of the contest, e.g.
+/// `FORMULA Model-ReachabilityCardinality-00 TRUE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answer {
+    pub id: String,
+    pub verdict: Verdict,
+}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "FORMULA {} ", self.id)?;
+        match self.verdict {
+            Verdict::True => write!(f, "TRUE")?,
+            Verdict::False => write!(f, "FALSE")?,
+            Verdict::Bound(bound) => write!(f, "{}", bound)?,
+        }
+        write!(f, " TECHNIQUES {}", TECHNIQUES)
+    }
+}
+
+/// An element which contains exactly one formula
+#[derive(Debug, Clone, Deserialize)]
+struct Operand {
+    #[serde(rename = "$value")]
+    element: Box<Element>,
+}
+
+/// An element which contains any number of formulas
+#[derive(Debug, Clone, Deserialize)]
+struct Operands {
+    #[serde(rename = "$value", default)]
+    elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Until {
+    before: Operand,
+    reach: Operand,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Places {
+    #[serde(rename = "place", default)]
+    places: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Transitions {
+    #[serde(rename = "transition", default)]
+    transitions: Vec<String>,
+}
+
+/// The elements which can occur in a formula
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Element {
+    AllPaths(Operand),
+    ExistsPath(Operand),
+    Globally(Operand),
+    Finally(Operand),
+    Next(Operand),
+    Until(Until),
+    Negation(Operand),
+    Conjunction(Operands),
+    Disjunction(Operands),
+    IsFireable(Transitions),
+    IntegerLt(Operands),
+    IntegerLe(Operands),
+    IntegerEq(Operands),
+    IntegerNe(Operands),
+    IntegerGe(Operands),
+    IntegerGt(Operands),
+    IntegerConstant(String),
+    TokensCount(Places),
+    PlaceBound(Places),
+    True,
+    False,
+}
+
+/// Combine formulas with a binary operator, or return the neutral element if there are none
+fn fold<T>(formulas: Vec<T>, neutral: T, operator: fn(Box<T>, Box<T>) -> T) -> T {
+    formulas
+        .into_iter()
+        .reduce(|left, right| operator(Box::new(left), Box::new(right)))
+        .unwrap_or(neutral)
+}
+
+/// Translates the formula of a property into CTL or LTL over a particular net
+struct Resolver<'net, C: CapacityFn, W: WeightFn> {
+    net: &'net PetriNet<C, W>,
+}
+
+impl<C: CapacityFn, W: WeightFn> Resolver<'_, C, W> {
+    fn place(&self, reference: &str) -> Result<PlaceId, String> {
+        match self.net.find_place(reference) {
+            Lookup::Found(id) => Ok(id),
+            Lookup::Unknown => Err(format!("unknown place \"{}\"", reference)),
+            Lookup::Ambiguous => Err(format!("ambiguous place \"{}\"", reference)),
+        }
+    }
+    fn transition(&self, reference: &str) -> Result<TransitionId, String> {
+        match self.net.find_transition(reference) {
+            Lookup::Found(id) => Ok(id),
+            Lookup::Unknown => Err(format!("unknown transition \"{}\"", reference)),
+            Lookup::Ambiguous => Err(format!("ambiguous transition \"{}\"", reference)),
+        }
+    }
+    fn places(&self, places: &Places) -> Result<Vec<PlaceId>, String> {
+        places.places.iter().map(|place| self.place(place.trim())).collect()
+    }
+    fn expression(&self, element: &Element) -> Result<Expression, String> {
+        match element {
+            Element::IntegerConstant(constant) => constant
+                .trim()
+                .parse()
+                .map(Expression::Constant)
+                .map_err(|_| format!("invalid integer constant \"{}\"", constant)),
+            Element::TokensCount(places) => self.places(places).map(Expression::Tokens),
+            _ => Err("expected an integer expression".to_string()),
+        }
+    }
+    /// Translate an element into an atomic proposition, or a disjunction of them for `is-fireable`
+    fn atom<T>(&self, element: &Element, atom: fn(Proposition) -> T, or: fn(Box<T>, Box<T>) -> T, never: T) -> Result<Option<T>, String> {
+        if let Element::IsFireable(transitions) = element {
+            let fireable = transitions.transitions
+                .iter()
+                .map(|transition| self.transition(transition.trim()).map(|id| atom(Proposition::Fireable(id))))
+                .collect::<Result<_, _>>()?;
+            return Ok(Some(fold(fireable, never, or)));
+        }
+        let (comparison, operands) = match element {
+            Element::IntegerLt(operands) => (Comparison::Less, operands),
+            Element::IntegerLe(operands) => (Comparison::LessEqual, operands),
+            Element::IntegerEq(operands) => (Comparison::Equal, operands),
+            Element::IntegerNe(operands) => (Comparison::NotEqual, operands),
+            Element::IntegerGe(operands) => (Comparison::GreaterEqual, operands),
+            Element::IntegerGt(operands) => (Comparison::Greater, operands),
+            _ => return Ok(None),
+        };
+        match operands.elements.as_slice() {
+            [left, right] => {
+                let proposition = Proposition::Compare(self.expression(left)?, comparison, self.expression(right)?);
+                Ok(Some(atom(proposition)))
+            }
+            _ => Err("integer comparisons need exactly two operands".to_string()),
+        }
+    }
+    fn ctl(&self, element: &Element) -> Result<Ctl, String> {
+        let ctl = |operand: &Operand| self.ctl(&operand.element).map(Box::new);
+        if let Some(atom) = self.atom(element, Ctl::Atom, Ctl::Or, Ctl::False)? {
+            return Ok(atom);
+        }
+        Ok(match element {
+            Element::AllPaths(operand) => match operand.element.as_ref() {
+                Element::Globally(operand) => Ctl::AG(ctl(operand)?),
+                Element::Finally(operand) => Ctl::AF(ctl(operand)?),
+                Element::Next(operand) => Ctl::AX(ctl(operand)?),
+                Element::Until(until) => Ctl::AU(ctl(&until.before)?, ctl(&until.reach)?),
+                // A path quantifier in front of a state formula has no effect
+                state => self.ctl(state)?,
+            },
+            Element::ExistsPath(operand) => match operand.element.as_ref() {
+                Element::Globally(operand) => Ctl::EG(ctl(operand)?),
+                Element::Finally(operand) => Ctl::EF(ctl(operand)?),
+                Element::Next(operand) => Ctl::EX(ctl(operand)?),
+                Element::Until(until) => Ctl::EU(ctl(&until.before)?, ctl(&until.reach)?),
+                state => self.ctl(state)?,
+            },
+            Element::Globally(_) | Element::Finally(_) | Element::Next(_) | Element::Until(_) => {
+                return Err("temporal operator without a path quantifier".to_string())
+            }
+            Element::Negation(operand) => Ctl::Not(ctl(operand)?),
+            Element::Conjunction(operands) => {
+                let conjuncts = operands.elements.iter().map(|element| self.ctl(element)).collect::<Result<_, _>>()?;
+                fold(conjuncts, Ctl::True, Ctl::And)
+            }
+            Element::Disjunction(operands) => {
+                let disjuncts = operands.elements.iter().map(|element| self.ctl(element)).collect::<Result<_, _>>()?;
+                fold(disjuncts, Ctl::False, Ctl::Or)
+            }
+            Element::True => Ctl::True,
+            Element::False => Ctl::False,
+            _ => return Err("expected a boolean formula".to_string()),
+        })
+    }
+    fn ltl(&self, element: &Element) -> Result<Ltl, String> {
+        let ltl = |operand: &Operand| self.ltl(&operand.element).map(Box::new);
+        if let Some(atom) = self.atom(element, Ltl::Atom, Ltl::Or, Ltl::False)? {
+            return Ok(atom);
+        }
+        Ok(match element {
+            Element::AllPaths(_) | Element::ExistsPath(_) => {
+                return Err("nested path quantifiers are not supported in LTL".to_string())
+            }
+            Element::Globally(operand) => Ltl::Globally(ltl(operand)?),
+            Element::Finally(operand) => Ltl::Finally(ltl(operand)?),
+            Element::Next(operand) => Ltl::Next(ltl(operand)?),
+            Element::Until(until) => Ltl::Until(ltl(&until.before)?, ltl(&until.reach)?),
+            Element::Negation(operand) => Ltl::Not(ltl(operand)?),
+            Element::Conjunction(operands) => {
+                let conjuncts = operands.elements.iter().map(|element| self.ltl(element)).collect::<Result<_, _>>()?;
+                fold(conjuncts, Ltl::True, Ltl::And)
+            }
+            Element::Disjunction(operands) => {
+                let disjuncts = operands.elements.iter().map(|element| self.ltl(element)).collect::<Result<_, _>>()?;
+                fold(disjuncts, Ltl::False, Ltl::Or)
+            }
+            Element::True => Ltl::True,
+            Element::False => Ltl::False,
+            _ => return Err("expected a boolean formula".to_string()),
+        })
+    }
+}
+
+impl Property {
+    /// Returns true if the ID of the property names an LTL category, e.g. `Model-LTLCardinality-00`
+    fn is_ltl(&self) -> bool {
+        self.id.rsplit('-').nth(1).is_some_and(|category| category.starts_with("LTL"))
+    }
+    /// Resolve the formula of the property against a Petri net
+    pub fn resolve<C: CapacityFn, W: WeightFn>(&self, net: &PetriNet<C, W>) -> Result<PropertyKind, Error> {
+        let resolver = Resolver { net };
+        let element = self.formula.element.as_ref();
+        let kind = match element {
+            Element::PlaceBound(places) => resolver.places(places).map(PropertyKind::UpperBounds),
+            Element::AllPaths(operand) if self.is_ltl() => resolver.ltl(&operand.element).map(PropertyKind::Ltl),
+            Element::AllPaths(operand) => resolver
+                .ctl(element)
+                .map(PropertyKind::Ctl)
+                .or_else(|_| resolver.ltl(&operand.element).map(PropertyKind::Ltl)),
+            _ => resolver.ctl(element).map(PropertyKind::Ctl),
+        };
+        kind.map_err(|message| Error::UnsupportedProperty(format!("{}: {}", self.id, message)))
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Answer a property of the Model Checking Contest
+    pub fn answer(&self, property: &Property) -> Result<Answer, Error> {
+        self.answer_with(property, &mut None)
+    }
+    /// Answer all properties of a property set.
+    /// The reachability graph is only computed once and shared between the properties which need it.
+    pub fn answer_all(&self, property_set: &PropertySet) -> Vec<Result<Answer, Error>> {
+        let mut analysis = None;
+        property_set.properties
+            .iter()
+            .map(|property| self.answer_with(property, &mut analysis))
+            .collect()
+    }
+    fn answer_with<'net>(
+        &'net self,
+        property: &Property,
+        analysis: &mut Option<ReachabilityAnalysis<'net, C, W>>,
+    ) -> Result<Answer, Error> {
+        let verdict = match property.resolve(self)? {
+            PropertyKind::Ctl(formula) => {
+                let analysis = analysis.get_or_insert_with(|| self.reachability_analysis());
+                analysis.check_ctl(&formula).holds.into()
+            }
+            PropertyKind::Ltl(formula) => self.check_ltl(&formula).holds.into(),
+            PropertyKind::UpperBounds(places) => {
+                let analysis = analysis.get_or_insert_with(|| self.reachability_analysis());
+                let tokens = Expression::Tokens(places);
+                let bound = analysis.rows.iter().map(|(_, marking, _)| tokens.evaluate(marking)).max();
+                Verdict::Bound(bound.unwrap_or_default())
+            }
+        };
+        Ok(Answer { id: property.id.clone(), verdict })
+    }
+}
+
+impl From<bool> for Verdict {
+    fn from(holds: bool) -> Self {
+        if holds { Verdict::True } else { Verdict::False }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::Marking;
+    use crate::{parse_xml, ECNet, Pnml};
+
+    #[test]
+    fn test_answer_all() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let property_set: PropertySet = parse_xml(include_str!("../../models/parallel-properties.xml").as_bytes())?;
+
+        let answers: Vec<String> = net
+            .answer_all(&property_set)
+            .into_iter()
+            .map(|answer| answer.map_or_else(|error| error.to_string(), |answer| answer.to_string()))
+            .collect();
+        assert_eq!(answers, [
+            "FORMULA parallel-ReachabilityCardinality-00 TRUE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-ReachabilityFireability-00 TRUE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-UpperBounds-00 2 TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-CTLCardinality-00 TRUE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-CTLCardinality-01 FALSE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-LTLFireability-00 TRUE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "FORMULA parallel-LTLFireability-01 FALSE TECHNIQUES EXPLICIT SEQUENTIAL_PROCESSING",
+            "Unsupported property: parallel-UpperBounds-01: unknown place \"P9\"",
+        ]);
+        assert!(matches!(property_set.properties[5].resolve(&net)?, PropertyKind::Ltl(_)));
+        assert!(matches!(property_set.properties[6].resolve(&net)?, PropertyKind::Ltl(_)));
+
+        // In a deadlock, A(X false) holds as a CTL formula, but not as an LTL formula
+        let mut dead = net.clone();
+        dead.initial_marking = Marking::default();
+        let next = "<formula><all-paths><next><false/></next></all-paths></formula>";
+        let xml = format!(
+            "<property-set><property><id>dead-CTLFireability-00</id>{next}</property>\
+             <property><id>dead-LTLFireability-00</id>{next}</property></property-set>"
+        );
+        let property_set: PropertySet = parse_xml(xml.as_bytes())?;
+        let verdicts: Vec<Verdict> = dead
+            .answer_all(&property_set)
+            .into_iter()
+            .map(|answer| answer.map(|answer| answer.verdict))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(verdicts, [Verdict::True, Verdict::False]);
+        Ok(())
+    }
+}
@@ -10,16 +10,57 @@
 
 mod reachability;
 mod pnml;
-
-pub use pnml::Pnml;
+mod observer;
+mod checkpoint;
+mod proposition;
+mod ctl;
+mod ltl;
+mod query;
+mod mcc;
+mod bounds;
+mod step;
+mod conflict;
+mod reduce;
+mod unfolding;
+mod symmetry;
+mod export;
+mod render;
+mod layout;
+mod annotations;
+mod any;
+mod lola;
+mod tina;
+
+pub use pnml::{Pnml, PnmlError, Problem};
+pub use observer::{Observer, Statistics};
+pub use checkpoint::Checkpoint;
+pub use proposition::{Comparison, Expression, Proposition};
+pub use ctl::{Ctl, CtlResult};
+pub use ltl::{Lasso, Ltl, LtlResult};
+pub use query::QueryError;
+pub(crate) use query::Lookup;
+pub use bounds::PlaceBound;
+pub use step::{ConcurrencyRelation, Step, StepContinuation, StepExploration, StepSemantics};
+pub use conflict::{Confusion, Conflict, PersistenceViolation, StructuralConflict};
+pub use reduce::{ReducedNet, Reduction};
+pub use unfolding::{Condition, ConditionId, Event, EventId, Unfolding};
+pub use symmetry::{Automorphism, SymmetricExploration, Symmetries};
+pub use layout::{Layout, Position};
+pub use annotations::{Annotations, OriginalIds, XmlElement};
+pub use any::{AnyAnalysis, AnyNet};
+pub(crate) use lola::is_lola;
+pub(crate) use tina::is_tina;
+pub use mcc::{Answer, Property, PropertyKind, PropertySet, Verdict};
+pub use reachability::{
+    Bound, Boundedness, Continuation, Exploration, Live, Liveness, Marking, MarkingFn, MarkingId,
+    ReachabilityAnalysis, Tokens, Trace,
+};
 
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::hash::Hash;
 use std::str::FromStr;
 
-use reachability::{Marking, MarkingFn, Tokens};
-
 /// An ID for a place in the Petri net
 /// This is a newtype around `usize` to ensure that we can't accidentally mix up place and transition IDs
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -231,6 +272,8 @@ pub struct PetriNet<C: CapacityFn, W: WeightFn> {
     pub capacities: C,
     pub weights: W,
     pub initial_marking: Marking,
+    /// What a PNML file contained beyond the net itself, to be written back on export
+    pub annotations: Annotations,
 }
 
 /// An Event/Condition Net (EC Net) has a fixed capacity of 1 for all places and a fixed weight of 1 for all arcs
@@ -283,6 +326,7 @@ where
         
         let mut factory = ElementFactory::default();
         let mut initial_marking = Marking::default();
+        let mut ids = OriginalIds::default();
 
         // Create petri net elements for each BPMN element so we can refer to them later
         let mut petri_net_elements = HashMap::with_capacity(bpmn.elements.len());
@@ -305,6 +349,11 @@ where
                     Element::Transition(factory.new_transition(element.name.clone()))
                 },
             };
+            // Remember which BPMN element each place or transition stems from
+            match &petri_net_element {
+                Element::Place(place) => ids.places.insert(place.id, element.id.clone()),
+                Element::Transition(transition) => ids.transitions.insert(transition.id, element.id.clone()),
+            };
             petri_net_elements.insert(element.id.clone(), petri_net_element);
         }
 
@@ -383,6 +432,14 @@ where
             capacities,
             weights,
             initial_marking,
+            annotations: Annotations { ids, ..Annotations::default() },
         }
     }
-}
\ No newline at end of file
+}
+
+/// The net of `models/parallel.pnml`, which the tests of several modules check their analyses against
+#[cfg(test)]
+pub(crate) fn parallel_net() -> ECNet {
+    let pnml: Pnml = crate::parse_xml(include_str!("../../models/parallel.pnml").as_bytes()).unwrap();
+    pnml.try_into().unwrap()
+}
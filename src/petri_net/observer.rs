@@ -0,0 +1,155 @@
+//! This module defines hooks for observing a reachability exploration while it is running.
+//!
+//! Long-running explorations give no feedback until they are finished.
+//! An observer is notified about every new marking, every new edge and every deadlock as they are found,
+//! and periodically receives statistics about the progress of the exploration.
+//! Each callback can abort the exploration by returning `ControlFlow::Break`,
+//! in which case the exploration stops as soon as the marking currently being explored is finished,
+//! after a final report of the statistics.
+
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+use super::{Continuation, Marking, MarkingId};
+
+/// Statistics about a running exploration, reported periodically to an observer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    /// The number of distinct markings discovered so far
+    pub discovered: usize,
+    /// The number of markings whose continuations have been computed
+    pub explored: usize,
+    /// The number of edges (continuations) found so far
+    pub edges: usize,
+    /// The number of discovered markings which are waiting to be explored
+    pub frontier: usize,
+    /// The time since the exploration started
+    pub elapsed: Duration,
+    /// The average number of markings explored per second
+    pub markings_per_second: f64,
+    /// An estimate of the number of bytes used to store the discovered markings
+    pub memory: usize,
+}
+
+/// An observer of a reachability exploration.
+/// All callbacks have default implementations which do nothing and let the exploration continue,
+/// so implementors only need to override the ones they are interested in.
+pub trait Observer {
+    /// Called when a marking is discovered for the first time
+    fn new_marking(&mut self, _id: MarkingId, _marking: &Marking) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+    /// Called for every edge of the reachability graph, including edges to markings seen before
+    fn new_edge(&mut self, _source: MarkingId, _continuation: Continuation) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+    /// Called when an explored marking turns out to have no continuations
+    fn deadlock(&mut self, _id: MarkingId, _marking: &Marking) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+    /// Called periodically (see `statistics_interval`) and once more when the exploration is finished or aborted
+    fn statistics(&mut self, _statistics: &Statistics) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+    /// The minimum time between two calls to `statistics`
+    fn statistics_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+/// The unit observer ignores all events
+impl Observer for () {}
+
+/// An observer can be lent to an exploration and inspected afterwards
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn new_marking(&mut self, id: MarkingId, marking: &Marking) -> ControlFlow<()> {
+        (**self).new_marking(id, marking)
+    }
+    fn new_edge(&mut self, source: MarkingId, continuation: Continuation) -> ControlFlow<()> {
+        (**self).new_edge(source, continuation)
+    }
+    fn deadlock(&mut self, id: MarkingId, marking: &Marking) -> ControlFlow<()> {
+        (**self).deadlock(id, marking)
+    }
+    fn statistics(&mut self, statistics: &Statistics) -> ControlFlow<()> {
+        (**self).statistics(statistics)
+    }
+    fn statistics_interval(&self) -> Duration {
+        (**self).statistics_interval()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::parallel_net;
+
+    /// Counts the callbacks and aborts once a given number of markings has been discovered
+    #[derive(Default)]
+    struct Counter {
+        limit: usize,
+        markings: usize,
+        edges: usize,
+        deadlocks: usize,
+        statistics: Vec<Statistics>,
+    }
+
+    impl Observer for Counter {
+        fn new_marking(&mut self, _id: MarkingId, _marking: &Marking) -> ControlFlow<()> {
+            self.markings += 1;
+            if self.markings >= self.limit {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+        fn new_edge(&mut self, _source: MarkingId, _continuation: Continuation) -> ControlFlow<()> {
+            self.edges += 1;
+            ControlFlow::Continue(())
+        }
+        fn deadlock(&mut self, _id: MarkingId, _marking: &Marking) -> ControlFlow<()> {
+            self.deadlocks += 1;
+            ControlFlow::Continue(())
+        }
+        fn statistics(&mut self, statistics: &Statistics) -> ControlFlow<()> {
+            self.statistics.push(*statistics);
+            ControlFlow::Continue(())
+        }
+        fn statistics_interval(&self) -> Duration {
+            Duration::MAX
+        }
+    }
+
+    #[test]
+    fn test_abort() {
+        let net = parallel_net();
+
+        // The third marking is discovered while exploring the second one, which is still finished
+        let mut counter = Counter { limit: 3, ..Default::default() };
+        let mut exploration = net.explore_with(&mut counter);
+        assert_eq!(exploration.by_ref().count(), 2);
+        assert!(exploration.is_aborted());
+        assert!(exploration.next().is_none());
+        assert_eq!((counter.markings, counter.edges, counter.deadlocks), (4, 3, 0));
+        assert_eq!(counter.statistics.len(), 1);
+        let statistics = counter.statistics[0];
+        assert_eq!((statistics.discovered, statistics.explored, statistics.edges, statistics.frontier), (4, 2, 3, 2));
+
+        // Aborting at the initial marking explores nothing, but still reports the final statistics
+        let mut counter = Counter { limit: 1, ..Default::default() };
+        let mut exploration = net.explore_with(&mut counter);
+        assert_eq!(exploration.by_ref().count(), 0);
+        assert!(exploration.is_aborted());
+        assert_eq!((counter.markings, counter.edges, counter.deadlocks), (1, 0, 0));
+        assert_eq!(counter.statistics.len(), 1);
+        assert_eq!(counter.statistics[0].explored, 0);
+
+        // Without aborting, the final statistics are reported once the state space is exhausted
+        let mut counter = Counter { limit: usize::MAX, ..Default::default() };
+        let mut exploration = net.explore_with(&mut counter);
+        assert_eq!(exploration.by_ref().count(), 6);
+        assert!(!exploration.is_aborted());
+        assert_eq!(counter.statistics.len(), 1);
+        assert_eq!(counter.statistics[0].frontier, 0);
+    }
+}
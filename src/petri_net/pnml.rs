@@ -1,81 +1,87 @@
 //! This module provides serialization and deserialization for Petri nets in PNML format.
+//!
+//! Everything in a PNML file which the net does not model, such as graphics and tool-specific data,
+//! is kept in the annotations of the net and written back when the net is saved.
+//!
+//! The places and transitions of a file may have arbitrary IDs. They are numbered densely in the order
+//! in which they appear, and their original IDs are kept in the annotations for export and lookup.
+//!
+//! Weights are read from and written to the inscriptions of arcs, as in the P/T net grammar of ISO/IEC 15909-2.
+//! A net is saved as a P/T net if it has weights or capacities, and with the core model otherwise.
+//!
+//! A file may contain several nets, each of which may be organized in pages. The pages are flattened when a net
+//! is loaded, and reference places and transitions are replaced by the nodes they refer to.
+//! A net loaded from pages is saved on a single page, with the ID, name and other elements of its first page.
 
-use std::fmt::{Display, Formatter, Result as FmtResult, Error as FmtError};
-use serde::{Deserialize, Serialize};
-use super::{CapacityFn, MarkingFn, PetriNet, WeightFn};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult, Error as FmtError};
+use serde::de::{EnumAccess, VariantAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use super::annotations::{Extra, IdAllocator};
+use super::any::unsupported;
+use super::{Annotations, CapacityFn, MarkingFn, PetriNet, Position, WeightFn, XmlElement};
 
-const PNML_NAMESPACE: &str = "http://www.pnml.org/version-2009/grammar/pnmlcoremodel";
+/// The type of nets which use no more than the core model: places, transitions and arcs with their names and markings
+const CORE_MODEL: &str = "http://www.pnml.org/version-2009/grammar/pnmlcoremodel";
+/// The type of Place/Transition nets, whose arcs have weights given by their inscriptions
+const PT_NET: &str = "http://www.pnml.org/version-2009/grammar/ptnet";
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct Name {
     #[serde(rename = "text")]
     text: String,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct InitialMarking {
     #[serde(rename = "text")]
-    amount: usize,
-}
-
-impl From<super::Tokens> for Option<InitialMarking> {
-    fn from(tokens: super::Tokens) -> Self {
-        if tokens.0 == 0 {
-            None
-        } else {
-            Some(InitialMarking { amount: tokens.0 })
-        }
-    }
+    amount: String,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Capacity {
     #[serde(rename = "text")]
-    amount: usize,
-}
-
-impl From<super::Capacity> for Capacity {
-    fn from(capacity: super::Capacity) -> Self {
-        Capacity { amount: capacity.0 }
-    }
+    amount: String,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Place {
     #[serde(rename = "@id")]
     id: String,
-    #[serde(rename = "name")]
+    #[serde(rename = "name", default)]
     name: Name,
-    #[serde(rename = "initialMarking")]
+    #[serde(rename = "initialMarking", skip_serializing_if = "Option::is_none")]
     initial_marking: Option<InitialMarking>,
-    #[serde(rename = "capacity")]
+    #[serde(rename = "capacity", skip_serializing_if = "Option::is_none")]
     capacity: Option<Capacity>,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Transition {
     #[serde(rename = "@id")]
     id: String,
-    #[serde(rename = "name")]
+    #[serde(rename = "name", default)]
     name: Name,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
+/// The weight of an arc in a P/T net
 #[derive(Debug, Serialize, Deserialize)]
 struct Inscription {
-    #[serde(rename = "inscription")]
-    inscription: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Weight {
     #[serde(rename = "text")]
-    amount: usize,
-}
-
-impl From<super::Weight> for Weight {
-    fn from(weight: super::Weight) -> Self {
-        Weight { amount: weight.0 }
-    }
+    amount: String,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -86,36 +92,155 @@ struct Arc {
     source: String,
     #[serde(rename = "@target")]
     target: String,
-    #[serde(rename = "name")]
-    name: Inscription,
-    #[serde(rename = "weight")]
-    weight: Option<Weight>,
+    #[serde(rename = "inscription", skip_serializing_if = "Option::is_none")]
+    inscription: Option<Inscription>,
+    /// Older versions of this crate wrote an empty `<name><inscription/></name>` for every arc, of which only the rest is kept
+    #[serde(rename = "name", default, skip_serializing)]
+    legacy_name: Option<Extra>,
+    /// Older versions of this crate wrote the weight as a `<weight>` label, which is read in place of a missing inscription
+    #[serde(rename = "weight", default, skip_serializing)]
+    legacy_weight: Option<Inscription>,
+    #[serde(flatten)]
+    extra: Extra,
 }
 
+/// A reference place or reference transition, which stands for the node it refers to, e.g. one on another page
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+struct Reference {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "@ref")]
+    reference: String,
+    #[serde(flatten)]
+    extra: Extra,
+}
+
+/// A page of a net, which groups elements of the net and may contain further pages
+#[derive(Debug, Deserialize)]
+struct Page {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(rename = "$value", default)]
+    elements: Vec<PnmlElement>,
+}
+
+/// A child element of a net or page: a node, an arc or a page, or any other element, which is kept as it is
+#[derive(Debug)]
 enum PnmlElement {
     Place(Place),
     Transition(Transition),
     Arc(Arc),
+    Page(Page),
+    ReferencePlace(Reference),
+    ReferenceTransition(Reference),
+    Other(XmlElement),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The elements of a net are told apart by their tag
+impl<'de> Deserialize<'de> for PnmlElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ElementVisitor;
+        impl<'de> Visitor<'de> for ElementVisitor {
+            type Value = PnmlElement;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "a place, transition, arc, page or other element")
+            }
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<PnmlElement, A::Error> {
+                let (tag, content): (String, _) = data.variant()?;
+                match tag.as_str() {
+                    "place" => content.newtype_variant().map(PnmlElement::Place),
+                    "transition" => content.newtype_variant().map(PnmlElement::Transition),
+                    "arc" => content.newtype_variant().map(PnmlElement::Arc),
+                    "page" => content.newtype_variant().map(PnmlElement::Page),
+                    "referencePlace" => content.newtype_variant().map(PnmlElement::ReferencePlace),
+                    "referenceTransition" => content.newtype_variant().map(PnmlElement::ReferenceTransition),
+                    _ => {
+                        let mut element: XmlElement = content.newtype_variant()?;
+                        element.name = tag;
+                        Ok(PnmlElement::Other(element))
+                    }
+                }
+            }
+        }
+        let variants = &["place", "transition", "arc", "page", "referencePlace", "referenceTransition"];
+        deserializer.deserialize_enum("PnmlElement", variants, ElementVisitor)
+    }
+}
+
+#[derive(Debug, Deserialize)]
 struct Net {
     #[serde(rename = "@id")]
     id: String,
     #[serde(rename = "@type")]
     r#type: String,
-    #[serde(rename = "$value")]
+    #[serde(rename = "$value", default)]
     elements: Vec<PnmlElement>,
 }
 
+/// Write the elements of a net or page as entries of a map from their tags to their content
+fn serialize_elements<M: SerializeMap>(map: &mut M, elements: &[PnmlElement]) -> Result<(), M::Error> {
+    for element in elements {
+        match element {
+            PnmlElement::Place(place) => map.serialize_entry("place", place)?,
+            PnmlElement::Transition(transition) => map.serialize_entry("transition", transition)?,
+            PnmlElement::Arc(arc) => map.serialize_entry("arc", arc)?,
+            PnmlElement::Page(page) => map.serialize_entry("page", page)?,
+            PnmlElement::ReferencePlace(reference) => map.serialize_entry("referencePlace", reference)?,
+            PnmlElement::ReferenceTransition(reference) => map.serialize_entry("referenceTransition", reference)?,
+            PnmlElement::Other(other) => map.serialize_entry(&other.name, other)?,
+        }
+    }
+    Ok(())
+}
+
+/// The net is written as a map from tags to elements, since other elements have tags which are only known at runtime
+impl Serialize for Net {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("@id", &self.id)?;
+        map.serialize_entry("@type", &self.r#type)?;
+        serialize_elements(&mut map, &self.elements)?;
+        map.end()
+    }
+}
+
+/// A page is written like a net
+impl Serialize for Page {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("@id", &self.id)?;
+        serialize_elements(&mut map, &self.elements)?;
+        map.end()
+    }
+}
+
 /// Internal representation of a PNML file.
 /// This is the format that the PNML file is serialized to and deserialized from.
+/// A file may contain several nets.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Pnml {
-    #[serde(rename = "net")]
-    net: Net,
+    #[serde(rename = "net", default)]
+    nets: Vec<Net>,
+}
+
+impl Pnml {
+    /// The number of nets in the file
+    pub fn len(&self) -> usize {
+        self.nets.len()
+    }
+    /// Returns true if the file contains no net
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+    /// Convert every net of the file, failing with the problems of the first net which has any
+    pub fn try_into_nets<C, W>(self) -> Result<Vec<PetriNet<C, W>>, PnmlError>
+    where
+        C: CapacityFn + FromIterator<(super::PlaceId, super::Capacity)>,
+        W: WeightFn + FromIterator<(super::Arc, super::Weight)>,
+    {
+        self.nets.into_iter().map(convert).collect()
+    }
 }
 
 /// Display a Pnml file as XML
@@ -127,103 +252,580 @@ impl Display for Pnml {
     }
 }
 
-/// Convert a parsed PNML file to a Petri net
-impl<C, W> From<Pnml> for PetriNet<C, W>
+/// Collect what was not interpreted of an element and its labels, or None if everything was
+fn residue(name: &str, extra: Extra, labels: Vec<(&str, Extra)>) -> Option<XmlElement> {
+    let mut element = extra.into_element(name);
+    for (label, extra) in labels {
+        if !extra.is_empty() {
+            element.children.push(extra.into_element(label));
+        }
+    }
+    (!element.is_empty()).then_some(element)
+}
+
+/// Split the residue of an element back into the unknown content of the element itself and that of its labels
+struct Residue {
+    extra: Extra,
+    labels: Vec<XmlElement>,
+}
+
+impl Residue {
+    fn new(residue: Option<&XmlElement>, label_names: &[&str]) -> Self {
+        let Some(residue) = residue else {
+            return Residue { extra: Extra::default(), labels: Vec::new() };
+        };
+        let (labels, children) = residue.children
+            .iter()
+            .cloned()
+            .partition(|child| label_names.contains(&child.name.as_str()));
+        Residue { extra: Extra { attributes: residue.attributes.clone(), children }, labels }
+    }
+    /// The unknown content of a label
+    fn label(&mut self, name: &str) -> Extra {
+        match self.labels.iter().position(|label| label.name == name) {
+            Some(index) => self.labels.remove(index).into(),
+            None => Extra::default(),
+        }
+    }
+    /// Returns true if the label has unknown content which should be written even without a value
+    fn has_label(&self, name: &str) -> bool {
+        self.labels.iter().any(|label| label.name == name)
+    }
+    /// Add the graphics of the layout, unless the element came with its own
+    fn with_graphics(mut self, positions: impl FnOnce() -> Vec<Position>) -> Self {
+        if !self.extra.children.iter().any(|child| child.name == "graphics") {
+            let mut graphics = XmlElement::new("graphics");
+            for position in positions() {
+                let mut element = XmlElement::new("position");
+                element.attributes = vec![("x".to_string(), position.x.to_string()), ("y".to_string(), position.y.to_string())];
+                graphics.children.push(element);
+            }
+            if !graphics.children.is_empty() {
+                self.extra.children.push(graphics);
+            }
+        }
+        self
+    }
+}
+
+/// A place or transition which an arc can refer to
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Place(super::PlaceId),
+    Transition(super::TransitionId),
+}
+
+/// A problem which keeps a PNML file from being converted to a Petri net
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Another element of the net has the same ID
+    DuplicateId,
+    /// The source of an arc is not a place or transition of the net
+    UnknownSource(String),
+    /// The target of an arc is not a place or transition of the net
+    UnknownTarget(String),
+    /// An arc connects two places or two transitions
+    SameKind,
+    /// The text of a label is not a non-negative number
+    Malformed { label: &'static str, text: String },
+    /// An arc has a weight of 0
+    ZeroWeight,
+    /// An arc has the same source and target as the arc with this ID
+    DuplicateArc(String),
+    /// The kind of net which is loaded does not allow this weight, e.g. an EC net any weight other than 1
+    UnsupportedWeight(usize),
+    /// The kind of net which is loaded does not allow this capacity, e.g. an EC net any capacity other than 1
+    UnsupportedCapacity(usize),
+    /// A place initially holds more tokens than its capacity
+    ExceedsCapacity { tokens: usize, capacity: usize },
+    /// A reference place or transition does not lead to a node of its kind, or is part of a cycle of references
+    UnresolvedReference(String),
+    /// A file with this number of nets was converted to a single net
+    NotOneNet(usize),
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Problem::DuplicateId => write!(f, "duplicate ID"),
+            Problem::UnknownSource(source) => write!(f, "unknown source \"{}\"", source),
+            Problem::UnknownTarget(target) => write!(f, "unknown target \"{}\"", target),
+            Problem::SameKind => write!(f, "arc connects two places or two transitions"),
+            Problem::Malformed { label, text } => write!(f, "{} \"{}\" is not a non-negative number", label, text),
+            Problem::ZeroWeight => write!(f, "weight must be at least 1"),
+            Problem::DuplicateArc(arc) => write!(f, "same source and target as arc \"{}\"", arc),
+            Problem::UnsupportedWeight(weight) => write!(f, "{}", unsupported(format_args!("weight {}", weight))),
+            Problem::UnsupportedCapacity(capacity) => write!(f, "{}", unsupported(format_args!("capacity {}", capacity))),
+            Problem::ExceedsCapacity { tokens, capacity } => {
+                write!(f, "initial marking of {} exceeds the capacity of {}", tokens, capacity)
+            }
+            Problem::UnresolvedReference(reference) => write!(f, "reference to \"{}\" does not lead to a node of the same kind", reference),
+            Problem::NotOneNet(count) => write!(f, "expected one net, but found {}", count),
+        }
+    }
+}
+
+/// Every problem found in a PNML file, each with the ID of the offending element
+#[derive(Clone, PartialEq, Eq)]
+pub struct PnmlError {
+    /// The net with the problems, or None if they concern the file as a whole
+    net: Option<String>,
+    problems: Vec<(String, Problem)>,
+}
+
+impl PnmlError {
+    /// The problems, each with the ID of the offending element.
+    /// Problems with the places and transitions come first in the order of the file, then those with the reference nodes
+    /// and the arcs, and finally the capacities, weights and initial markings which this kind of net cannot represent.
+    pub fn problems(&self) -> &[(String, Problem)] {
+        &self.problems
+    }
+}
+
+/// PNML errors are displayed with one problem per line, e.g.
+/// ```text
+/// 2 problems in net "mutex":
+///   a3: unknown target "t9"
+///   p1: initialMarking "-1" is not a non-negative number
+/// ```
+impl Debug for PnmlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let plural = if self.problems.len() == 1 { "" } else { "s" };
+        match &self.net {
+            Some(net) => write!(f, "{} problem{} in net \"{}\":", self.problems.len(), plural, net)?,
+            None => write!(f, "{} problem{} in file:", self.problems.len(), plural)?,
+        }
+        for (element, problem) in &self.problems {
+            write!(f, "\n  {}: {}", element, problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for PnmlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+
+impl std::error::Error for PnmlError {}
+
+/// Parse the number of a label, leaving surrounding whitespace aside
+fn amount(label: &'static str, text: &str) -> Result<usize, Problem> {
+    text.trim().parse().map_err(|_| Problem::Malformed { label, text: text.to_string() })
+}
+
+/// Where the elements of a page go when it is flattened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    /// The elements directly in the net
+    Net,
+    /// The first page, whose other elements such as its name are kept
+    FirstPage,
+    /// Any other page, whose other elements are dropped
+    OtherPage,
+}
+
+/// Move the nodes and arcs of pages, and of pages within them, into one list in the order of the file.
+/// The first page keeps its ID and other elements, so that the net can be written back on a page.
+fn flatten(elements: Vec<PnmlElement>, flat: &mut Vec<PnmlElement>, page: &mut Option<XmlElement>, level: Level) {
+    for element in elements {
+        match element {
+            PnmlElement::Page(inner) => {
+                let level = match page {
+                    Some(_) => Level::OtherPage,
+                    None => {
+                        let mut first = XmlElement::new("page");
+                        first.attributes.push(("id".to_string(), inner.id));
+                        *page = Some(first);
+                        Level::FirstPage
+                    }
+                };
+                flatten(inner.elements, flat, page, level);
+            }
+            PnmlElement::Other(other) => match (level, page.as_mut()) {
+                (Level::Net, _) => flat.push(PnmlElement::Other(other)),
+                (Level::FirstPage, Some(page)) => page.children.push(other),
+                _ => {}
+            },
+            element => flat.push(element),
+        }
+    }
+}
+
+/// Convert a parsed PNML file with a single net to a Petri net, keeping everything it does not interpret as annotations.
+/// The conversion fails with every problem of the file instead of analyzing a different net than the one described.
+impl<C, W> TryFrom<Pnml> for PetriNet<C, W>
 where
     C: CapacityFn + FromIterator<(super::PlaceId, super::Capacity)>,
     W: WeightFn + FromIterator<(super::Arc, super::Weight)>,
 {
-    fn from(pnml: Pnml) -> Self {
-        let mut places = Vec::new();
-        let mut transitions = Vec::new();
-        let mut arcs = Vec::new();
-        let mut capacities = Vec::new();
-        let mut weights = Vec::new();
-        let mut initial_marking = super::Marking::default();
-
-        for element in pnml.net.elements {
-            match element {
-                PnmlElement::Place(place) => {
-                    if let Ok(id) = place.id.parse() {
-                        if let Some(capacity) = place.capacity {
-                            capacities.push((id, super::Capacity(capacity.amount)));
-                        }
-                        if let Some(marking) = place.initial_marking {
-                            initial_marking.set(id, super::Tokens(marking.amount));
-                        }
-                        places.push(super::Place { id, name: place.name.text });
-                    }
-                },
-                PnmlElement::Transition(transition) => {
-                    if let Ok(id) = transition.id.parse() {
-                        transitions.push(super::Transition { id, name: transition.name.text })
+    type Error = PnmlError;
+
+    fn try_from(mut pnml: Pnml) -> Result<Self, PnmlError> {
+        match pnml.nets.len() {
+            1 => convert(pnml.nets.remove(0)),
+            count => Err(PnmlError { net: None, problems: vec![("pnml".to_string(), Problem::NotOneNet(count))] }),
+        }
+    }
+}
+
+/// Convert a net of a PNML file, with its pages flattened and its reference nodes resolved
+fn convert<C, W>(net: Net) -> Result<PetriNet<C, W>, PnmlError>
+where
+    C: CapacityFn + FromIterator<(super::PlaceId, super::Capacity)>,
+    W: WeightFn + FromIterator<(super::Arc, super::Weight)>,
+{
+    let mut places = Vec::new();
+    let mut transitions = Vec::new();
+    let mut arcs = Vec::new();
+    let mut capacities = Vec::new();
+    let mut weights = Vec::new();
+    let mut initial_marking = super::Marking::default();
+    let mut annotations = Annotations::default();
+    let mut problems = Vec::new();
+
+    // Places and transitions get dense IDs in the order in which they appear, whatever their IDs in the file.
+    // The arcs are resolved once all of them are known, since they may refer to elements further down.
+    let mut nodes: HashMap<String, Node> = HashMap::new();
+    let mut pnml_arcs = Vec::new();
+    let mut references = Vec::new();
+    let mut elements = Vec::new();
+    flatten(net.elements, &mut elements, &mut annotations.page, Level::Net);
+    for element in elements {
+        match element {
+            PnmlElement::Place(place) => {
+                let id = super::PlaceId(places.len());
+                let mut labels = vec![("name", place.name.extra)];
+                if let Some(capacity) = place.capacity {
+                    match amount("capacity", &capacity.amount) {
+                        Ok(amount) => capacities.push((id, super::Capacity(amount))),
+                        Err(problem) => problems.push((place.id.clone(), problem)),
                     }
-                },
-                PnmlElement::Arc(pnml) => {
-                    // Parse the source and target IDs to determine the arc type
-                    let arc = if let (Ok(source), Ok(target)) = (pnml.source.parse(), pnml.target.parse()) {
-                        super::Arc::PlaceTransition(source, target)
-                    } else if let (Ok(source), Ok(target)) = (pnml.source.parse(), pnml.target.parse()) {
-                        super::Arc::TransitionPlace(source, target)
-                    } else {
-                        continue // Skip arcs with invalid source or target IDs. Might change to an error later
-                    };
-                    if let Some(weight) = pnml.weight {
-                        weights.push((arc, super::Weight(weight.amount)));
+                    labels.push(("capacity", capacity.extra));
+                }
+                if let Some(marking) = place.initial_marking {
+                    match amount("initialMarking", &marking.amount) {
+                        Ok(amount) => initial_marking.set(id, super::Tokens(amount)),
+                        Err(problem) => problems.push((place.id.clone(), problem)),
                     }
-                    arcs.push(arc);
+                    labels.push(("initialMarking", marking.extra));
                 }
+                if let Some(residue) = residue("place", place.extra, labels) {
+                    annotations.places.insert(id, residue);
+                }
+                if nodes.insert(place.id.clone(), Node::Place(id)).is_some() {
+                    problems.push((place.id.clone(), Problem::DuplicateId));
+                }
+                annotations.ids.places.insert(id, place.id);
+                places.push(super::Place { id, name: place.name.text });
+            },
+            PnmlElement::Transition(transition) => {
+                let id = super::TransitionId(transitions.len());
+                if let Some(residue) = residue("transition", transition.extra, vec![("name", transition.name.extra)]) {
+                    annotations.transitions.insert(id, residue);
+                }
+                if nodes.insert(transition.id.clone(), Node::Transition(id)).is_some() {
+                    problems.push((transition.id.clone(), Problem::DuplicateId));
+                }
+                annotations.ids.transitions.insert(id, transition.id);
+                transitions.push(super::Transition { id, name: transition.name.text })
+            },
+            PnmlElement::Arc(arc) => pnml_arcs.push(arc),
+            PnmlElement::ReferencePlace(reference) => references.push((reference, true)),
+            PnmlElement::ReferenceTransition(reference) => references.push((reference, false)),
+            PnmlElement::Page(_) => {} // Pages were flattened
+            PnmlElement::Other(element) => annotations.net.push(element),
+        }
+    }
+    // A reference stands for the node it refers to, possibly through further references
+    let targets: HashMap<&str, &str> = references
+        .iter()
+        .map(|(reference, _)| (reference.id.as_str(), reference.reference.as_str()))
+        .collect();
+    for (reference, is_place) in &references {
+        let mut target = reference.reference.as_str();
+        for _ in 0..references.len() {
+            match targets.get(target) {
+                Some(next) => target = next,
+                None => break,
+            }
+        }
+        let node = match (nodes.get(target), is_place) {
+            (Some(&node @ Node::Place(_)), true) | (Some(&node @ Node::Transition(_)), false) => node,
+            _ => {
+                problems.push((reference.id.clone(), Problem::UnresolvedReference(reference.reference.clone())));
+                continue;
+            }
+        };
+        if nodes.contains_key(&reference.id) {
+            problems.push((reference.id.clone(), Problem::DuplicateId));
+        } else {
+            nodes.insert(reference.id.clone(), node);
+        }
+    }
+    let mut arc_ids = HashSet::new();
+    for pnml in pnml_arcs {
+        if nodes.contains_key(&pnml.id) || !arc_ids.insert(pnml.id.clone()) {
+            problems.push((pnml.id.clone(), Problem::DuplicateId));
+        }
+        // Look up the source and target to determine the arc type
+        let arc = match (nodes.get(&pnml.source), nodes.get(&pnml.target)) {
+            (Some(&Node::Place(source)), Some(&Node::Transition(target))) => super::Arc::PlaceTransition(source, target),
+            (Some(&Node::Transition(source)), Some(&Node::Place(target))) => super::Arc::TransitionPlace(source, target),
+            (None, _) => {
+                problems.push((pnml.id, Problem::UnknownSource(pnml.source)));
+                continue;
             }
+            (_, None) => {
+                problems.push((pnml.id, Problem::UnknownTarget(pnml.target)));
+                continue;
+            }
+            _ => {
+                problems.push((pnml.id, Problem::SameKind));
+                continue;
+            }
+        };
+        // A net has at most one arc between two nodes, its weight says how many tokens move along it
+        if let Some(first) = annotations.ids.arcs.get(&arc) {
+            problems.push((pnml.id, Problem::DuplicateArc(first.clone())));
+            continue;
+        }
+        let mut labels = Vec::new();
+        if let Some(mut name) = pnml.legacy_name {
+            name.children.retain(|child| child.name != "inscription");
+            labels.push(("name", name));
         }
-        
-        let id = pnml.net.id;
-        let capacities = capacities.into_iter().collect();
-        let weights = weights.into_iter().collect();
+        let inscription = match (pnml.inscription, pnml.legacy_weight) {
+            (Some(inscription), _) => Some(("inscription", inscription)),
+            (None, weight) => weight.map(|weight| ("weight", weight)),
+        };
+        if let Some((label, inscription)) = inscription {
+            match amount(label, &inscription.amount) {
+                Ok(0) => problems.push((pnml.id.clone(), Problem::ZeroWeight)),
+                Ok(amount) => weights.push((arc, super::Weight(amount))),
+                Err(problem) => problems.push((pnml.id.clone(), problem)),
+            }
+            labels.push(("inscription", inscription.extra));
+        }
+        if let Some(residue) = residue("arc", pnml.extra, labels) {
+            annotations.arcs.insert(arc, residue);
+        }
+        annotations.ids.arcs.insert(arc, pnml.id);
+        arcs.push(arc);
+    }
 
-        PetriNet {
-            id,
-            places,
-            transitions,
-            arcs,
-            capacities,
-            weights,
-            initial_marking,
+    // The capacity and weight functions keep what the kind of net can represent, and drop the rest
+    let capacity_fn: C = capacities.iter().copied().collect();
+    let weight_fn: W = weights.iter().copied().collect();
+    for (place, capacity) in capacities {
+        if capacity_fn.get_or_default(&place) != capacity {
+            problems.push((annotations.ids.places[&place].clone(), Problem::UnsupportedCapacity(capacity.0)));
         }
     }
+    for (arc, weight) in weights {
+        if weight_fn.get_or_default(&arc) != weight {
+            problems.push((annotations.ids.arcs[&arc].clone(), Problem::UnsupportedWeight(weight.0)));
+        }
+    }
+    for (place, tokens) in initial_marking.iter() {
+        let capacity = capacity_fn.get_or_default(&place).0;
+        if tokens.0 > capacity {
+            problems.push((annotations.ids.places[&place].clone(), Problem::ExceedsCapacity { tokens: tokens.0, capacity }));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(PnmlError { net: Some(net.id), problems });
+    }
+
+    Ok(PetriNet {
+        id: net.id,
+        places,
+        transitions,
+        arcs,
+        capacities: capacity_fn,
+        weights: weight_fn,
+        initial_marking,
+        annotations,
+    })
 }
 
-/// Convert a Petri net to a PNML file
+/// Convert a Petri net to a PNML file with this net only
 impl<C: CapacityFn, W: WeightFn> From<PetriNet<C, W>> for Pnml {
     fn from(net: PetriNet<C, W>) -> Self {
+        std::iter::once(net).collect()
+    }
+}
+
+/// Collect Petri nets into one PNML file
+impl<C: CapacityFn, W: WeightFn> FromIterator<PetriNet<C, W>> for Pnml {
+    fn from_iter<I: IntoIterator<Item = PetriNet<C, W>>>(nets: I) -> Self {
+        Pnml { nets: nets.into_iter().map(Net::from).collect() }
+    }
+}
+
+/// Convert a Petri net to a PNML net, with the annotations it was loaded with.
+/// Unless the annotations contain graphics, the graphics of an automatic layout are added.
+/// If the net was loaded from pages, its elements are written on its first page.
+impl<C: CapacityFn, W: WeightFn> From<PetriNet<C, W>> for Net {
+    fn from(net: PetriNet<C, W>) -> Self {
+        let layout = (!net.annotations.has_graphics()).then(|| net.layout());
+        // Weights and capacities go beyond the core model
+        let r#type = if net.arcs.iter().any(|arc| net.weights.get(arc).is_some())
+            || net.places.iter().any(|place| net.capacities.get(&place.id).is_some())
+        {
+            PT_NET
+        } else {
+            CORE_MODEL
+        };
+        let annotations = &net.annotations;
+        let ids = &annotations.ids;
+        let mut allocator = IdAllocator::new(ids.places.values().chain(ids.transitions.values()).chain(ids.arcs.values()));
+        let mut place_ids = HashMap::new();
+        let mut transition_ids = HashMap::new();
         let mut elements = Vec::new();
         for place in net.places {
-            let id = format!("{}", place.id);
-            let name = Name { text: place.name };
-            let initial_marking = net.initial_marking.get(&place.id).into();
-            let capacity = net.capacities.get(&place.id).map(Into::into);
-            elements.push(PnmlElement::Place(Place { id, name, initial_marking, capacity }));
+            let mut residue = Residue::new(annotations.places.get(&place.id), &["name", "initialMarking", "capacity"])
+                .with_graphics(|| layout.iter().map(|layout| layout.place(place.id)).collect());
+            let id = allocator.allocate(ids.places.get(&place.id), place.id.to_string());
+            place_ids.insert(place.id, id.clone());
+            let name = Name { text: place.name, extra: residue.label("name") };
+            let tokens = net.initial_marking.get(&place.id).0;
+            let initial_marking = (tokens > 0 || residue.has_label("initialMarking"))
+                .then(|| InitialMarking { amount: tokens.to_string(), extra: residue.label("initialMarking") });
+            let capacity = net.capacities
+                .get(&place.id)
+                .map(|capacity| Capacity { amount: capacity.0.to_string(), extra: residue.label("capacity") });
+            elements.push(PnmlElement::Place(Place { id, name, initial_marking, capacity, extra: residue.extra }));
         }
         for transition in net.transitions {
-            let id = format!("{}", transition.id);
-            let name = Name { text: transition.name };
-            elements.push(PnmlElement::Transition(Transition { id, name }));
+            let mut residue = Residue::new(annotations.transitions.get(&transition.id), &["name"])
+                .with_graphics(|| layout.iter().map(|layout| layout.transition(transition.id)).collect());
+            let id = allocator.allocate(ids.transitions.get(&transition.id), transition.id.to_string());
+            transition_ids.insert(transition.id, id.clone());
+            let name = Name { text: transition.name, extra: residue.label("name") };
+            elements.push(PnmlElement::Transition(Transition { id, name, extra: residue.extra }));
         }
         for arc in net.arcs {
+            let mut residue = Residue::new(annotations.arcs.get(&arc), &["inscription"])
+                .with_graphics(|| layout.iter().flat_map(|layout| layout.bends(&arc).to_vec()).collect());
             let (source, target) = match arc {
-                super::Arc::PlaceTransition(source, target) => (format!("{}", source), format!("{}", target)),
-                super::Arc::TransitionPlace(source, target) => (format!("{}", source), format!("{}", target)),
+                super::Arc::PlaceTransition(source, target) => (place_ids[&source].clone(), transition_ids[&target].clone()),
+                super::Arc::TransitionPlace(source, target) => (transition_ids[&source].clone(), place_ids[&target].clone()),
             };
-            let id = format!("a_{}_{}", source, target);
-            let name = Inscription { inscription: String::new() }; // TODO: Add support for arc inscriptions in the petri_net module
-            let weight = net.weights.get(&arc).map(Into::into);
-            elements.push(PnmlElement::Arc(Arc { id, source, target, name, weight }));
-        }
-        Pnml {
-            net: Net {
-                id: net.id,
-                r#type: PNML_NAMESPACE.to_string(),
-                elements
+            let id = allocator.allocate(ids.arcs.get(&arc), format!("a_{}_{}", source, target));
+            let weight = net.weights.get(&arc);
+            let inscription = (weight.is_some() || residue.has_label("inscription")).then(|| Inscription {
+                amount: net.weights.get_or_default(&arc).0.to_string(),
+                extra: residue.label("inscription"),
+            });
+            elements.push(PnmlElement::Arc(Arc {
+                id,
+                source,
+                target,
+                inscription,
+                legacy_name: None,
+                legacy_weight: None,
+                extra: residue.extra,
+            }));
+        }
+        let mut children: Vec<PnmlElement> = annotations.net.iter().cloned().map(PnmlElement::Other).collect();
+        match &annotations.page {
+            Some(page) => {
+                let mut contents: Vec<PnmlElement> = page.children.iter().cloned().map(PnmlElement::Other).collect();
+                contents.extend(elements);
+                let id = page.attribute("id").unwrap_or_default().to_string();
+                children.push(PnmlElement::Page(Page { id, elements: contents }));
             }
+            None => children.extend(elements),
         }
+        Net {
+            id: net.id,
+            r#type: r#type.to_string(),
+            elements: children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, ECNet, PTNet};
+
+    #[test]
+    fn test_validation() -> Result<(), Box<dyn std::error::Error>> {
+        let load = || parse_xml::<_, Pnml>(include_str!("../../models/broken.pnml").as_bytes());
+        let error = PTNet::try_from(load()?).unwrap_err();
+        let problems: Vec<(&str, &Problem)> = error.problems().iter().map(|(id, problem)| (id.as_str(), problem)).collect();
+        assert_eq!(problems, [
+            ("p1", &Problem::Malformed { label: "initialMarking", text: "-1".to_string() }),
+            ("p2", &Problem::DuplicateId),
+            ("r1", &Problem::UnresolvedReference("p1".to_string())),
+            ("a2", &Problem::UnknownTarget("t9".to_string())),
+            ("a3", &Problem::SameKind),
+            ("a4", &Problem::ZeroWeight),
+            ("a5", &Problem::DuplicateArc("a1".to_string())),
+            ("p2", &Problem::ExceedsCapacity { tokens: 3, capacity: 2 }),
+        ]);
+        assert!(error.to_string().starts_with("8 problems in net \"broken\":\n  p1: initialMarking \"-1\""));
+
+        // An EC net can represent neither the capacity nor the weight
+        let error = ECNet::try_from(load()?).unwrap_err();
+        assert!(error.problems().contains(&("p2".to_string(), Problem::UnsupportedCapacity(2))));
+        assert!(error.problems().contains(&("a1".to_string(), Problem::UnsupportedWeight(2))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_inscriptions() -> Result<(), Box<dyn std::error::Error>> {
+        // Weights are read from standard inscriptions as well as from the labels written by older versions
+        let pnml: Pnml = parse_xml(include_str!("../../models/weighted.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let weights: Vec<usize> = net.arcs.iter().map(|arc| net.weights.get_or_default(arc).0).collect();
+        assert_eq!(weights, [2, 3]);
+        assert_eq!(net.reachability_analysis().rows.len(), 3);
+
+        // Weights are written as inscriptions of a P/T net
+        let xml = Pnml::from(net).to_string();
+        assert!(xml.contains(PT_NET));
+        assert!(xml.contains("<inscription>\n        <text>3</text>\n      </inscription>"), "{}", xml);
+        assert!(!xml.contains("<weight>") && !xml.contains("<inscription/>"));
+
+        // Nets without weights and capacities only need the core model
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let xml = Pnml::from(net).to_string();
+        assert!(xml.contains(CORE_MODEL));
+        assert!(!xml.contains("<inscription"));
+        Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pages() -> Result<(), Box<dyn std::error::Error>> {
+        let load = || parse_xml::<_, Pnml>(include_str!("../../models/pages.pnml").as_bytes());
+        let error = ECNet::try_from(load()?).unwrap_err();
+        assert_eq!(error.problems(), [("pnml".to_string(), Problem::NotOneNet(2))]);
+
+        // The nodes of nested pages are collected, and arcs through references connect the nodes referred to
+        let nets: Vec<ECNet> = load()?.try_into_nets()?;
+        assert_eq!(nets.iter().map(|net| net.id.as_str()).collect::<Vec<_>>(), ["pipeline", "single"]);
+        let net = &nets[0];
+        assert_eq!(net.places.len(), 3);
+        assert_eq!(net.transitions.len(), 2);
+        let parsed = net.annotations.ids.find_place("parsed").unwrap();
+        let emit = net.annotations.ids.find_transition("emit").unwrap();
+        assert!(net.arcs.contains(&crate::petri_net::Arc::PlaceTransition(parsed, emit)));
+        assert_eq!(net.reachability_analysis().rows.len(), 3);
+
+        // The net is written back on its first page, and all nets are written to one file
+        let xml = Pnml::from(net.clone()).to_string();
+        assert!(xml.contains("<page id=\"top\">\n      <name>\n        <text>Overview</text>"), "{}", xml);
+        assert!(!xml.contains("reference"));
+        let reloaded: ECNet = parse_xml::<_, Pnml>(xml.as_bytes())?.try_into()?;
+        assert_eq!(reloaded.arcs, net.arcs);
+        let pnml: Pnml = nets.into_iter().collect();
+        assert_eq!(pnml.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! This module defines atomic propositions about the markings of a Petri net.
+//! They are the building blocks of the temporal logic formulas which can be checked against a net.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::{Marking, MarkingFn, PlaceId, TransitionId};
+
+/// An integer expression which can be evaluated in a marking
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expression {
+    /// A constant number
+    Constant(usize),
+    /// The total number of tokens on a set of places
+    Tokens(Vec<PlaceId>),
+}
+
+impl Expression {
+    /// Evaluate the expression in a marking
+    pub fn evaluate(&self, marking: &Marking) -> usize {
+        match self {
+            Expression::Constant(constant) => *constant,
+            Expression::Tokens(places) => places.iter().map(|place| marking.get(place).0).sum(),
+        }
+    }
+}
+
+/// Constants are displayed as numbers, token counts as a sum of place IDs, e.g. P1 + P2
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Expression::Constant(constant) => write!(f, "{}", constant),
+            Expression::Tokens(places) if places.is_empty() => write!(f, "0"),
+            Expression::Tokens(places) => {
+                let mut separator = "";
+                for place in places {
+                    write!(f, "{}{}", separator, place)?;
+                    separator = " + ";
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A comparison between two integer expressions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, derive_more::Display)]
+pub enum Comparison {
+    #[display(fmt = "<")]
+    Less,
+    #[display(fmt = "<=")]
+    LessEqual,
+    #[display(fmt = "==")]
+    Equal,
+    #[display(fmt = "!=")]
+    NotEqual,
+    #[display(fmt = ">=")]
+    GreaterEqual,
+    #[display(fmt = ">")]
+    Greater,
+}
+
+impl Comparison {
+    /// Compare two numbers
+    pub fn holds(self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::GreaterEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
+/// An atomic proposition which is either true or false in a given marking
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Proposition {
+    /// Compares two expressions, e.g. P1 + P2 <= 1
+    Compare(Expression, Comparison, Expression),
+    /// The transition is enabled
+    Fireable(TransitionId),
+    /// No transition is enabled
+    Deadlock,
+}
+
+impl Proposition {
+    /// Evaluate the proposition in a marking, given the transitions which are enabled in that marking
+    pub fn holds(&self, marking: &Marking, enabled: &[TransitionId]) -> bool {
+        match self {
+            Proposition::Compare(left, comparison, right) => {
+                comparison.holds(left.evaluate(marking), right.evaluate(marking))
+            }
+            Proposition::Fireable(transition) => enabled.contains(transition),
+            Proposition::Deadlock => enabled.is_empty(),
+        }
+    }
+}
+
+impl Display for Proposition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Proposition::Compare(left, comparison, right) => write!(f, "{} {} {}", left, comparison, right),
+            Proposition::Fireable(transition) => write!(f, "fireable({})", transition),
+            Proposition::Deadlock => write!(f, "deadlock"),
+        }
+    }
+}
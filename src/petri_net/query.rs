@@ -0,0 +1,510 @@
+//! This module parses properties written as text into CTL and LTL formulas.
+//!
+//! The syntax follows the usual notation of temporal logics, for example
+//!
+//! ```text
+//! AG (EF P5 >= 1)
+//! EF (P3 >= 2 && !fireable(T4))
+//! G (fired("send request") -> F fired("grant"))
+//! E["waiting" + "ready" <= 1 U deadlock]
+//! ```
+//!
+//! - Boolean connectives: `!`, `&&`, `||`, `->` (from strongest to weakest binding), `true` and `false`
+//! - CTL operators: `EX`, `AX`, `EF`, `AF`, `EG`, `AG`, `E[a U b]` and `A[a U b]`
+//! - LTL operators: `X`, `F`, `G`, `a U b` and `a R b`
+//! - Comparisons between sums of places and numbers: `<`, `<=`, `==`, `!=`, `>=`, `>`
+//! - `fireable(t)` holds if the transition is enabled, `deadlock` if no transition is enabled,
+//!   and `fired(t)` (LTL only) if the transition was fired in the last step
+//!
+//! Places and transitions are referred to either by their ID or by their name.
+//! Names which contain spaces or clash with keywords can be written in double quotes.
+
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use super::proposition::{Comparison, Expression, Proposition};
+use super::{CapacityFn, Ctl, Ltl, PetriNet, PlaceId, TransitionId, WeightFn};
+
+/// An error in a textual query, pointing at the offending part of the query
+#[derive(Clone, PartialEq, Eq)]
+pub struct QueryError {
+    query: String,
+    /// Byte offset of the error in the query
+    position: usize,
+    message: String,
+}
+
+impl QueryError {
+    fn new(query: &str, position: usize, message: impl Into<String>) -> Self {
+        QueryError { query: query.to_string(), position, message: message.into() }
+    }
+    /// The (1-based) column at which the error occurred
+    pub fn column(&self) -> usize {
+        self.query[..self.position].chars().count() + 1
+    }
+    /// A description of the error
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Query errors are displayed with the query and a caret pointing at the error, e.g.
+/// ```text
+/// error at column 4: unknown place "P9"
+///   EF P9 >= 1
+///      ^
+/// ```
+impl Debug for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "error at column {}: {}", self.column(), self.message)?;
+        writeln!(f, "  {}", self.query)?;
+        write!(f, "  {}^", " ".repeat(self.column() - 1))
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// The tokens of the query language
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A keyword, place or transition
+    Identifier(String),
+    /// A place or transition name in double quotes
+    Quoted(String),
+    Number(usize),
+    /// Punctuation and operators
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Token::Identifier(identifier) => write!(f, "{}", identifier),
+            Token::Quoted(name) => write!(f, "\"{}\"", name),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// Symbols ordered so that longer symbols are matched before their prefixes
+const SYMBOLS: [&str; 17] = [
+    "&&", "||", "->", "<=", ">=", "==", "!=", "(", ")", "[", "]", "!", "+", "<", ">", "=", ",",
+];
+
+/// Split a query into tokens, each with its byte offset
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut rest = query;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let position = query.len() - rest.len();
+        let c = rest.chars().next().expect("rest is not empty");
+        let length = if c == '"' {
+            let end = rest[1..].find('"').ok_or((position, "unterminated name".to_string()))?;
+            tokens.push((position, Token::Quoted(rest[1..=end].to_string())));
+            end + 2
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push((position, Token::Symbol(symbol)));
+            symbol.len()
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            match word.parse() {
+                Ok(number) => tokens.push((position, Token::Number(number))),
+                Err(_) => tokens.push((position, Token::Identifier(word.to_string()))),
+            }
+            end
+        } else {
+            return Err((position, format!("unexpected character '{}'", c)));
+        };
+        rest = &rest[length..];
+    }
+    Ok(tokens)
+}
+
+/// A parsed formula before it is checked to be valid CTL or LTL.
+/// Temporal operators and firings remember their position for error messages.
+#[derive(Debug, Clone)]
+enum Ast {
+    True,
+    False,
+    Atom(Proposition),
+    Fired(usize, TransitionId),
+    Not(Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    Implies(Box<Ast>, Box<Ast>),
+    /// A unary temporal operator such as EX or G
+    Unary(usize, &'static str, Box<Ast>),
+    /// A binary temporal operator: EU, AU, U or R
+    Binary(usize, &'static str, Box<Ast>, Box<Ast>),
+}
+
+const UNARY_OPERATORS: [&str; 9] = ["EX", "AX", "EF", "AF", "EG", "AG", "X", "F", "G"];
+
+/// A recursive descent parser for the query language
+struct Parser<'a, C: CapacityFn, W: WeightFn> {
+    net: &'a PetriNet<C, W>,
+    query: &'a str,
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Set while parsing the left side of E[a U b] or A[a U b], where U ends the operand
+    quantified_until: bool,
+}
+
+impl<C: CapacityFn, W: WeightFn> Parser<'_, C, W> {
+    fn error(&self, position: usize, message: impl Into<String>) -> QueryError {
+        QueryError::new(self.query, position, message)
+    }
+    /// The position of the next token, or the end of the query
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.query.len(), |(position, _)| *position)
+    }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+        self.next += 1;
+        token
+    }
+    /// Consume the next token if it is the given symbol or keyword
+    fn accept(&mut self, expected: &str) -> bool {
+        let matches = match self.peek() {
+            Some(Token::Symbol(symbol)) => *symbol == expected,
+            Some(Token::Identifier(identifier)) => identifier == expected,
+            _ => false,
+        };
+        if matches {
+            self.next += 1;
+        }
+        matches
+    }
+    fn expect(&mut self, expected: &str) -> Result<(), QueryError> {
+        if self.accept(expected) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", expected)))
+        }
+    }
+    fn unexpected(&self, expected: &str) -> QueryError {
+        match self.peek() {
+            Some(token) => self.error(self.position(), format!("expected {} but found '{}'", expected, token)),
+            None => self.error(self.position(), format!("expected {} but the query ended", expected)),
+        }
+    }
+    fn parse(mut self) -> Result<Ast, QueryError> {
+        let formula = self.implication()?;
+        if self.peek().is_some() {
+            return Err(self.unexpected("an operator"));
+        }
+        Ok(formula)
+    }
+    fn implication(&mut self) -> Result<Ast, QueryError> {
+        let left = self.disjunction()?;
+        if self.accept("->") {
+            Ok(Ast::Implies(Box::new(left), Box::new(self.implication()?)))
+        } else {
+            Ok(left)
+        }
+    }
+    fn disjunction(&mut self) -> Result<Ast, QueryError> {
+        let mut left = self.conjunction()?;
+        while self.accept("||") {
+            left = Ast::Or(Box::new(left), Box::new(self.conjunction()?));
+        }
+        Ok(left)
+    }
+    fn conjunction(&mut self) -> Result<Ast, QueryError> {
+        let mut left = self.until()?;
+        while self.accept("&&") {
+            left = Ast::And(Box::new(left), Box::new(self.until()?));
+        }
+        Ok(left)
+    }
+    fn until(&mut self) -> Result<Ast, QueryError> {
+        let left = self.unary()?;
+        let position = self.position();
+        let operators: &[&'static str] = if self.quantified_until { &["R"] } else { &["U", "R"] };
+        for &operator in operators {
+            if self.accept(operator) {
+                return Ok(Ast::Binary(position, operator, Box::new(left), Box::new(self.until()?)));
+            }
+        }
+        Ok(left)
+    }
+    fn unary(&mut self) -> Result<Ast, QueryError> {
+        let position = self.position();
+        if self.accept("!") {
+            return Ok(Ast::Not(Box::new(self.unary()?)));
+        }
+        for operator in UNARY_OPERATORS {
+            if self.accept(operator) {
+                return Ok(Ast::Unary(position, operator, Box::new(self.unary()?)));
+            }
+        }
+        for (quantifier, operator) in [("E", "EU"), ("A", "AU")] {
+            if self.accept(quantifier) {
+                self.expect("[")?;
+                let enclosing = std::mem::replace(&mut self.quantified_until, true);
+                let left = self.implication();
+                self.quantified_until = enclosing;
+                let left = left?;
+                self.expect("U")?;
+                let right = self.implication()?;
+                self.expect("]")?;
+                return Ok(Ast::Binary(position, operator, Box::new(left), Box::new(right)));
+            }
+        }
+        self.atom()
+    }
+    fn atom(&mut self) -> Result<Ast, QueryError> {
+        let position = self.position();
+        if self.accept("(") {
+            // Parentheses shield an until from an enclosing E[a U b]
+            let enclosing = std::mem::replace(&mut self.quantified_until, false);
+            let formula = self.implication();
+            self.quantified_until = enclosing;
+            let formula = formula?;
+            self.expect(")")?;
+            return Ok(formula);
+        }
+        if self.accept("true") {
+            return Ok(Ast::True);
+        }
+        if self.accept("false") {
+            return Ok(Ast::False);
+        }
+        if self.accept("deadlock") {
+            return Ok(Ast::Atom(Proposition::Deadlock));
+        }
+        for function in ["fireable", "fired"] {
+            if self.accept(function) {
+                self.expect("(")?;
+                let transition = self.transition()?;
+                self.expect(")")?;
+                return Ok(match function {
+                    "fireable" => Ast::Atom(Proposition::Fireable(transition)),
+                    _ => Ast::Fired(position, transition),
+                });
+            }
+        }
+        let left = self.expression()?;
+        let comparison = match self.advance() {
+            Some(Token::Symbol("<")) => Comparison::Less,
+            Some(Token::Symbol("<=")) => Comparison::LessEqual,
+            Some(Token::Symbol("==" | "=")) => Comparison::Equal,
+            Some(Token::Symbol("!=")) => Comparison::NotEqual,
+            Some(Token::Symbol(">=")) => Comparison::GreaterEqual,
+            Some(Token::Symbol(">")) => Comparison::Greater,
+            _ => {
+                self.next -= 1;
+                return Err(self.unexpected("a comparison"));
+            }
+        };
+        let right = self.expression()?;
+        Ok(Ast::Atom(Proposition::Compare(left, comparison, right)))
+    }
+    /// A number or a sum of places
+    fn expression(&mut self) -> Result<Expression, QueryError> {
+        if let Some(&Token::Number(number)) = self.peek() {
+            self.next += 1;
+            return Ok(Expression::Constant(number));
+        }
+        let mut places = vec![self.place()?];
+        while self.accept("+") {
+            places.push(self.place()?);
+        }
+        Ok(Expression::Tokens(places))
+    }
+    /// The name or ID of a place or transition
+    fn reference(&mut self, kind: &str) -> Result<(usize, String), QueryError> {
+        let position = self.position();
+        match self.advance() {
+            Some(Token::Identifier(name) | Token::Quoted(name)) => Ok((position, name)),
+            _ => {
+                self.next -= 1;
+                Err(self.unexpected(&format!("a {}", kind)))
+            }
+        }
+    }
+    fn place(&mut self) -> Result<PlaceId, QueryError> {
+        let (position, reference) = self.reference("place")?;
+        match self.net.find_place(&reference) {
+            Lookup::Found(id) => Ok(id),
+            Lookup::Unknown => Err(self.error(position, format!("unknown place \"{}\"", reference))),
+            Lookup::Ambiguous => Err(self.error(position, format!("ambiguous place \"{}\", refer to it by a unique ID or name", reference))),
+        }
+    }
+    fn transition(&mut self) -> Result<TransitionId, QueryError> {
+        let (position, reference) = self.reference("transition")?;
+        match self.net.find_transition(&reference) {
+            Lookup::Found(id) => Ok(id),
+            Lookup::Unknown => Err(self.error(position, format!("unknown transition \"{}\"", reference))),
+            Lookup::Ambiguous => {
+                Err(self.error(position, format!("ambiguous transition \"{}\", refer to it by a unique ID or name", reference)))
+            }
+        }
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Parse a query into an AST, resolving place and transition names in this net
+    fn parse_query(&self, query: &str) -> Result<Ast, QueryError> {
+        let tokens = tokenize(query).map_err(|(position, message)| QueryError::new(query, position, message))?;
+        Parser { net: self, query, tokens, next: 0, quantified_until: false }.parse()
+    }
+    /// Parse a textual query as a CTL formula over this net
+    pub fn parse_ctl(&self, query: &str) -> Result<Ctl, QueryError> {
+        fn convert(query: &str, ast: Ast) -> Result<Ctl, QueryError> {
+            let convert = |ast: Box<Ast>| convert(query, *ast).map(Box::new);
+            Ok(match ast {
+                Ast::True => Ctl::True,
+                Ast::False => Ctl::False,
+                Ast::Atom(proposition) => Ctl::Atom(proposition),
+                Ast::Fired(position, _) => {
+                    return Err(QueryError::new(query, position, "fired(...) can only be used in LTL formulas"));
+                }
+                Ast::Not(inner) => Ctl::Not(convert(inner)?),
+                Ast::And(left, right) => Ctl::And(convert(left)?, convert(right)?),
+                Ast::Or(left, right) => Ctl::Or(convert(left)?, convert(right)?),
+                Ast::Implies(left, right) => Ctl::Implies(convert(left)?, convert(right)?),
+                Ast::Unary(position, operator, inner) => match operator {
+                    "EX" => Ctl::EX(convert(inner)?),
+                    "AX" => Ctl::AX(convert(inner)?),
+                    "EF" => Ctl::EF(convert(inner)?),
+                    "AF" => Ctl::AF(convert(inner)?),
+                    "EG" => Ctl::EG(convert(inner)?),
+                    "AG" => Ctl::AG(convert(inner)?),
+                    _ => {
+                        let message = format!("{} needs a path quantifier in CTL, e.g. A{} or E{}", operator, operator, operator);
+                        return Err(QueryError::new(query, position, message));
+                    }
+                },
+                Ast::Binary(position, operator, left, right) => match operator {
+                    "EU" => Ctl::EU(convert(left)?, convert(right)?),
+                    "AU" => Ctl::AU(convert(left)?, convert(right)?),
+                    _ => {
+                        let message = format!("{} needs a path quantifier in CTL, e.g. E[a {} b]", operator, operator);
+                        return Err(QueryError::new(query, position, message));
+                    }
+                },
+            })
+        }
+        convert(query, self.parse_query(query)?)
+    }
+    /// Parse a textual query as an LTL formula over this net
+    pub fn parse_ltl(&self, query: &str) -> Result<Ltl, QueryError> {
+        fn convert(query: &str, ast: Ast) -> Result<Ltl, QueryError> {
+            let convert = |ast: Box<Ast>| convert(query, *ast).map(Box::new);
+            Ok(match ast {
+                Ast::True => Ltl::True,
+                Ast::False => Ltl::False,
+                Ast::Atom(proposition) => Ltl::Atom(proposition),
+                Ast::Fired(_, transition) => Ltl::Fired(transition),
+                Ast::Not(inner) => Ltl::Not(convert(inner)?),
+                Ast::And(left, right) => Ltl::And(convert(left)?, convert(right)?),
+                Ast::Or(left, right) => Ltl::Or(convert(left)?, convert(right)?),
+                Ast::Implies(left, right) => Ltl::Implies(convert(left)?, convert(right)?),
+                Ast::Unary(position, operator, inner) => match operator {
+                    "X" => Ltl::Next(convert(inner)?),
+                    "F" => Ltl::Finally(convert(inner)?),
+                    "G" => Ltl::Globally(convert(inner)?),
+                    _ => {
+                        let message = format!("path quantifiers are not allowed in LTL, use {} instead of {}", &operator[1..], operator);
+                        return Err(QueryError::new(query, position, message));
+                    }
+                },
+                Ast::Binary(position, operator, left, right) => match operator {
+                    "U" => Ltl::Until(convert(left)?, convert(right)?),
+                    "R" => Ltl::Release(convert(left)?, convert(right)?),
+                    _ => return Err(QueryError::new(query, position, "path quantifiers are not allowed in LTL, use a U b instead")),
+                },
+            })
+        }
+        convert(query, self.parse_query(query)?)
+    }
+}
+
+/// The outcome of looking up a place or transition by its ID or name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lookup<T> {
+    Found(T),
+    Unknown,
+    /// The reference fits several elements: they share the name,
+    /// or the ID of one in the file is the internal ID of another
+    Ambiguous,
+}
+
+impl<T: Copy + PartialEq> Lookup<T> {
+    /// Resolve a reference by the ID from the file and the internal ID, which must not point to different elements,
+    /// and otherwise by the name
+    fn resolve(original: Option<T>, internal: Option<T>, named: impl FnOnce() -> Vec<T>) -> Self {
+        match (original, internal) {
+            (Some(original), Some(internal)) if original != internal => Lookup::Ambiguous,
+            (Some(id), _) | (None, Some(id)) => Lookup::Found(id),
+            (None, None) => match named()[..] {
+                [id] => Lookup::Found(id),
+                [] => Lookup::Unknown,
+                _ => Lookup::Ambiguous,
+            },
+        }
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Find a place by the ID it had in the file the net was loaded from, by its ID,
+    /// or by its name if no place has that ID
+    pub(crate) fn find_place(&self, reference: &str) -> Lookup<PlaceId> {
+        let internal = reference.parse::<PlaceId>().ok().filter(|id| self.places.iter().any(|place| place.id == *id));
+        Lookup::resolve(self.annotations.ids.find_place(reference), internal, || {
+            self.places.iter().filter(|place| place.name == reference).map(|place| place.id).collect()
+        })
+    }
+    /// Find a transition by the ID it had in the file the net was loaded from, by its ID,
+    /// or by its name if no transition has that ID
+    pub(crate) fn find_transition(&self, reference: &str) -> Lookup<TransitionId> {
+        let internal = reference
+            .parse::<TransitionId>()
+            .ok()
+            .filter(|id| self.transitions.iter().any(|transition| transition.id == *id));
+        Lookup::resolve(self.annotations.ids.find_transition(reference), internal, || {
+            self.transitions
+                .iter()
+                .filter(|transition| transition.name == reference)
+                .map(|transition| transition.id)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::petri_net::parallel_net;
+
+    #[test]
+    fn test_parse_query() {
+        let net = parallel_net();
+        let ctl = net.parse_ctl("EF (P3 >= 1 && !fireable(\"join\"))").unwrap();
+        assert_eq!(ctl.to_string(), "EF ((P3 >= 1 && !(fireable(T3))))");
+        let ctl = net.parse_ctl("A[start == 1 || \"left\" + right > 0 U joined == 1]").unwrap();
+        assert_eq!(ctl.to_string(), "A[(P0 == 1 || P1 + P2 > 0) U P5 == 1]");
+        let ltl = net.parse_ltl("G (fired(split) -> F fired(T3))").unwrap();
+        assert_eq!(ltl.to_string(), "G ((fired(T0) -> F (fired(T3))))");
+
+        let error = net.parse_ctl("EF P9 >= 1").unwrap_err();
+        assert_eq!((error.column(), error.message()), (4, "unknown place \"P9\""));
+        let error = net.parse_ctl("G P0 == 1").unwrap_err();
+        assert_eq!(error.column(), 1);
+        let error = net.parse_ltl("F (P0 == 1").unwrap_err();
+        assert_eq!(error.message(), "expected ')' but the query ended");
+    }
+}
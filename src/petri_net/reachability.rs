@@ -1,11 +1,15 @@
 //! This module performs reachability analysis on a Petri net
 
+use super::observer::{Observer, Statistics};
 use super::{Arc, CapacityFn, PetriNet, PlaceId, TransitionId, WeightFn};
 use derive_more::Display as DeriveDisplay;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::hash::Hash;
+use std::mem::size_of;
+use std::ops::ControlFlow;
+use std::time::Instant;
 
 /// A number of tokens in a place
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, DeriveDisplay)]
@@ -13,9 +17,9 @@ pub struct Tokens(pub usize);
 
 /// A unique ID for a marking in the reachability graph.
 /// Displayed as "M" followed by the ID padded by 3 leading 0s, e.g. M000, M001, M002, ...
-#[derive(Debug, Clone, Copy, DeriveDisplay)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, DeriveDisplay)]
 #[display(fmt = "M{:03}", _0)]
-pub struct MarkingId(usize);
+pub struct MarkingId(pub(super) usize);
 
 /// A marking function is a mapping from place IDs to the number of tokens in each place
 /// It is used to keep track of the current state of the Petri net
@@ -40,6 +44,10 @@ impl Marking {
             .iter()
             .all(|(id, own_tokens)| other.get(id).0 >= own_tokens.0)
     }
+    /// Iterate over the places which carry at least one token, in order of their IDs
+    pub fn iter(&self) -> impl Iterator<Item = (PlaceId, Tokens)> + '_ {
+        self.0.iter().map(|(&id, &tokens)| (id, tokens))
+    }
 }
 
 impl MarkingFn for Marking {
@@ -74,16 +82,51 @@ impl<P: Into<PlaceId>, T: Into<Tokens>> FromIterator<(P, T)> for Marking {
 /// A continuation is a transition that can be fired from a marking, resulting in a new marking.
 /// If the resulting marking has been seen before, the continuation might be a loop.
 /// Displayed as "{T}->{M}", e.g. T0->M000, T1->M001, ...
-#[derive(Debug, Clone, Copy, DeriveDisplay)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveDisplay)]
 #[display(fmt = "{}->{}", _0, _1)]
-pub struct Continuation(TransitionId, MarkingId);
+pub struct Continuation(pub TransitionId, pub MarkingId);
+
+/// A path through the reachability graph which starts at some marking and follows a sequence of continuations.
+/// A trace can end in a loop, in which case the last continuation leads back to the marking
+/// which was reached after `loop_start` steps.
+/// Displayed as the start marking followed by the continuations, e.g. M000 T0->M001 T1->M002
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub start: MarkingId,
+    pub steps: Vec<Continuation>,
+    pub loop_start: Option<usize>,
+}
+
+impl Trace {
+    /// The sequence of transitions fired along the trace
+    pub fn firing_sequence(&self) -> impl Iterator<Item = TransitionId> + '_ {
+        self.steps.iter().map(|step| step.0)
+    }
+    /// The marking reached at the end of the trace
+    pub fn end(&self) -> MarkingId {
+        self.steps.last().map_or(self.start, |step| step.1)
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.start)?;
+        for step in &self.steps {
+            write!(f, " {}", step)?;
+        }
+        if let Some(loop_start) = self.loop_start {
+            let target = loop_start.checked_sub(1).map_or(self.start, |index| self.steps[index].1);
+            write!(f, " (loops back to {})", target)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveDisplay)]
 pub enum Bound {
     #[display(fmt = "{}-Bounded", _0)]
     Bounded(Tokens),
     #[display(fmt = "Unbounded")]
-    #[expect(unused)] // Will be unused until unboundedness checking is implemented
     Unbounded,
 }
 
@@ -104,23 +147,31 @@ impl Ord for Bound {
     }
 }
 
-/// Describes the maximum number of tokens stored on a place at any point in time
+/// Describes the maximum number of tokens stored on a place at any point in time,
+/// and the first marking in which each place reached its maximum
 #[derive(Debug, Clone)]
-pub struct Boundedness(Vec<Bound>);
+pub struct Boundedness {
+    pub(super) bounds: Vec<Bound>,
+    pub(super) witnesses: Vec<MarkingId>,
+}
 
 impl Boundedness {
     /// Creates a new Boundedness object with all places in the net set to 0
     fn new<C: CapacityFn, W: WeightFn>(net: &PetriNet<C, W>) -> Self {
-        let mut vec = vec![Bound::Bounded(Tokens(0)); net.places.len()];
+        let mut bounds = vec![Bound::Bounded(Tokens(0)); net.places.len()];
         // Update the boundedness with the initial marking
         for (place_id, &initial_tokens) in net.initial_marking.0.iter() {
-            vec[place_id.0] = Bound::Bounded(initial_tokens);
+            bounds[place_id.0] = Bound::Bounded(initial_tokens);
         }
-        Self(vec)
+        let witnesses = vec![MarkingId(0); bounds.len()];
+        Self { bounds, witnesses }
     }
     /// Updates the boundedness of a place if the new value is greater than the old value
-    fn update(&mut self, place_id: PlaceId, bound: Bound) {
-        self.0[place_id.0] = std::cmp::max(self.0[place_id.0], bound);
+    fn update(&mut self, place_id: PlaceId, bound: Bound, witness: MarkingId) {
+        if bound > self.bounds[place_id.0] {
+            self.bounds[place_id.0] = bound;
+            self.witnesses[place_id.0] = witness;
+        }
     }
 }
 
@@ -133,10 +184,8 @@ pub enum Live {
     /// Fires a finite and deterministic number of times
     L1,
     /// Fires a finite but non-deterministic number of times
-    #[expect(unused)]
     L2,
     /// Fires a non-deterministically finite or infinite number of times
-    #[expect(unused)]
     L3,
     /// Fires a deterministically infinite number of times
     L4,
@@ -144,7 +193,7 @@ pub enum Live {
 
 /// Liveness is a list of liveness classes for each transition in the Petri net (ID = index)
 #[derive(Debug, Clone)]
-pub struct Liveness(Vec<Live>);
+pub struct Liveness(pub(super) Vec<Live>);
 
 impl Liveness {
     /// Create a new liveness map from a list of transitions
@@ -200,32 +249,55 @@ impl Display for Liveness {
 /// A transition ID and the IDs of its input places and of its output places
 /// This allows for easy checking of whether a transition can fire from a given marking
 #[derive(Debug, Clone)]
-struct TransitionIO {
-    id: TransitionId,
-    inputs: Vec<PlaceId>,
-    outputs: Vec<PlaceId>,
+pub(super) struct TransitionIO {
+    pub(super) id: TransitionId,
+    pub(super) inputs: Vec<PlaceId>,
+    pub(super) outputs: Vec<PlaceId>,
 }
 
 /// Struct for keeping track of the markings we have seen before and their IDs
 /// TODO: Change out the HashMap for a tree-like data structure for tracking paths
 #[derive(Debug, Default)]
-struct Markings {
-    markings: HashMap<Marking, MarkingId, ahash::RandomState>,
+pub(super) struct Markings {
+    pub(super) markings: HashMap<Marking, MarkingId, ahash::RandomState>,
+    /// A rough estimate of the number of bytes occupied by the remembered markings
+    memory: usize,
 }
 
 impl Markings {
     /// Insert a new marking into the map and return its ID
-    fn remember(&mut self, marking: Marking) -> MarkingId {
+    pub(super) fn remember(&mut self, marking: Marking) -> MarkingId {
         let id = MarkingId(self.markings.len());
+        self.memory += size_of::<(Marking, MarkingId)>() + marking.0.len() * size_of::<(PlaceId, Tokens)>();
         self.markings.insert(marking, id);
         id
     }
     /// Get the ID of a marking, if it exists
-    fn look_up(&self, marking: &Marking) -> Option<MarkingId> {
+    pub(super) fn look_up(&self, marking: &Marking) -> Option<MarkingId> {
         self.markings.get(marking).copied()
     }
 }
 
+/// The breadth-first spanning tree of the reachability graph explored so far.
+/// For every discovered marking (ID = index), it stores the marking from which it was discovered first
+/// and the transition which led to it, or None for the initial marking.
+/// Following the predecessors back to the initial marking yields a shortest firing sequence.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Predecessors(pub(super) Vec<Option<(MarkingId, TransitionId)>>);
+
+impl Predecessors {
+    /// A shortest trace from the initial marking to the given marking
+    pub(super) fn trace(&self, mut id: MarkingId) -> Trace {
+        let mut steps = Vec::new();
+        while let Some((predecessor, transition_id)) = self.0[id.0] {
+            steps.push(Continuation(transition_id, id));
+            id = predecessor;
+        }
+        steps.reverse();
+        Trace { start: id, steps, loop_start: None }
+    }
+}
+
 #[derive(Debug, Clone)]
 #[expect(unused)]
 pub struct IncidenceMatrix<'net, C: CapacityFn, W: WeightFn> {
@@ -237,16 +309,17 @@ pub struct IncidenceMatrix<'net, C: CapacityFn, W: WeightFn> {
 /// and each with a list of the transitions that can be fired from them and the IDs of the resulting markings
 #[derive(Debug, Clone)]
 pub struct ReachabilityAnalysis<'net, C: CapacityFn, W: WeightFn> {
-    petri_net: &'net PetriNet<C, W>,
+    pub(super) petri_net: &'net PetriNet<C, W>,
     pub rows: Vec<(MarkingId, Marking, Vec<Continuation>)>,
     pub boundedness: Boundedness,
     pub liveness: Liveness,
+    pub(super) predecessors: Predecessors,
 }
 
 impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
     /// Create a Vec<TransitionIO> for efficient transition firing.
     /// The indices of the transitions in this vector correspond to the indices of the transitions in the Petri net, and their IDs
-    fn transition_io(&self) -> Vec<TransitionIO> {
+    pub(super) fn transition_io(&self) -> Vec<TransitionIO> {
         let mut transitions = Vec::with_capacity(self.transitions.len());
         // For each transition in the net, collect its ID, input places, and output places
         for transition in &self.transitions {
@@ -296,16 +369,9 @@ impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
     /// Fires all enabled transitions in the Petri net from the provided marking,
     /// and returns a list of the resulting markings.
     /// This attempts to fire all transitions, but silently fails for those that are not enabled.
-    /// This function also updates the place boundedness and transition liveness.
     #[rustfmt::skip]
-    fn fire_transitions(
-        transition_io: &[TransitionIO],
-        marking: &Marking,
-        capacities: &C,
-        weights: &W,
-        boundedness: &mut Boundedness,
-        liveness: &mut Liveness,
-    ) -> Vec<(TransitionId, Marking)> {
+    pub(super) fn fire_transitions(&self, transition_io: &[TransitionIO], marking: &Marking) -> Vec<(TransitionId, Marking)> {
+        let (capacities, weights) = (&self.capacities, &self.weights);
         transition_io.iter().filter_map(|transition| {
             // Create a clone of the start marking to modify
             let mut marking = marking.clone();
@@ -323,71 +389,219 @@ impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
                 let capacity = capacities.get_or_default(&target_place).0;
                 capacity.checked_sub(output_weight)
                     .filter(|&max_current_tokens| current_tokens <= max_current_tokens)
-                    .map(|_| {
-                        let new_tokens = Tokens(current_tokens + output_weight);
-                        // If so, add the tokens to the target place
-                        marking.set(target_place, new_tokens);
-                        // Since we are increasing tokens on a place, we need to update the boundedness
-                        boundedness.update(target_place, Bound::Bounded(new_tokens));
-                    })
+                    // If so, add the tokens to the target place
+                    .map(|_| marking.set(target_place, Tokens(current_tokens + output_weight)))
                     .ok_or(()) // Produce Ok if tokens were added, Err if not enough capacity
             // If the transition fired successfully, return its ID and the resulting marking
             }))
                 .ok()
-                .map(|_| {
-                    // This transition fired successfully, so it must be at least L1-live
-                    liveness.update(transition.id, Live::L1);
-                    (transition.id, marking)
-                })
+                .map(|_| (transition.id, marking))
         }).collect() // Collect all successful firing attempts
     }
+    /// Lazily explore the reachability graph of the Petri net.
+    /// The returned iterator yields each marking together with its continuations as soon as it is explored,
+    /// so the state space can be consumed (or abandoned) without holding all rows in memory.
+    pub fn explore(&self) -> Exploration<'_, C, W> {
+        self.explore_with(())
+    }
+    /// Lazily explore the reachability graph of the Petri net while notifying the given observer
+    pub fn explore_with<O: Observer>(&self, observer: O) -> Exploration<'_, C, W, O> {
+        Exploration::new(self, observer)
+    }
     /// Perform a reachability analysis on the Petri net
     pub fn reachability_analysis(&self) -> ReachabilityAnalysis<'_, C, W> {
-        let mut analysis = ReachabilityAnalysis::new(self);
+        self.reachability_analysis_with(())
+    }
+    /// Perform a reachability analysis on the Petri net while notifying the given observer.
+    /// If the observer aborts the exploration, the analysis only covers the markings explored until then.
+    pub fn reachability_analysis_with<O: Observer>(&self, observer: O) -> ReachabilityAnalysis<'_, C, W> {
+        let mut exploration = self.explore_with(observer);
+        let rows = exploration.by_ref().collect();
+        exploration.into_analysis(rows)
+    }
+}
+
+/// A breadth-first exploration of the reachability graph which is performed lazily.
+/// Every call to `next` explores one marking, fires all transitions enabled in it,
+/// and yields the marking together with the continuations leading out of it.
+/// Markings are yielded in the order of their IDs.
+/// The boundedness and liveness gathered so far can be inspected at any point during the exploration.
+/// An observer is notified of everything the exploration finds, and can abort it early.
+#[derive(Debug)]
+pub struct Exploration<'net, C: CapacityFn, W: WeightFn, O: Observer = ()> {
+    pub(super) petri_net: &'net PetriNet<C, W>,
+    transition_io: Vec<TransitionIO>,
+    pub(super) markings: Markings,
+    pub(super) queue: VecDeque<(MarkingId, Marking)>,
+    pub(super) boundedness: Boundedness,
+    pub(super) liveness: Liveness,
+    pub(super) predecessors: Predecessors,
+    observer: O,
+    /// Whether the initial marking has been reported to the observer
+    pub(super) started: bool,
+    /// Whether the observer has aborted the exploration
+    aborted: bool,
+    /// Whether the observer has received the final statistics
+    finished: bool,
+    pub(super) explored: usize,
+    pub(super) edges: usize,
+    pub(super) start_time: Instant,
+    last_report: Instant,
+}
+
+impl<'net, C: CapacityFn, W: WeightFn, O: Observer> Exploration<'net, C, W, O> {
+    /// Start a new exploration at the initial marking of the given Petri net
+    pub(super) fn new(petri_net: &'net PetriNet<C, W>, observer: O) -> Self {
         let mut markings = Markings::default();
-        let id = markings.remember(self.initial_marking.clone());
-        let transition_io = self.transition_io();
-        let mut queue = VecDeque::new();
-        // Start the reachability analysis with the initial marking and its enabled transitions
-        queue.push_back((
-            id,
-            self.initial_marking.clone(),
-            PetriNet::fire_transitions(
-                &transition_io,
-                &self.initial_marking,
-                &self.capacities,
-                &self.weights,
-                &mut analysis.boundedness,
-                &mut analysis.liveness,
-            ),
-        ));
-        while let Some((source_marking_id, source_marking, branches_to_explore)) = queue.pop_front() {
-            let mut continuations = Vec::with_capacity(branches_to_explore.len());
-            for (transition_id, resulting_marking) in branches_to_explore {
-                if let Some(existing_marking_id) = markings.look_up(&resulting_marking) {
-                    // TODO: Fix loop detection (find path from marking to itself)
-                    // TODO: Detect L3/L4 transitions
-                    // If we have seen this marking before, don't explore it again
-                    continuations.push(Continuation(transition_id, existing_marking_id));
-                } else {
-                    // If we have not seen this marking before, remember it and explore it
-                    let new_marking_id = markings.remember(resulting_marking.clone());
-                    continuations.push(Continuation(transition_id, new_marking_id));
-                    // Fire all enabled transitions from the new marking
-                    let new_branches = PetriNet::fire_transitions(
-                        &transition_io,
-                        &resulting_marking,
-                        &self.capacities,
-                        &self.weights,
-                        &mut analysis.boundedness,
-                        &mut analysis.liveness,
-                    );
-                    queue.push_back((new_marking_id, resulting_marking, new_branches));
+        let id = markings.remember(petri_net.initial_marking.clone());
+        let now = Instant::now();
+        Self {
+            petri_net,
+            transition_io: petri_net.transition_io(),
+            markings,
+            queue: VecDeque::from([(id, petri_net.initial_marking.clone())]),
+            boundedness: Boundedness::new(petri_net),
+            liveness: Liveness::new(petri_net),
+            predecessors: Predecessors(vec![None]),
+            observer,
+            started: false,
+            aborted: false,
+            finished: false,
+            explored: 0,
+            edges: 0,
+            start_time: now,
+            last_report: now,
+        }
+    }
+    /// The observer of this exploration
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+    /// Returns true if the observer stopped the exploration before the whole state space was explored
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+    /// A snapshot of the current progress of the exploration
+    pub fn statistics(&self) -> Statistics {
+        let elapsed = self.start_time.elapsed();
+        Statistics {
+            discovered: self.discovered(),
+            explored: self.explored,
+            edges: self.edges,
+            frontier: self.frontier(),
+            elapsed,
+            markings_per_second: self.explored as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            memory: self.markings.memory,
+        }
+    }
+    /// Record the result of an observer callback, aborting the exploration if it asks to
+    fn notify(&mut self, flow: ControlFlow<()>) {
+        if flow.is_break() {
+            self.aborted = true;
+        }
+    }
+    /// Report the current statistics to the observer.
+    /// The final report is only sent once, even if the observer aborts while receiving it.
+    fn report_statistics(&mut self, last: bool) {
+        if self.finished {
+            return;
+        }
+        self.finished = last;
+        self.last_report = Instant::now();
+        let statistics = self.statistics();
+        let flow = self.observer.statistics(&statistics);
+        self.notify(flow);
+    }
+    /// The boundedness of the places, as far as it is known from the markings explored so far
+    pub fn boundedness(&self) -> &Boundedness {
+        &self.boundedness
+    }
+    /// The liveness of the transitions, as far as it is known from the markings explored so far
+    pub fn liveness(&self) -> &Liveness {
+        &self.liveness
+    }
+    /// The number of distinct markings discovered so far, including those which are not yet explored
+    pub fn discovered(&self) -> usize {
+        self.markings.markings.len()
+    }
+    /// The number of markings which have been discovered but not yet explored
+    pub fn frontier(&self) -> usize {
+        self.queue.len()
+    }
+    /// Finish the exploration by packaging the collected rows into a reachability analysis.
+    /// The rows are expected to be the ones yielded by this exploration.
+    pub fn into_analysis(self, rows: Vec<(MarkingId, Marking, Vec<Continuation>)>) -> ReachabilityAnalysis<'net, C, W> {
+        ReachabilityAnalysis {
+            petri_net: self.petri_net,
+            rows,
+            boundedness: self.boundedness,
+            liveness: self.liveness,
+            predecessors: self.predecessors,
+        }
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn, O: Observer> Iterator for Exploration<'_, C, W, O> {
+    type Item = (MarkingId, Marking, Vec<Continuation>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            // The initial marking is discovered before the observer can do anything about it
+            self.started = true;
+            let flow = self.observer.new_marking(MarkingId(0), &self.petri_net.initial_marking);
+            self.notify(flow);
+        }
+        if self.aborted {
+            // The initial marking may already have been rejected, which still deserves a final report
+            self.report_statistics(true);
+            return None;
+        }
+        let (source_marking_id, source_marking) = self.queue.pop_front()?;
+        // Fire all enabled transitions from the marking
+        let branches_to_explore = self.petri_net.fire_transitions(&self.transition_io, &source_marking);
+        for (transition_id, _) in &branches_to_explore {
+            // This transition fired successfully, so it must be at least L1-live
+            self.liveness.update(*transition_id, Live::L1);
+        }
+        let mut continuations = Vec::with_capacity(branches_to_explore.len());
+        for (transition_id, resulting_marking) in branches_to_explore {
+            if let Some(existing_marking_id) = self.markings.look_up(&resulting_marking) {
+                // TODO: Fix loop detection (find path from marking to itself)
+                // TODO: Detect L3/L4 transitions
+                // If we have seen this marking before, don't explore it again
+                continuations.push(Continuation(transition_id, existing_marking_id));
+            } else {
+                // If we have not seen this marking before, remember it and queue it for exploration
+                let new_marking_id = self.markings.remember(resulting_marking.clone());
+                self.predecessors.0.push(Some((source_marking_id, transition_id)));
+                // Tokens were added to the output places, so we need to update their boundedness.
+                // Markings which were seen before have already been accounted for when they were discovered.
+                for output in &self.transition_io[transition_id.0].outputs {
+                    self.boundedness.update(*output, Bound::Bounded(resulting_marking.get(output)), new_marking_id);
                 }
+                continuations.push(Continuation(transition_id, new_marking_id));
+                let flow = self.observer.new_marking(new_marking_id, &resulting_marking);
+                self.notify(flow);
+                self.queue.push_back((new_marking_id, resulting_marking));
             }
-            analysis.rows.push((source_marking_id, source_marking, continuations));
         }
-        analysis
+        for &continuation in &continuations {
+            let flow = self.observer.new_edge(source_marking_id, continuation);
+            self.notify(flow);
+        }
+        if continuations.is_empty() {
+            let flow = self.observer.deadlock(source_marking_id, &source_marking);
+            self.notify(flow);
+        }
+        self.explored += 1;
+        self.edges += continuations.len();
+        // Report statistics periodically, and once more when there is nothing left to explore or the observer aborted
+        if self.aborted || self.queue.is_empty() {
+            self.report_statistics(true);
+        } else if self.last_report.elapsed() >= self.observer.statistics_interval() {
+            self.report_statistics(false);
+        }
+        Some((source_marking_id, source_marking, continuations))
     }
 }
 
@@ -403,15 +617,6 @@ pub enum DeadlockInterpretation {
 }
 
 impl<'net, C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'net, C, W> {
-    /// Create a new reachability analysis for the given Petri net
-    fn new(petri_net: &'net PetriNet<C, W>) -> Self {
-        Self {
-            petri_net,
-            rows: Vec::new(),
-            boundedness: Boundedness::new(petri_net),
-            liveness: Liveness::new(petri_net),
-        }
-    }
     /// Returns a list of deadlocked markings and their interpretation
     #[rustfmt::skip]
     fn deadlocks(&self) -> Vec<(MarkingId, DeadlockInterpretation)> {
@@ -441,12 +646,12 @@ impl<'net, C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'net, C, W> {
     /// Returns the maximum boundedness of any place in the Petri net
     #[rustfmt::skip]
     fn boundedness(&self) -> Bound {
-        self.boundedness.0.iter().copied().max().unwrap_or(Bound::Bounded(Tokens(0)))
+        self.boundedness.bounds.iter().copied().max().unwrap_or(Bound::Bounded(Tokens(0)))
     }
     /// Returns true if every place in the Petri net is 1-bounded
     #[rustfmt::skip]
     fn is_safe(&self) -> bool {
-        self.boundedness.0.iter().all(|&bound| bound == Bound::Bounded(Tokens(1)))
+        self.boundedness.bounds.iter().all(|&bound| bound == Bound::Bounded(Tokens(1)))
     }
     /// Returns true if every transition in the Petri net is L4-live
     fn is_live(&self) -> bool {
@@ -455,7 +660,7 @@ impl<'net, C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'net, C, W> {
     /// Returns true if at least one transition in the Petri net is L4-live
     /// and at least one transition is not L4-live
     fn is_quasi_live(&self) -> bool {
-        !self.is_live() && self.liveness.0.iter().any(|&live| live == Live::L4)
+        !self.is_live() && self.liveness.0.contains(&Live::L4)
     }
     /// Returns the markings from which we can reach a previous marking,
     /// forming a loop in the reachability graph
@@ -467,7 +672,7 @@ impl<'net, C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'net, C, W> {
     #[rustfmt::skip]
     fn is_sound(&self) -> bool {
         self.liveness.0.iter().all(|&live| live != Live::L0)
-            && self.boundedness.0.iter().all(|&bound| bound > Bound::Bounded(Tokens(0)))
+            && self.boundedness.bounds.iter().all(|&bound| bound > Bound::Bounded(Tokens(0)))
     }
 }
 
@@ -516,3 +721,30 @@ impl<'net, C: CapacityFn, W: WeightFn> Display for ReachabilityAnalysis<'net, C,
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_xml, ECNet, Pnml};
+
+    #[test]
+    fn test_exploration() {
+        let pnml: Pnml = parse_xml(include_str!("../../models/workers.pnml").as_bytes()).unwrap();
+        let net: ECNet = pnml.try_into().unwrap();
+        let analysis = net.reachability_analysis();
+        assert!(analysis.rows.len() > 2);
+
+        // Every prefix of the lazy exploration matches the rows of the complete analysis
+        for n in 0..=analysis.rows.len() + 1 {
+            let rows: Vec<_> = net.explore().take(n).collect();
+            assert_eq!(rows.len(), n.min(analysis.rows.len()));
+            assert_eq!(rows[..], analysis.rows[..rows.len()]);
+        }
+
+        // A partial exploration only knows the markings discovered so far
+        let mut exploration = net.explore();
+        let (id, marking, continuations) = exploration.next().unwrap();
+        assert_eq!((id.0, &marking), (0, &net.initial_marking));
+        assert_eq!(exploration.discovered(), 1 + continuations.len());
+        assert_eq!(exploration.frontier(), continuations.len());
+    }
+}
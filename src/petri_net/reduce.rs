@@ -0,0 +1,406 @@
+//! This module reduces Petri nets with the transformation rules from Murata's paper (section VI.A),
+//! which go back to Berthelot. Each rule preserves liveness, safeness and boundedness:
+//!
+//! - Fusion of series places: a transition which only moves a token from one place to another is removed,
+//!   and the two places become one.
+//! - Fusion of series transitions: a place which only passes tokens from one transition to another is removed,
+//!   and the two transitions become one.
+//! - Fusion of parallel places: of two places with the same input and output transitions (and the same marking), one is removed.
+//! - Fusion of parallel transitions: of two transitions with the same input and output places, one is removed.
+//! - Elimination of self-loop places: a marked place which is only read and written back by one transition is removed.
+//! - Elimination of self-loop transitions: a transition which only takes a token from a place and puts it back is removed.
+//!
+//! Parallel and self-loop places are the simplest kinds of implicit places, i.e. places which never restrict firing.
+//! Recognizing implicit places in general requires linear programming and is not attempted here.
+//!
+//! The rules are applied until none of them applies anymore.
+//! Series fusions only involve arcs of weight 1. Places with a finite capacity, which includes every place
+//! of an EC net, are never fused or removed, since fusing series places can put more tokens on a place
+//! than its capacity allows. Only the rules for transitions apply to them.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::{
+    Annotations, Arc, Capacity, CapacityFn, Marking, MarkingFn, PetriNet, Place, PlaceId, Tokens, Transition,
+    TransitionId, Weight, WeightFn,
+};
+
+/// A reduction rule which was applied to a net, referring to places and transitions by their original IDs.
+/// A transition which already absorbed others through series fusion is referred to by its first original transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reduction {
+    /// The transition moved tokens from the first place to the second, which now holds the tokens of both
+    SeriesPlaces { removed: PlaceId, into: PlaceId, transition: TransitionId },
+    /// The place passed tokens from the first transition to the second, which is now part of the first
+    SeriesTransitions { removed: TransitionId, into: TransitionId, place: PlaceId },
+    /// The place had the same inputs, outputs and marking as the kept one
+    ParallelPlaces { removed: PlaceId, kept: PlaceId },
+    /// The transition had the same inputs and outputs as the kept one
+    ParallelTransitions { removed: TransitionId, kept: TransitionId },
+    /// The marked place was only read and written back by the transition
+    SelfLoopPlace { removed: PlaceId, transition: TransitionId },
+    /// The transition only took a token from the place and put it back
+    SelfLoopTransition { removed: TransitionId, place: PlaceId },
+}
+
+impl Display for Reduction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Reduction::SeriesPlaces { removed, into, transition } => {
+                write!(f, "fused series place {} into {} by removing {}", removed, into, transition)
+            }
+            Reduction::SeriesTransitions { removed, into, place } => {
+                write!(f, "fused series transition {} into {} by removing {}", removed, into, place)
+            }
+            Reduction::ParallelPlaces { removed, kept } => write!(f, "removed {} parallel to {}", removed, kept),
+            Reduction::ParallelTransitions { removed, kept } => write!(f, "removed {} parallel to {}", removed, kept),
+            Reduction::SelfLoopPlace { removed, transition } => {
+                write!(f, "removed self-loop place {} of {}", removed, transition)
+            }
+            Reduction::SelfLoopTransition { removed, place } => {
+                write!(f, "removed self-loop transition {} of {}", removed, place)
+            }
+        }
+    }
+}
+
+/// A reduced net together with the rules which produced it.
+/// The places and transitions of the reduced net have new, dense IDs,
+/// which can be mapped back to the IDs of the original net.
+#[derive(Debug, Clone)]
+pub struct ReducedNet<C: CapacityFn, W: WeightFn> {
+    pub net: PetriNet<C, W>,
+    pub reductions: Vec<Reduction>,
+    /// The original ID of every place (ID = index)
+    places: Vec<PlaceId>,
+    /// The original transitions which every transition (ID = index) fires in sequence
+    transitions: Vec<Vec<TransitionId>>,
+}
+
+impl<C: CapacityFn, W: WeightFn> ReducedNet<C, W> {
+    /// The ID of a place of the reduced net in the original net
+    pub fn original_place(&self, id: PlaceId) -> PlaceId {
+        self.places[id.0]
+    }
+    /// The transitions of the original net which a transition of the reduced net fires in sequence
+    pub fn original_transitions(&self, id: TransitionId) -> &[TransitionId] {
+        &self.transitions[id.0]
+    }
+}
+
+#[derive(Debug, Clone)]
+struct WorkingPlace {
+    name: String,
+    tokens: usize,
+    /// Places with a finite capacity are left alone
+    fixed: bool,
+}
+
+#[derive(Debug, Clone)]
+struct WorkingTransition {
+    name: String,
+    /// Input and output places by original index, with the weights of their arcs
+    inputs: BTreeMap<usize, usize>,
+    outputs: BTreeMap<usize, usize>,
+    originals: Vec<TransitionId>,
+}
+
+/// A copy of the net in which places and transitions can be removed without changing the IDs of the others
+struct Working {
+    places: Vec<Option<WorkingPlace>>,
+    transitions: Vec<Option<WorkingTransition>>,
+}
+
+impl Working {
+    fn transitions(&self) -> impl Iterator<Item = (usize, &WorkingTransition)> + '_ {
+        self.transitions.iter().enumerate().filter_map(|(index, transition)| Some((index, transition.as_ref()?)))
+    }
+    fn places(&self) -> impl Iterator<Item = (usize, &WorkingPlace)> + '_ {
+        self.places.iter().enumerate().filter_map(|(index, place)| Some((index, place.as_ref()?)))
+    }
+    fn transition(&mut self, index: usize) -> &mut WorkingTransition {
+        self.transitions[index].as_mut().expect("Transition should exist")
+    }
+    /// The transitions which put tokens on the place, with the weights of their arcs
+    fn producers(&self, place: usize) -> BTreeMap<usize, usize> {
+        self.transitions().filter_map(|(index, transition)| Some((index, *transition.outputs.get(&place)?))).collect()
+    }
+    /// The transitions which take tokens from the place, with the weights of their arcs
+    fn consumers(&self, place: usize) -> BTreeMap<usize, usize> {
+        self.transitions().filter_map(|(index, transition)| Some((index, *transition.inputs.get(&place)?))).collect()
+    }
+    fn is_free(&self, place: usize) -> bool {
+        self.places[place].as_ref().is_some_and(|place| !place.fixed)
+    }
+    fn series_places(&mut self) -> Option<Reduction> {
+        let (transition, from, to) = self.transitions().find_map(|(index, transition)| {
+            let (&[(&from, &1)], &[(&to, &1)]) = (
+                transition.inputs.iter().collect::<Vec<_>>().as_slice(),
+                transition.outputs.iter().collect::<Vec<_>>().as_slice(),
+            ) else {
+                return None;
+            };
+            let producers = self.producers(from);
+            let applies = from != to
+                && self.is_free(from)
+                && self.is_free(to)
+                && self.consumers(from).keys().eq([&index])
+                // Redirecting the producers must not create parallel arcs
+                && producers.keys().all(|&producer| {
+                    self.transitions[producer].as_ref().is_some_and(|producer| !producer.outputs.contains_key(&to))
+                });
+            applies.then_some((index, from, to))
+        })?;
+        for producer in self.producers(from).into_keys() {
+            let producer = self.transition(producer);
+            let weight = producer.outputs.remove(&from).expect("Producer should output to the place");
+            producer.outputs.insert(to, weight);
+        }
+        let removed = self.places[from].take().expect("Place should exist");
+        self.places[to].as_mut().expect("Place should exist").tokens += removed.tokens;
+        let removed_transition = self.transitions[transition].take().expect("Transition should exist");
+        Some(Reduction::SeriesPlaces {
+            removed: PlaceId(from),
+            into: PlaceId(to),
+            transition: removed_transition.originals[0],
+        })
+    }
+    fn series_transitions(&mut self) -> Option<Reduction> {
+        let (place, from, to) = self.places().find_map(|(index, place)| {
+            let producers = self.producers(index);
+            let consumers = self.consumers(index);
+            let (&[(&from, &1)], &[(&to, &1)]) = (
+                producers.iter().collect::<Vec<_>>().as_slice(),
+                consumers.iter().collect::<Vec<_>>().as_slice(),
+            ) else {
+                return None;
+            };
+            let source = self.transitions[from].as_ref()?;
+            let target = self.transitions[to].as_ref()?;
+            let applies = from != to
+                && place.tokens == 0
+                && !place.fixed
+                && target.inputs.len() == 1
+                // Merging the outputs must not create parallel arcs
+                && target.outputs.keys().all(|output| !source.outputs.contains_key(output));
+            applies.then_some((index, from, to))
+        })?;
+        self.places[place] = None;
+        let target = self.transitions[to].take().expect("Transition should exist");
+        let source = self.transition(from);
+        source.outputs.remove(&place);
+        source.outputs.extend(target.outputs);
+        source.originals.extend(&target.originals);
+        if source.name.is_empty() {
+            source.name = target.name;
+        }
+        Some(Reduction::SeriesTransitions {
+            removed: target.originals[0],
+            into: source.originals[0],
+            place: PlaceId(place),
+        })
+    }
+    fn parallel_places(&mut self) -> Option<Reduction> {
+        let places: Vec<_> = self.places()
+            .filter(|(_, place)| !place.fixed)
+            .map(|(index, place)| (index, place.tokens, self.producers(index), self.consumers(index)))
+            .collect();
+        let (kept, removed) = places.iter().enumerate().find_map(|(i, a)| {
+            let b = places[i + 1..].iter().find(|b| (a.1, &a.2, &a.3) == (b.1, &b.2, &b.3))?;
+            Some((a.0, b.0))
+        })?;
+        self.places[removed] = None;
+        for transition in self.transitions.iter_mut().flatten() {
+            transition.inputs.remove(&removed);
+            transition.outputs.remove(&removed);
+        }
+        Some(Reduction::ParallelPlaces { removed: PlaceId(removed), kept: PlaceId(kept) })
+    }
+    fn parallel_transitions(&mut self) -> Option<Reduction> {
+        let transitions: Vec<_> = self.transitions().collect();
+        let (kept, removed) = transitions.iter().enumerate().find_map(|(i, (a, first))| {
+            let (b, _) = transitions[i + 1..]
+                .iter()
+                .find(|(_, second)| (&first.inputs, &first.outputs) == (&second.inputs, &second.outputs))?;
+            Some((*a, *b))
+        })?;
+        let removed = self.transitions[removed].take().expect("Transition should exist");
+        let kept = self.transition(kept);
+        Some(Reduction::ParallelTransitions { removed: removed.originals[0], kept: kept.originals[0] })
+    }
+    fn self_loop_places(&mut self) -> Option<Reduction> {
+        let (place, transition) = self.places().find_map(|(index, place)| {
+            let producers = self.producers(index);
+            let consumers = self.consumers(index);
+            let &[(&transition, &weight)] = producers.iter().collect::<Vec<_>>().as_slice() else {
+                return None;
+            };
+            let applies = !place.fixed && place.tokens >= weight && producers == consumers;
+            applies.then_some((index, transition))
+        })?;
+        self.places[place] = None;
+        let transition = self.transition(transition);
+        transition.inputs.remove(&place);
+        transition.outputs.remove(&place);
+        Some(Reduction::SelfLoopPlace { removed: PlaceId(place), transition: transition.originals[0] })
+    }
+    fn self_loop_transitions(&mut self) -> Option<Reduction> {
+        let (transition, place) = self.transitions().find_map(|(index, transition)| {
+            let &[(&place, _)] = transition.inputs.iter().collect::<Vec<_>>().as_slice() else {
+                return None;
+            };
+            (transition.inputs == transition.outputs).then_some((index, place))
+        })?;
+        let removed = self.transitions[transition].take().expect("Transition should exist");
+        Some(Reduction::SelfLoopTransition { removed: removed.originals[0], place: PlaceId(place) })
+    }
+}
+
+impl<C, W> PetriNet<C, W>
+where
+    C: CapacityFn + FromIterator<(PlaceId, Capacity)>,
+    W: WeightFn + FromIterator<(Arc, Weight)>,
+{
+    /// Reduce the net with the rules of Murata until none of them applies anymore
+    pub fn reduce(&self) -> ReducedNet<C, W> {
+        let mut working = Working {
+            places: self.places
+                .iter()
+                .map(|place| {
+                    Some(WorkingPlace {
+                        name: place.name.clone(),
+                        tokens: self.initial_marking.get(&place.id).0,
+                        fixed: self.capacities.get_or_default(&place.id).0 != usize::MAX,
+                    })
+                })
+                .collect(),
+            transitions: self.transitions
+                .iter()
+                .map(|transition| {
+                    Some(WorkingTransition {
+                        name: transition.name.clone(),
+                        inputs: BTreeMap::new(),
+                        outputs: BTreeMap::new(),
+                        originals: vec![transition.id],
+                    })
+                })
+                .collect(),
+        };
+        for &arc in &self.arcs {
+            let weight = self.weights.get_or_default(&arc).0;
+            match arc {
+                Arc::PlaceTransition(place, transition) => working.transition(transition.0).inputs.insert(place.0, weight),
+                Arc::TransitionPlace(transition, place) => working.transition(transition.0).outputs.insert(place.0, weight),
+            };
+        }
+
+        let rules = [
+            Working::series_places,
+            Working::series_transitions,
+            Working::parallel_places,
+            Working::parallel_transitions,
+            // Removing self-loop transitions first keeps the marked place of a trivial loop
+            Working::self_loop_transitions,
+            Working::self_loop_places,
+        ];
+        let mut reductions = Vec::new();
+        while let Some(reduction) = rules.iter().find_map(|rule| rule(&mut working)) {
+            reductions.push(reduction);
+        }
+
+        // Give the remaining places and transitions dense IDs again
+        let mut place_ids = vec![None; working.places.len()];
+        let mut places = Vec::new();
+        let mut original_places = Vec::new();
+        let mut initial_marking = Marking::default();
+        for (index, place) in working.places() {
+            let id = PlaceId(places.len());
+            place_ids[index] = Some(id);
+            places.push(Place { id, name: place.name.clone() });
+            original_places.push(PlaceId(index));
+            initial_marking.set(id, Tokens(place.tokens));
+        }
+        let place_id = |index: &usize| place_ids[*index].expect("Place should exist");
+        let mut transitions = Vec::new();
+        let mut original_transitions = Vec::new();
+        let mut arcs = Vec::new();
+        let mut weights = Vec::new();
+        for (_, transition) in working.transitions() {
+            let id = TransitionId(transitions.len());
+            transitions.push(Transition { id, name: transition.name.clone() });
+            original_transitions.push(transition.originals.clone());
+            for (place, &weight) in &transition.inputs {
+                arcs.push(Arc::PlaceTransition(place_id(place), id));
+                weights.push((Arc::PlaceTransition(place_id(place), id), Weight(weight)));
+            }
+            for (place, &weight) in &transition.outputs {
+                arcs.push(Arc::TransitionPlace(id, place_id(place)));
+                weights.push((Arc::TransitionPlace(id, place_id(place)), Weight(weight)));
+            }
+        }
+        let capacities = original_places
+            .iter()
+            .enumerate()
+            .filter_map(|(index, original)| Some((PlaceId(index), self.capacities.get(original)?)))
+            .collect();
+
+        ReducedNet {
+            net: PetriNet {
+                id: self.id.clone(),
+                places,
+                transitions,
+                arcs,
+                capacities,
+                weights: weights.into_iter().collect(),
+                initial_marking,
+                // The annotations of places, transitions and arcs refer to elements which may have been merged away
+                annotations: Annotations {
+                    net: self.annotations.net.clone(),
+                    page: self.annotations.page.clone(),
+                    ..Annotations::default()
+                },
+            },
+            reductions,
+            places: original_places,
+            transitions: original_transitions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, ECNet, PTNet, Pnml};
+
+    #[test]
+    fn test_reduce() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let reduced = net.reduce();
+        let reductions: Vec<String> = reduced.reductions.iter().map(ToString::to_string).collect();
+        assert_eq!(reductions, [
+            "fused series place P1 into P3 by removing T1",
+            "fused series place P2 into P4 by removing T2",
+            "fused series place P5 into P0 by removing T4",
+            "removed P4 parallel to P3",
+            "fused series place P0 into P3 by removing T0",
+            "removed self-loop transition T3 of P3",
+        ]);
+        assert_eq!(reduced.net.places.len(), 1);
+        assert!(reduced.net.transitions.is_empty());
+        assert_eq!(reduced.original_place(PlaceId(0)), PlaceId(3));
+        assert_eq!(reduced.net.initial_marking.get(&PlaceId(0)), Tokens(1));
+
+        // The reduced net is as bounded as the original
+        assert!(reduced.net.is_k_bounded(1));
+
+        // The places of an EC net have a capacity of 1, which fusing them could exceed
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let reduced = net.reduce();
+        assert!(reduced.reductions.is_empty());
+        assert_eq!(reduced.net.places.len(), net.places.len());
+        Ok(())
+    }
+}
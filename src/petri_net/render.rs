@@ -0,0 +1,218 @@
+//! This module renders a Petri net for viewing.
+//!
+//! - Graphviz DOT, which leaves the layout to Graphviz. Places are drawn as circles with their tokens,
+//!   transitions as boxes, and arcs with a weight other than 1 are labeled with it.
+//!   Silent transitions (those without a name, e.g. created when converting BPMN) are drawn as black bars.
+//! - SVG, which needs no other tools. The nodes are placed by the layered layout of the net,
+//!   and arcs which span several layers bend around the nodes in between.
+
+use std::io::{Result as IoResult, Write};
+
+use quick_xml::escape::escape;
+
+use super::export::escape_dot;
+use super::{Arc, CapacityFn, Layout, MarkingFn, PetriNet, PlaceId, Position, TransitionId, WeightFn};
+
+const PLACE_RADIUS: f64 = 18.0;
+const TRANSITION_WIDTH: f64 = 40.0;
+const TRANSITION_HEIGHT: f64 = 40.0;
+const SILENT_WIDTH: f64 = 8.0;
+
+/// A node of the net, which is either a place or a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    Place(PlaceId),
+    Transition(TransitionId),
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// The source and target of an arc, and its weight
+    fn arc_ends(&self, arc: &Arc) -> (Node, Node, usize) {
+        let weight = self.weights.get_or_default(arc).0;
+        match *arc {
+            Arc::PlaceTransition(place, transition) => (Node::Place(place), Node::Transition(transition), weight),
+            Arc::TransitionPlace(transition, place) => (Node::Transition(transition), Node::Place(place), weight),
+        }
+    }
+    /// Write the net in the Graphviz DOT format
+    pub fn write_dot<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        writeln!(out, "digraph \"{}\" {{", escape_dot(&self.id))?;
+        writeln!(out, "    rankdir=LR;")?;
+        for place in &self.places {
+            let tokens = self.initial_marking.get(&place.id).0;
+            let label = if tokens > 0 { tokens.to_string() } else { String::new() };
+            writeln!(
+                out,
+                "    {} [shape=circle, label=\"{}\", xlabel=\"{}\"];",
+                place.id,
+                label,
+                escape_dot(&place.name)
+            )?;
+        }
+        for transition in &self.transitions {
+            if transition.name.is_empty() {
+                writeln!(
+                    out,
+                    "    {} [shape=box, label=\"\", style=filled, fillcolor=black, width=0.1, height=0.4];",
+                    transition.id
+                )?;
+            } else {
+                writeln!(out, "    {} [shape=box, label=\"{}\"];", transition.id, escape_dot(&transition.name))?;
+            }
+        }
+        for arc in &self.arcs {
+            let (source, target) = match *arc {
+                Arc::PlaceTransition(place, transition) => (place.to_string(), transition.to_string()),
+                Arc::TransitionPlace(transition, place) => (transition.to_string(), place.to_string()),
+            };
+            match self.weights.get_or_default(arc).0 {
+                1 => writeln!(out, "    {} -> {};", source, target)?,
+                weight => writeln!(out, "    {} -> {} [label=\"{}\"];", source, target, weight)?,
+            }
+        }
+        writeln!(out, "}}")
+    }
+    /// Write the net as a self-contained SVG image with a layered layout
+    pub fn write_svg<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let layout = self.layout();
+        let position = |layout: &Layout, node: Node| match node {
+            Node::Place(place) => layout.place(place),
+            Node::Transition(transition) => layout.transition(transition),
+        };
+        let (width, height) = (layout.width(), layout.height());
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="12">"#,
+            width, height, width, height
+        )?;
+        writeln!(out, "  <title>{}</title>", escape(&self.id))?;
+        writeln!(out, "  <defs>")?;
+        writeln!(
+            out,
+            r#"    <marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto-start-reverse">"#
+        )?;
+        writeln!(out, r#"      <path d="M 0 0 L 10 5 L 0 10 z"/>"#)?;
+        writeln!(out, "    </marker>")?;
+        writeln!(out, "  </defs>")?;
+        for arc in &self.arcs {
+            let (source, target, weight) = self.arc_ends(arc);
+            let mut points = vec![position(&layout, source)];
+            points.extend_from_slice(layout.bends(arc));
+            points.push(position(&layout, target));
+            // Clip the first and last segment at the borders of the source and target
+            let last = points.len() - 1;
+            points[0] = self.clip(source, points[0], points[1]);
+            points[last] = self.clip(target, points[last], points[last - 1]);
+            let coordinates: Vec<String> = points.iter().map(|point| format!("{:.1},{:.1}", point.x, point.y)).collect();
+            writeln!(
+                out,
+                r#"  <polyline points="{}" fill="none" stroke="black" marker-end="url(#arrow)"/>"#,
+                coordinates.join(" ")
+            )?;
+            if weight != 1 {
+                let (from, to) = (points[0], points[1]);
+                writeln!(
+                    out,
+                    r#"  <text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+                    (from.x + to.x) / 2.0,
+                    (from.y + to.y) / 2.0 - 4.0,
+                    weight
+                )?;
+            }
+        }
+        for place in &self.places {
+            let Position { x, y } = layout.place(place.id);
+            writeln!(
+                out,
+                r#"  <circle id="{}" cx="{:.1}" cy="{:.1}" r="{}" fill="white" stroke="black"/>"#,
+                place.id, x, y, PLACE_RADIUS
+            )?;
+            let tokens = self.initial_marking.get(&place.id).0;
+            if tokens > 0 {
+                writeln!(out, r#"  <text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#, x, y + 4.0, tokens)?;
+            }
+            self.write_name(&mut out, &place.name, x, y + PLACE_RADIUS + 14.0)?;
+        }
+        for transition in &self.transitions {
+            let Position { x, y } = layout.transition(transition.id);
+            let (width, fill) = if transition.name.is_empty() { (SILENT_WIDTH, "black") } else { (TRANSITION_WIDTH, "white") };
+            writeln!(
+                out,
+                r#"  <rect id="{}" x="{:.1}" y="{:.1}" width="{}" height="{}" fill="{}" stroke="black"/>"#,
+                transition.id,
+                x - width / 2.0,
+                y - TRANSITION_HEIGHT / 2.0,
+                width,
+                TRANSITION_HEIGHT,
+                fill
+            )?;
+            self.write_name(&mut out, &transition.name, x, y + TRANSITION_HEIGHT / 2.0 + 14.0)?;
+        }
+        writeln!(out, "</svg>")
+    }
+    /// Move the end of an arc at the center of a node towards the next point, onto the border of the node
+    fn clip(&self, node: Node, center: Position, towards: Position) -> Position {
+        let (dx, dy) = (towards.x - center.x, towards.y - center.y);
+        let length = dx.hypot(dy).max(f64::EPSILON);
+        let distance = self.boundary(node, dx / length, dy / length);
+        Position { x: center.x + distance * dx / length, y: center.y + distance * dy / length }
+    }
+    /// The distance from the center of a node to its border in the given direction
+    fn boundary(&self, node: Node, dx: f64, dy: f64) -> f64 {
+        match node {
+            Node::Place(_) => PLACE_RADIUS,
+            Node::Transition(transition) => {
+                let width = if self.transitions[transition.0].name.is_empty() { SILENT_WIDTH } else { TRANSITION_WIDTH };
+                let horizontal = if dx.abs() > f64::EPSILON { width / 2.0 / dx.abs() } else { f64::INFINITY };
+                let vertical = if dy.abs() > f64::EPSILON { TRANSITION_HEIGHT / 2.0 / dy.abs() } else { f64::INFINITY };
+                horizontal.min(vertical)
+            }
+        }
+    }
+    /// Write the name of a node below it, unless it has none
+    fn write_name<Out: Write>(&self, out: &mut Out, name: &str, x: f64, y: f64) -> IoResult<()> {
+        if name.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, r#"  <text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#, x, y, escape(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_xml, Bpmn, ECNet, PTNet, Pnml};
+
+    #[test]
+    fn test_render() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+
+        let mut dot = Vec::new();
+        net.write_dot(&mut dot)?;
+        let dot = String::from_utf8(dot)?;
+        assert!(dot.contains("P0 [shape=circle, label=\"1\", xlabel=\"ready\"];"));
+        assert!(dot.contains("T2 [shape=box, label=\"use\"];"));
+        assert!(dot.contains("P1 -> T2;"));
+
+        let mut svg = Vec::new();
+        net.write_svg(&mut svg)?;
+        let svg = String::from_utf8(svg)?;
+        assert!(svg.starts_with("<svg "));
+        assert_eq!(svg.matches("<circle ").count(), 5);
+        assert_eq!(svg.matches("<rect ").count(), 3);
+        assert_eq!(svg.matches("<polyline ").count(), 7);
+        // The marked places come first, and the transitions consuming from them follow in the next layer
+        assert!(svg.contains(r#"<circle id="P0" cx="60.0""#));
+        assert!(svg.contains(r#"<rect id="T0" x="140.0""#));
+
+        // Nets converted from BPMN contain silent transitions
+        let bpmn: Bpmn = parse_xml(include_str!("../../models/choice.bpmn").as_bytes())?;
+        let net: ECNet = bpmn.into();
+        let mut dot = Vec::new();
+        net.write_dot(&mut dot)?;
+        let silent = net.transitions.iter().filter(|transition| transition.name.is_empty()).count();
+        assert_eq!(silent, 2);
+        assert_eq!(String::from_utf8(dot)?.matches("fillcolor=black").count(), silent);
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+//! This module implements step semantics, in which several transitions may fire at once,
+//! as an alternative to the interleaving semantics of the reachability analysis.
+//!
+//! A step is a multiset of transitions. It is enabled in a marking if the marking holds enough tokens
+//! for all of its transitions together, and if the tokens produced by the step fit into the capacities
+//! of the places once the consumed tokens have been removed.
+//! Transitions without input places could occur arbitrarily often in a single step,
+//! so they are limited to one occurrence per step.
+//!
+//! Two transitions are concurrent if they are enabled together as a step in some reachable marking.
+//! A transition which is concurrent with itself can fire twice at once (auto-concurrency).
+
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::reachability::{Markings, TransitionIO};
+use super::{Arc, CapacityFn, Continuation, Marking, MarkingFn, MarkingId, PetriNet, ReachabilityAnalysis, Tokens, TransitionId, WeightFn};
+
+/// Which steps are allowed to fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepSemantics {
+    /// Any non-empty step which is enabled
+    Arbitrary,
+    /// Only the enabled steps which cannot be extended by another transition
+    Maximal,
+}
+
+/// A multiset of transitions which fire at once, sorted by ID
+/// Displayed as a set, e.g. {T0, T1, T1}
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Step(Vec<TransitionId>);
+
+impl Step {
+    /// The transitions of the step, each repeated as often as it fires
+    pub fn transitions(&self) -> &[TransitionId] {
+        &self.0
+    }
+    /// Build the step from the number of times each transition (ID = index) fires
+    fn from_counts(counts: &[usize]) -> Self {
+        let transitions = counts
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &count)| std::iter::repeat_n(TransitionId(index), count))
+            .collect();
+        Step(transitions)
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{{")?;
+        let mut separator = "";
+        for transition in &self.0 {
+            write!(f, "{}{}", separator, transition)?;
+            separator = ", ";
+        }
+        write!(f, "}}")
+    }
+}
+
+/// A step which can fire from a marking and the ID of the resulting marking
+/// Displayed as "{step}->{M}", e.g. {T1, T2}->M003
+#[derive(Debug, Clone, derive_more::Display)]
+#[display(fmt = "{}->{}", _0, _1)]
+pub struct StepContinuation(pub Step, pub MarkingId);
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Remove the input tokens of one occurrence of a transition, if there are enough of them
+    fn consume(&self, transition: &TransitionIO, marking: &Marking) -> Option<Marking> {
+        let mut marking = marking.clone();
+        for &input in &transition.inputs {
+            let weight = self.weights.get_or_default(&Arc::PlaceTransition(input, transition.id)).0;
+            let tokens = marking.get(&input).0.checked_sub(weight)?;
+            marking.set(input, Tokens(tokens));
+        }
+        Some(marking)
+    }
+    /// Add the output tokens of a step to a marking from which its input tokens were already removed,
+    /// if they fit into the capacities of the output places
+    fn produce(&self, transition_io: &[TransitionIO], counts: &[usize], marking: &Marking) -> Option<Marking> {
+        let mut marking = marking.clone();
+        for (transition, &count) in transition_io.iter().zip(counts) {
+            for &output in &transition.outputs {
+                let weight = self.weights.get_or_default(&Arc::TransitionPlace(transition.id, output)).0;
+                let tokens = marking.get(&output).0 + weight * count;
+                marking.set(output, Tokens(tokens));
+            }
+        }
+        let fits = transition_io
+            .iter()
+            .zip(counts)
+            .filter(|(_, &count)| count > 0)
+            .flat_map(|(transition, _)| &transition.outputs)
+            .all(|output| marking.get(output) <= Tokens(self.capacities.get_or_default(output).0));
+        fits.then_some(marking)
+    }
+    /// Fire a step given by the number of times each transition (ID = index) fires, if it is enabled
+    fn fire_step(&self, transition_io: &[TransitionIO], counts: &[usize], marking: &Marking) -> Option<Marking> {
+        let mut remaining = marking.clone();
+        for (transition, &count) in transition_io.iter().zip(counts) {
+            for _ in 0..count {
+                remaining = self.consume(transition, &remaining)?;
+            }
+        }
+        self.produce(transition_io, counts, &remaining)
+    }
+    /// Returns true if the step consisting of the two transitions is enabled in the marking.
+    /// Transitions without inputs may occur only once per step, so they are never concurrent to themselves.
+    pub(super) fn concurrently_enabled(&self, transition_io: &[TransitionIO], a: TransitionId, b: TransitionId, marking: &Marking) -> bool {
+        if a == b && transition_io[a.0].inputs.is_empty() {
+            return false;
+        }
+        let mut counts = vec![0; transition_io.len()];
+        counts[a.0] += 1;
+        counts[b.0] += 1;
+        self.fire_step(transition_io, &counts, marking).is_some()
+    }
+    /// Enumerate the enabled steps from the transition at `index` onwards,
+    /// given the tokens which are left after the transitions before it consumed theirs
+    fn extend_steps(
+        &self,
+        transition_io: &[TransitionIO],
+        remaining: &Marking,
+        index: usize,
+        counts: &mut Vec<usize>,
+        steps: &mut Vec<(Vec<usize>, Marking)>,
+    ) {
+        let Some(transition) = transition_io.get(index) else {
+            if counts.iter().any(|&count| count > 0) {
+                if let Some(marking) = self.produce(transition_io, counts, remaining) {
+                    steps.push((counts.clone(), marking));
+                }
+            }
+            return;
+        };
+        self.extend_steps(transition_io, remaining, index + 1, counts, steps);
+        let mut remaining = remaining.clone();
+        while !transition.inputs.is_empty() || counts[index] == 0 {
+            let Some(next) = self.consume(transition, &remaining) else { break };
+            remaining = next;
+            counts[index] += 1;
+            self.extend_steps(transition_io, &remaining, index + 1, counts, steps);
+        }
+        counts[index] = 0;
+    }
+    /// Fire all steps which are enabled in the provided marking under the given semantics,
+    /// and return a list of the resulting markings
+    pub(super) fn fire_steps(
+        &self,
+        transition_io: &[TransitionIO],
+        semantics: StepSemantics,
+        marking: &Marking,
+    ) -> Vec<(Step, Marking)> {
+        let mut steps = Vec::new();
+        self.extend_steps(transition_io, marking, 0, &mut vec![0; transition_io.len()], &mut steps);
+        if semantics == StepSemantics::Maximal {
+            // A step is maximal if adding any single transition to it yields a step which is not enabled
+            let enabled: HashSet<Vec<usize>> = steps.iter().map(|(counts, _)| counts.clone()).collect();
+            steps.retain(|(counts, _)| {
+                (0..counts.len()).all(|index| {
+                    let mut extended = counts.clone();
+                    extended[index] += 1;
+                    !enabled.contains(&extended)
+                })
+            });
+        }
+        steps.into_iter().map(|(counts, marking)| (Step::from_counts(&counts), marking)).collect()
+    }
+    /// Lazily explore the markings which are reachable under step semantics
+    pub fn explore_steps(&self, semantics: StepSemantics) -> StepExploration<'_, C, W> {
+        let mut markings = Markings::default();
+        let id = markings.remember(self.initial_marking.clone());
+        StepExploration {
+            petri_net: self,
+            transition_io: self.transition_io(),
+            semantics,
+            markings,
+            queue: VecDeque::from([(id, self.initial_marking.clone())]),
+        }
+    }
+    /// Compute which transitions can be enabled concurrently in some reachable marking
+    pub fn concurrency_relation(&self) -> ConcurrencyRelation {
+        self.reachability_analysis().concurrency_relation()
+    }
+}
+
+/// A breadth-first exploration of the reachability graph under step semantics which is performed lazily.
+/// Every call to `next` explores one marking, fires all steps enabled in it,
+/// and yields the marking together with the step continuations leading out of it.
+/// Markings are yielded in the order of their IDs.
+#[derive(Debug)]
+pub struct StepExploration<'net, C: CapacityFn, W: WeightFn> {
+    petri_net: &'net PetriNet<C, W>,
+    transition_io: Vec<TransitionIO>,
+    semantics: StepSemantics,
+    markings: Markings,
+    queue: VecDeque<(MarkingId, Marking)>,
+}
+
+impl<C: CapacityFn, W: WeightFn> Iterator for StepExploration<'_, C, W> {
+    type Item = (MarkingId, Marking, Vec<StepContinuation>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (marking_id, marking) = self.queue.pop_front()?;
+        let steps = self.petri_net.fire_steps(&self.transition_io, self.semantics, &marking);
+        let mut continuations = Vec::with_capacity(steps.len());
+        for (step, resulting_marking) in steps {
+            let resulting_marking_id = match self.markings.look_up(&resulting_marking) {
+                Some(existing_marking_id) => existing_marking_id,
+                None => {
+                    let new_marking_id = self.markings.remember(resulting_marking.clone());
+                    self.queue.push_back((new_marking_id, resulting_marking));
+                    new_marking_id
+                }
+            };
+            continuations.push(StepContinuation(step, resulting_marking_id));
+        }
+        Some((marking_id, marking, continuations))
+    }
+}
+
+/// The pairs of transitions which can be enabled concurrently
+/// Displayed as a list of pairs, e.g. T1 || T2, T3 || T3
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConcurrencyRelation(BTreeSet<(TransitionId, TransitionId)>);
+
+impl ConcurrencyRelation {
+    /// Returns true if the two transitions can be enabled concurrently
+    pub fn are_concurrent(&self, a: TransitionId, b: TransitionId) -> bool {
+        self.0.contains(&(a.min(b), a.max(b)))
+    }
+    /// The concurrent pairs, each with the smaller ID first
+    pub fn pairs(&self) -> impl Iterator<Item = (TransitionId, TransitionId)> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl Display for ConcurrencyRelation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut separator = "";
+        for (a, b) in &self.0 {
+            write!(f, "{}{} || {}", separator, a, b)?;
+            separator = ", ";
+        }
+        Ok(())
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> ReachabilityAnalysis<'_, C, W> {
+    /// Compute which transitions can be enabled concurrently in the markings of this analysis
+    pub fn concurrency_relation(&self) -> ConcurrencyRelation {
+        let net = self.petri_net;
+        let transition_io = net.transition_io();
+        let mut relation = BTreeSet::new();
+        for (_, marking, continuations) in &self.rows {
+            // Only transitions which are enabled on their own can be part of a step
+            for (i, &Continuation(a, _)) in continuations.iter().enumerate() {
+                for &Continuation(b, _) in &continuations[i..] {
+                    let pair = (a.min(b), a.max(b));
+                    if !relation.contains(&pair) && net.concurrently_enabled(&transition_io, a, b, marking) {
+                        relation.insert(pair);
+                    }
+                }
+            }
+        }
+        ConcurrencyRelation(relation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_step_semantics() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+
+        // Only the two branches can work at the same time
+        assert_eq!(net.concurrency_relation().to_string(), "T1 || T2");
+
+        // Under maximal step semantics, both branches always work in a single step
+        let rows: Vec<_> = net.explore_steps(StepSemantics::Maximal).collect();
+        let steps: Vec<String> = rows
+            .iter()
+            .flat_map(|(_, _, continuations)| continuations.iter().map(ToString::to_string))
+            .collect();
+        assert_eq!(steps, ["{T0}->M001", "{T1, T2}->M002", "{T3}->M003", "{T4}->M000"]);
+
+        // Arbitrary steps also allow the branches to work one after the other
+        assert_eq!(net.explore_steps(StepSemantics::Arbitrary).count(), net.reachability_analysis().rows.len());
+        let (_, _, continuations) = net.explore_steps(StepSemantics::Arbitrary).nth(1).unwrap();
+        assert_eq!(continuations.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+//! This module detects the symmetries of a Petri net and uses them to reduce the reachability analysis.
+//!
+//! A symmetry (automorphism) is a permutation of the places and of the transitions which maps every arc
+//! onto an arc with the same weight, every place onto a place with the same capacity,
+//! and the initial marking onto itself. Nets built from replicated components, such as several identical
+//! workers sharing a resource, have many of them. Symmetric markings behave the same way,
+//! so it suffices to explore a single representative of every orbit of markings.
+//!
+//! Automorphisms are found by color refinement and individualization on the bipartite graph of the net.
+//! The group they form is stored as a stabilizer chain over the places followed by the transitions:
+//! on level i, it holds one automorphism for each node that node i can be mapped to while the nodes before it
+//! stay fixed. The levels of the transitions hold the automorphisms which only permute transitions, e.g. parallel
+//! ones. The chain determines the order of the group, and all of its elements together generate it.
+//!
+//! The representative of a marking is computed greedily along the chain, by choosing on every level
+//! the image whose tokens are lexicographically smallest. This is cheap, but for some groups the same orbit
+//! may end up with more than one representative, which costs some reduction but never correctness.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use super::reachability::{Markings, TransitionIO};
+use super::{Arc, CapacityFn, Continuation, Marking, MarkingFn, MarkingId, PetriNet, PlaceId, TransitionId, WeightFn};
+
+/// A permutation of the places and transitions which maps the net onto itself
+/// Displayed in cycle notation, e.g. (P1 P3)(P2 P4)(T0 T2)(T1 T3), or () for the identity
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Automorphism {
+    places: Vec<PlaceId>,
+    transitions: Vec<TransitionId>,
+}
+
+impl Automorphism {
+    /// The place which the given place is mapped to
+    pub fn place(&self, place: PlaceId) -> PlaceId {
+        self.places[place.0]
+    }
+    /// The transition which the given transition is mapped to
+    pub fn transition(&self, transition: TransitionId) -> TransitionId {
+        self.transitions[transition.0]
+    }
+    /// The automorphism which undoes this one
+    pub fn inverse(&self) -> Automorphism {
+        let mut places = self.places.clone();
+        for (index, image) in self.places.iter().enumerate() {
+            places[image.0] = PlaceId(index);
+        }
+        let mut transitions = self.transitions.clone();
+        for (index, image) in self.transitions.iter().enumerate() {
+            transitions[image.0] = TransitionId(index);
+        }
+        Automorphism { places, transitions }
+    }
+    /// Move the tokens of every place to its image
+    pub fn apply(&self, marking: &Marking) -> Marking {
+        marking.iter().map(|(place, tokens)| (self.place(place), tokens)).collect()
+    }
+}
+
+impl Display for Automorphism {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        /// Write the non-trivial cycles of a permutation given by the image of every index
+        fn write_cycles<T: Display>(f: &mut Formatter<'_>, images: &[usize], name: impl Fn(usize) -> T) -> FmtResult {
+            let mut seen = vec![false; images.len()];
+            for start in 0..images.len() {
+                if seen[start] || images[start] == start {
+                    continue;
+                }
+                write!(f, "(")?;
+                let mut separator = "";
+                let mut current = start;
+                while !seen[current] {
+                    seen[current] = true;
+                    write!(f, "{}{}", separator, name(current))?;
+                    separator = " ";
+                    current = images[current];
+                }
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+        let places: Vec<usize> = self.places.iter().map(|place| place.0).collect();
+        let transitions: Vec<usize> = self.transitions.iter().map(|transition| transition.0).collect();
+        let identity = places.iter().enumerate().all(|(index, &image)| index == image)
+            && transitions.iter().enumerate().all(|(index, &image)| index == image);
+        if identity {
+            return write!(f, "()");
+        }
+        write_cycles(f, &places, PlaceId)?;
+        write_cycles(f, &transitions, TransitionId)
+    }
+}
+
+/// The net as a colored bipartite graph, whose nodes are the places (0..P) followed by the transitions (P..P+T)
+struct Graph {
+    places: usize,
+    /// For every node, the other end, weight and direction (true if outgoing) of each of its arcs
+    adjacency: Vec<Vec<(usize, usize, bool)>>,
+    /// The weight of the arc between two nodes
+    arcs: HashMap<(usize, usize), usize>,
+    /// The colors which any automorphism has to preserve: the kind of node, initial tokens and capacity
+    colors: Vec<usize>,
+}
+
+impl Graph {
+    fn new<C: CapacityFn, W: WeightFn>(net: &PetriNet<C, W>) -> Self {
+        let places = net.places.len();
+        let nodes = places + net.transitions.len();
+        let mut adjacency = vec![Vec::new(); nodes];
+        let mut arcs = HashMap::new();
+        for arc in &net.arcs {
+            let (source, target) = match *arc {
+                Arc::PlaceTransition(place, transition) => (place.0, places + transition.0),
+                Arc::TransitionPlace(transition, place) => (places + transition.0, place.0),
+            };
+            let weight = net.weights.get_or_default(arc).0;
+            adjacency[source].push((target, weight, true));
+            adjacency[target].push((source, weight, false));
+            arcs.insert((source, target), weight);
+        }
+        let signatures: Vec<(bool, usize, usize)> = (0..nodes)
+            .map(|node| match node < places {
+                true => {
+                    let place = PlaceId(node);
+                    (false, net.initial_marking.get(&place).0, net.capacities.get_or_default(&place).0)
+                }
+                false => (true, 0, 0),
+            })
+            .collect();
+        let colors = number(&signatures, &[]);
+        Graph { places, adjacency, arcs, colors }
+    }
+    /// Refine two colorings of the graph together until they are stable,
+    /// so that equal colors on both sides still denote nodes which may be mapped onto each other
+    fn refine(&self, left: &mut Vec<usize>, right: &mut Vec<usize>) {
+        let signature = |colors: &[usize], node: usize| {
+            let mut neighbors: Vec<(usize, usize, bool)> = self.adjacency[node]
+                .iter()
+                .map(|&(other, weight, outgoing)| (colors[other], weight, outgoing))
+                .collect();
+            neighbors.sort_unstable();
+            (colors[node], neighbors)
+        };
+        let mut count = 0;
+        loop {
+            let left_signatures: Vec<_> = (0..left.len()).map(|node| signature(left, node)).collect();
+            let right_signatures: Vec<_> = (0..right.len()).map(|node| signature(right, node)).collect();
+            let colors = number(&left_signatures, &right_signatures);
+            let refined = colors.iter().max().map_or(0, |&max| max + 1);
+            *right = colors[left.len()..].to_vec();
+            *left = colors[..left.len()].to_vec();
+            if refined == count {
+                return;
+            }
+            count = refined;
+        }
+    }
+    /// Search for an automorphism which respects the given colorings of its preimages (left) and images (right),
+    /// and return the image of every node
+    fn search(&self, mut left: Vec<usize>, mut right: Vec<usize>) -> Option<Vec<usize>> {
+        self.refine(&mut left, &mut right);
+        let (mut sorted_left, mut sorted_right) = (left.clone(), right.clone());
+        sorted_left.sort_unstable();
+        sorted_right.sort_unstable();
+        if sorted_left != sorted_right {
+            return None;
+        }
+        let fresh = sorted_left.last().map_or(0, |&max| max + 1);
+        let mut sizes = vec![0; fresh];
+        for &color in &left {
+            sizes[color] += 1;
+        }
+        match (0..left.len()).find(|&node| sizes[left[node]] > 1) {
+            // Every color is unique, so the mapping is determined
+            None => {
+                let mut nodes_by_color = vec![0; fresh];
+                for (node, &color) in right.iter().enumerate() {
+                    nodes_by_color[color] = node;
+                }
+                let mapping: Vec<usize> = left.iter().map(|&color| nodes_by_color[color]).collect();
+                let preserves_arcs = self.arcs
+                    .iter()
+                    .all(|(&(source, target), weight)| self.arcs.get(&(mapping[source], mapping[target])) == Some(weight));
+                preserves_arcs.then_some(mapping)
+            }
+            // Otherwise, try every possible image of the first ambiguous node
+            Some(node) => (0..right.len()).filter(|&image| right[image] == left[node]).find_map(|image| {
+                let (mut left, mut right) = (left.clone(), right.clone());
+                left[node] = fresh;
+                right[image] = fresh;
+                self.search(left, right)
+            }),
+        }
+    }
+    fn automorphism(&self, mapping: &[usize]) -> Automorphism {
+        Automorphism {
+            places: mapping[..self.places].iter().map(|&node| PlaceId(node)).collect(),
+            transitions: mapping[self.places..].iter().map(|&node| TransitionId(node - self.places)).collect(),
+        }
+    }
+}
+
+/// Number the distinct values of both slices consecutively in ascending order,
+/// and return the number of each value in the order of the concatenated slices
+fn number<T: Ord>(left: &[T], right: &[T]) -> Vec<usize> {
+    let numbers: BTreeMap<&T, usize> = left.iter().chain(right).map(|value| (value, 0)).collect();
+    let numbers: BTreeMap<&T, usize> = numbers.into_keys().enumerate().map(|(number, value)| (value, number)).collect();
+    left.iter().chain(right).map(|value| numbers[value]).collect()
+}
+
+/// The automorphism group of a Petri net as a stabilizer chain
+/// Displayed as its generators, one per line
+#[derive(Debug, Clone, Default)]
+pub struct Symmetries {
+    /// On level i, the automorphisms which fix the nodes before node i and map node i to another node,
+    /// where the places come first and the transitions after them
+    levels: Vec<Vec<Automorphism>>,
+    /// The inverses of the automorphisms on each level of a place, which map other places onto place i
+    inverses: Vec<Vec<Automorphism>>,
+}
+
+impl Symmetries {
+    /// The non-trivial automorphisms of the stabilizer chain, which generate the whole group
+    pub fn generators(&self) -> impl Iterator<Item = &Automorphism> + '_ {
+        self.levels.iter().flatten()
+    }
+    /// The number of automorphisms in the group, including the identity
+    pub fn order(&self) -> usize {
+        self.levels.iter().fold(1, |order, level| order.saturating_mul(level.len() + 1))
+    }
+    /// Returns true if the identity is the only automorphism
+    pub fn is_trivial(&self) -> bool {
+        self.levels.iter().all(Vec::is_empty)
+    }
+    /// The representative of the orbit of a marking
+    pub fn canonical(&self, marking: &Marking) -> Marking {
+        let tokens = |marking: &Marking| marking.iter().map(|(place, tokens)| (place, tokens.0)).collect::<Vec<_>>();
+        let mut canonical = marking.clone();
+        // Each level can bring the tokens of any place in the orbit of place i onto place i
+        for level in &self.inverses {
+            let mut smallest = tokens(&canonical);
+            let mut best = None;
+            for automorphism in level {
+                let image = automorphism.apply(&canonical);
+                // Tokens on lower places make a marking smaller, absent places come last
+                let image_tokens = tokens(&image);
+                if lexicographically_smaller(&image_tokens, &smallest) {
+                    smallest = image_tokens;
+                    best = Some(image);
+                }
+            }
+            if let Some(image) = best {
+                canonical = image;
+            }
+        }
+        canonical
+    }
+    /// All markings which are symmetric to the given one, starting with the marking itself
+    pub fn orbit(&self, marking: &Marking) -> Vec<Marking> {
+        let mut orbit = vec![marking.clone()];
+        let mut seen: HashSet<Marking> = HashSet::from([marking.clone()]);
+        let mut index = 0;
+        while let Some(current) = orbit.get(index).cloned() {
+            for automorphism in self.generators() {
+                let image = automorphism.apply(&current);
+                if seen.insert(image.clone()) {
+                    orbit.push(image);
+                }
+            }
+            index += 1;
+        }
+        orbit
+    }
+}
+
+/// Compare two markings by their tokens on each place in order of the place IDs,
+/// where the sparse representation leaves out the places without tokens
+fn lexicographically_smaller(a: &[(PlaceId, usize)], b: &[(PlaceId, usize)]) -> bool {
+    for (&(place_a, tokens_a), &(place_b, tokens_b)) in a.iter().zip(b) {
+        if place_a != place_b {
+            // The marking which skips a place has no tokens on it
+            return place_a > place_b;
+        }
+        if tokens_a != tokens_b {
+            return tokens_a < tokens_b;
+        }
+    }
+    a.len() < b.len()
+}
+
+impl Display for Symmetries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for automorphism in self.generators() {
+            writeln!(f, "{}", automorphism)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Compute the group of automorphisms which preserve the structure and the initial marking of the net
+    pub fn symmetries(&self) -> Symmetries {
+        let graph = Graph::new(self);
+        let mut fixed = graph.colors.clone();
+        let places = self.places.len();
+        let nodes = places + self.transitions.len();
+        let mut levels = Vec::with_capacity(nodes);
+        for node in 0..nodes {
+            let (mut colors, mut unused) = (fixed.clone(), fixed.clone());
+            graph.refine(&mut colors, &mut unused);
+            let fresh = fixed.iter().max().map_or(0, |&max| max + 1);
+            // Places and transitions have different colors, so the other node is of the same kind
+            let level = (0..nodes)
+                .filter(|&other| other != node && colors[other] == colors[node])
+                .filter_map(|other| {
+                    let (mut left, mut right) = (fixed.clone(), fixed.clone());
+                    left[node] = fresh;
+                    right[other] = fresh;
+                    graph.search(left, right)
+                })
+                .map(|mapping| graph.automorphism(&mapping))
+                .collect();
+            levels.push(level);
+            // All further levels keep this node in place
+            fixed[node] = fresh;
+        }
+        // Automorphisms which fix every place do not move any tokens, so only the levels of the places matter for markings
+        let inverses = levels[..places].iter().map(|level: &Vec<Automorphism>| level.iter().map(Automorphism::inverse).collect()).collect();
+        Symmetries { levels, inverses }
+    }
+    /// Lazily explore one representative of every orbit of reachable markings
+    pub fn explore_symmetric(&self) -> SymmetricExploration<'_, C, W> {
+        let symmetries = self.symmetries();
+        let initial_marking = symmetries.canonical(&self.initial_marking);
+        let mut markings = Markings::default();
+        let id = markings.remember(initial_marking.clone());
+        SymmetricExploration {
+            petri_net: self,
+            transition_io: self.transition_io(),
+            symmetries,
+            markings,
+            queue: VecDeque::from([(id, initial_marking)]),
+        }
+    }
+}
+
+/// A breadth-first exploration of the reachability graph which is reduced by the symmetries of the net.
+/// Every call to `next` explores one representative, fires all transitions enabled in it,
+/// and yields it together with the continuations leading out of it.
+/// Each continuation leads to the representative of the marking reached by firing the transition.
+#[derive(Debug)]
+pub struct SymmetricExploration<'net, C: CapacityFn, W: WeightFn> {
+    petri_net: &'net PetriNet<C, W>,
+    transition_io: Vec<TransitionIO>,
+    symmetries: Symmetries,
+    markings: Markings,
+    queue: VecDeque<(MarkingId, Marking)>,
+}
+
+impl<C: CapacityFn, W: WeightFn> SymmetricExploration<'_, C, W> {
+    /// The symmetries by which the exploration is reduced
+    pub fn symmetries(&self) -> &Symmetries {
+        &self.symmetries
+    }
+    /// Explore until a marking symmetric to the given one is found, and return true if there is one
+    pub fn is_reachable(&mut self, marking: &Marking) -> bool {
+        let orbit = self.symmetries.orbit(marking);
+        loop {
+            if orbit.iter().any(|marking| self.markings.look_up(marking).is_some()) {
+                return true;
+            }
+            if self.next().is_none() {
+                return false;
+            }
+        }
+    }
+    /// Finish the exploration and expand the representatives back to all reachable markings of the net
+    pub fn reachable_markings(mut self) -> Vec<Marking> {
+        self.by_ref().for_each(drop);
+        let mut representatives: Vec<(Marking, MarkingId)> = self.markings.markings.into_iter().collect();
+        representatives.sort_by_key(|(_, id)| *id);
+        let mut seen = HashSet::new();
+        representatives
+            .iter()
+            .flat_map(|(representative, _)| self.symmetries.orbit(representative))
+            .filter(|marking| seen.insert(marking.clone()))
+            .collect()
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> Iterator for SymmetricExploration<'_, C, W> {
+    type Item = (MarkingId, Marking, Vec<Continuation>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (marking_id, marking) = self.queue.pop_front()?;
+        let fired = self.petri_net.fire_transitions(&self.transition_io, &marking);
+        let mut continuations = Vec::with_capacity(fired.len());
+        for (transition_id, resulting_marking) in fired {
+            let representative = self.symmetries.canonical(&resulting_marking);
+            let resulting_marking_id = match self.markings.look_up(&representative) {
+                Some(existing_marking_id) => existing_marking_id,
+                None => {
+                    let new_marking_id = self.markings.remember(representative.clone());
+                    self.queue.push_back((new_marking_id, representative));
+                    new_marking_id
+                }
+            };
+            continuations.push(Continuation(transition_id, resulting_marking_id));
+        }
+        Some((marking_id, marking, continuations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::petri_net::{Tokens, Transition};
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_symmetries() -> Result<(), Box<dyn std::error::Error>> {
+        // Three identical workers share a mutex, so they can be permuted arbitrarily
+        let pnml: Pnml = parse_xml(include_str!("../../models/workers.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let symmetries = net.symmetries();
+        assert_eq!(symmetries.order(), 6);
+        assert_eq!(
+            symmetries.generators().next().map(ToString::to_string).as_deref(),
+            Some("(P1 P3)(P2 P4)(T0 T2)(T1 T3)")
+        );
+
+        // Whichever worker is busy, the representative is the first one
+        let last_busy: Marking = [(PlaceId(1), Tokens(1)), (PlaceId(3), Tokens(1)), (PlaceId(6), Tokens(1))].into_iter().collect();
+        let first_busy: Marking = [(PlaceId(2), Tokens(1)), (PlaceId(3), Tokens(1)), (PlaceId(5), Tokens(1))].into_iter().collect();
+        assert_eq!(symmetries.canonical(&last_busy), first_busy);
+        assert_eq!(symmetries.orbit(&last_busy).len(), 3);
+
+        // Only idle and one busy worker are explored, which expand back to all reachable markings
+        let rows: Vec<_> = net.explore_symmetric().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].2.len(), 3);
+        let mut exploration = net.explore_symmetric();
+        assert!(exploration.is_reachable(&last_busy));
+        let reachable = exploration.reachable_markings();
+        assert_eq!(reachable.len(), net.reachability_analysis().rows.len());
+
+        // The two branches of the parallel net can be swapped, so only one of them works on its own
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        assert_eq!(net.symmetries().to_string(), "(P1 P2)(P3 P4)(T1 T2)\n");
+        assert_eq!(net.explore_symmetric().count(), 5);
+        assert_eq!(net.symmetries().order(), 2);
+        let mut reachable = net.explore_symmetric().reachable_markings();
+        let mut expected: Vec<Marking> = net.reachability_analysis().rows.into_iter().map(|(_, marking, _)| marking).collect();
+        let key = |marking: &Marking| marking.iter().map(|(place, tokens)| (place, tokens.0)).collect::<Vec<_>>();
+        reachable.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(reachable, expected);
+
+        // A transition parallel to another one can be swapped with it without moving any place
+        let mut doubled = net.clone();
+        let copy = TransitionId(doubled.transitions.len());
+        doubled.transitions.push(Transition { id: copy, name: "restart again".to_string() });
+        doubled.arcs.extend([Arc::PlaceTransition(PlaceId(5), copy), Arc::TransitionPlace(copy, PlaceId(0))]);
+        let symmetries = doubled.symmetries();
+        assert_eq!(symmetries.order(), 4);
+        assert!(symmetries.generators().any(|automorphism| automorphism.to_string() == "(T4 T5)"));
+
+        // Preparing and taking play different roles, so the confusion net has no symmetries
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        assert!(net.symmetries().is_trivial());
+        assert_eq!(net.symmetries().order(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,999 @@
+//! This module reads and writes nets in the textual `.net` format of [TINA](https://projects.laas.fr/tina/)
+//! and the `.ndr` format of its graphical editor, so that models can be exchanged with TINA users
+//! and the results of both tools compared. A `.net` file looks like this:
+//!
+//! ```text
+//! net handover
+//! tr send : {Send message} [1,3] idle -> channel*2 waiting
+//! tr receive ]0,2] channel*2 -> done
+//! tr ack done waiting -> idle
+//! pl idle (2)
+//! ```
+//!
+//! Every line declares a transition (`tr`), a place (`pl`), a label (`lb`) or the name of the net (`net`),
+//! and `#` starts a comment. Names which are not made of letters, digits, `_` and `'` are quoted in braces.
+//! Places and transitions may be declared on several lines or only be used in arcs; the arcs of all lines add up.
+//! The names are kept as the original IDs of the places and transitions, and their labels as their names.
+//!
+//! An `.ndr` file describes the same net together with the drawing of the editor:
+//!
+//! ```text
+//! p 60.0 100.0 idle 2 n
+//! p 260.0 60.0 channel 0 n {Message channel} s
+//! t 160.0 100.0 send 1 3 n {Send message} s
+//! e idle send 1 n
+//! e send channel 2 n
+//! h handover
+//! ```
+//!
+//! Places (`p`) have a position, a name, a marking and the anchor of the name, and transitions (`t`) a position,
+//! a name and the bounds of their interval, where `w` is infinity. Both may end with a label and its anchor.
+//! Edges (`e`) lead from a node to another one, optionally with the angle and radius of their curve at either end,
+//! and have a weight and an anchor. The name of the net comes last (`h`). The positions are kept as the graphics of
+//! the places and transitions, so they end up in PNML files, while anchors and curves are dropped.
+//! Written `.ndr` files place the nodes by their graphics if they have any, and by the layered layout otherwise.
+//! The editor only knows closed intervals and intervals open towards infinity, so other intervals cannot be written.
+//!
+//! The time intervals of transitions are kept with the transitions and written back, also to PNML,
+//! but they are not interpreted: the reachability analysis explores the untimed net,
+//! whose reachable markings include those of the timed one.
+//! Test arcs, inhibitor arcs, stopwatch arcs and priorities cannot be represented and are rejected.
+//! TINA has no capacities, so a place with a finite capacity is written together with a complementary place
+//! holding its free capacity, which restricts the transitions exactly like the capacity does.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::iter::Peekable;
+use std::path::Path;
+use std::vec::IntoIter;
+
+use super::annotations::IdAllocator;
+use super::any::unsupported;
+use super::layout::NODE_SPACING;
+use super::{
+    Annotations, Arc, Capacity, CapacityFn, Marking, MarkingFn, PetriNet, Place, PlaceId, Position, Tokens, Transition,
+    TransitionId, Weight, WeightFn, XmlElement,
+};
+use crate::Error;
+
+/// The name of TINA in tool-specific PNML elements
+const TOOL: &str = "Tina";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A name or number, with the braces of a quoted name removed
+    Name(String),
+    Symbol(char),
+    Arrow,
+}
+
+fn invalid(line: usize, message: impl AsRef<str>) -> Error {
+    Error::InvalidTina(format!("line {}: {}", line, message.as_ref()))
+}
+
+/// Returns true if the character may appear in a name without braces
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '\''
+}
+
+/// Quote a name in braces unless it only consists of letters, digits, _ and '
+fn quote(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(is_name_char) {
+        return name.to_string();
+    }
+    let mut quoted = String::from("{");
+    for c in name.chars() {
+        if matches!(c, '{' | '}' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(if c == '\n' { ' ' } else { c });
+    }
+    quoted.push('}');
+    quoted
+}
+
+/// Split a TINA file into the tokens of each line which declares something, dropping comments
+fn tokenize(text: &str) -> Result<Vec<(usize, Vec<Token>)>, Error> {
+    let mut lines = Vec::new();
+    for (index, text) in text.lines().enumerate() {
+        let line = index + 1;
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '#' => break,
+                c if c.is_whitespace() => {}
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('\\') => name.extend(chars.next()),
+                            Some(c) => name.push(c),
+                            None => return Err(invalid(line, "unterminated name")),
+                        }
+                    }
+                    tokens.push(Token::Name(name));
+                }
+                '-' if chars.peek() == Some(&'>') => {
+                    chars.next();
+                    tokens.push(Token::Arrow);
+                }
+                ':' | '(' | ')' | '[' | ']' | ',' | '*' | '?' | '!' | '-' | '>' | '<' => tokens.push(Token::Symbol(c)),
+                c if is_name_char(c) || c == '.' => {
+                    let mut name = c.to_string();
+                    while let Some(c) = chars.next_if(|&c| is_name_char(c) || c == '.') {
+                        name.push(c);
+                    }
+                    tokens.push(Token::Name(name));
+                }
+                c => return Err(invalid(line, format!("unexpected {}", c))),
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push((line, tokens));
+        }
+    }
+    Ok(lines)
+}
+
+/// Returns true if the text looks like a TINA file, i.e. starts with a net, transition or place declaration
+pub(crate) fn is_tina(text: &str) -> bool {
+    tokenize(text).is_ok_and(|lines| {
+        matches!(lines.first(), Some((_, tokens)) if matches!(tokens.first(), Some(Token::Name(keyword)) if ["net", "tr", "pl"].contains(&keyword.as_str())))
+    })
+}
+
+/// The name of the node at the other end of an arc, with the weight of the arc
+type NodeWeight = (String, usize);
+
+/// The anchors of names and labels in `.ndr` files, i.e. the directions in which they are drawn from their node
+const ANCHORS: [&str; 9] = ["n", "ne", "e", "se", "s", "sw", "w", "nw", "c"];
+
+/// Reads the tokens of a line of a TINA file
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+    line: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, Error> {
+        self.tokens.next().ok_or_else(|| invalid(self.line, "unexpected end of line"))
+    }
+    /// Returns true if the next token is the given one
+    fn at(&mut self, token: &Token) -> bool {
+        self.tokens.peek() == Some(token)
+    }
+    fn at_end(&mut self) -> bool {
+        self.tokens.peek().is_none()
+    }
+    fn symbol(&mut self, symbol: char) -> Result<(), Error> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            _ => Err(invalid(self.line, format!("expected {}", symbol))),
+        }
+    }
+    fn name(&mut self) -> Result<String, Error> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            _ => Err(invalid(self.line, "expected a name")),
+        }
+    }
+    /// A number, possibly with the suffix K for thousands or M for millions
+    fn number(&mut self) -> Result<usize, Error> {
+        let text = self.name()?;
+        let (digits, factor) = match text.strip_suffix('K') {
+            Some(digits) => (digits, 1_000),
+            None => text.strip_suffix('M').map_or((text.as_str(), 1), |digits| (digits, 1_000_000)),
+        };
+        digits
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_mul(factor))
+            .ok_or_else(|| invalid(self.line, format!("expected a number instead of {}", text)))
+    }
+    /// A name or number which may be negative, e.g. a coordinate in an `.ndr` file
+    fn signed(&mut self) -> Result<String, Error> {
+        if self.at(&Token::Symbol('-')) {
+            self.next()?;
+            return Ok(format!("-{}", self.name()?));
+        }
+        self.name()
+    }
+    /// The position of a node in an `.ndr` file
+    fn position(&mut self) -> Result<Position, Error> {
+        let mut coordinate = || {
+            let text = self.signed()?;
+            text.parse::<f64>().map_err(|_| invalid(self.line, format!("expected a coordinate instead of {}", text)))
+        };
+        Ok(Position { x: coordinate()?, y: coordinate()? })
+    }
+    /// Returns true if the next token is the anchor of a name or label in an `.ndr` file
+    fn at_anchor(&mut self) -> bool {
+        matches!(self.tokens.peek(), Some(Token::Name(name)) if ANCHORS.contains(&name.as_str()))
+    }
+    fn anchor(&mut self) -> Result<(), Error> {
+        if !self.at_anchor() {
+            return Err(invalid(self.line, "expected an anchor"));
+        }
+        self.next().map(drop)
+    }
+    /// An optional label and its anchor at the end of a node in an `.ndr` file
+    fn ndr_label(&mut self) -> Result<Option<String>, Error> {
+        if self.at_end() {
+            return Ok(None);
+        }
+        let label = self.name()?;
+        self.anchor()?;
+        Ok(Some(label))
+    }
+    /// An optional label after a colon
+    fn label(&mut self) -> Result<Option<String>, Error> {
+        if !self.at(&Token::Symbol(':')) {
+            return Ok(None);
+        }
+        self.next()?;
+        self.name().map(Some)
+    }
+    /// The weight of an arc after the name of its node, which is 1 if it is left out
+    fn weight(&mut self) -> Result<usize, Error> {
+        let weight = match self.tokens.peek() {
+            Some(Token::Symbol('*')) => {
+                self.next()?;
+                self.number()?
+            }
+            Some(Token::Symbol('?')) => {
+                self.next()?;
+                let kind = if self.at(&Token::Symbol('-')) { "inhibitor" } else { "test" };
+                return Err(invalid(self.line, format!("{} arcs are not supported", kind)));
+            }
+            Some(Token::Symbol('!')) => return Err(invalid(self.line, "stopwatch arcs are not supported")),
+            _ => 1,
+        };
+        if weight == 0 {
+            return Err(invalid(self.line, "weight must be at least 1"));
+        }
+        Ok(weight)
+    }
+    /// The arcs of a declaration: the nodes before the arrow with their weights, and those after it
+    fn arcs(&mut self) -> Result<(Vec<NodeWeight>, Vec<NodeWeight>), Error> {
+        let mut inputs = Vec::new();
+        while !self.at(&Token::Arrow) {
+            inputs.push((self.name()?, self.weight()?));
+        }
+        self.next()?;
+        let mut outputs = Vec::new();
+        while !self.at_end() {
+            outputs.push((self.name()?, self.weight()?));
+        }
+        Ok((inputs, outputs))
+    }
+}
+
+/// The firing interval of a transition in a time Petri net
+#[derive(Debug, Clone, PartialEq)]
+struct Interval {
+    lower: String,
+    lower_open: bool,
+    /// The upper bound, or none if the interval is unbounded
+    upper: Option<String>,
+    upper_open: bool,
+}
+
+impl Interval {
+    /// Read an interval like [1,3], ]0,2] or [0,w[
+    fn parse(parser: &mut Parser) -> Result<Self, Error> {
+        let lower_open = parser.next()? == Token::Symbol(']');
+        let lower = parser.name()?;
+        parser.symbol(',')?;
+        let upper = Some(parser.name()?).filter(|upper| upper != "w");
+        let upper_open = match parser.next()? {
+            Token::Symbol(']') => upper.is_none(),
+            Token::Symbol('[') => true,
+            _ => return Err(invalid(parser.line, "expected ] or [")),
+        };
+        Interval { lower, lower_open, upper, upper_open }.checked(parser.line)
+    }
+    /// Read the bounds of an interval in an `.ndr` file like 1 3 or 0 w, which is closed unless it is unbounded
+    fn parse_bounds(parser: &mut Parser) -> Result<Self, Error> {
+        let lower = parser.name()?;
+        let upper = Some(parser.name()?).filter(|upper| upper != "w");
+        Interval { lower, lower_open: false, upper_open: upper.is_none(), upper }.checked(parser.line)
+    }
+    /// Check that the bounds are times and the interval is not empty
+    fn checked(self, line: usize) -> Result<Self, Error> {
+        let time = |text: &str| text.parse::<f64>().ok().filter(|time| *time >= 0.0 && !text.starts_with('.'));
+        let lower_time = time(&self.lower).ok_or_else(|| invalid(line, format!("invalid time {}", self.lower)))?;
+        if let Some(upper) = &self.upper {
+            let upper_time = time(upper).ok_or_else(|| invalid(line, format!("invalid time {}", upper)))?;
+            if lower_time > upper_time || (lower_time == upper_time && (self.lower_open || self.upper_open)) {
+                return Err(invalid(line, "empty interval"));
+            }
+        }
+        Ok(self)
+    }
+    /// Returns true if the interval does not restrict the transition, i.e. is [0,w[
+    fn is_unrestricted(&self) -> bool {
+        !self.lower_open && self.lower.parse::<f64>() == Ok(0.0) && self.upper.is_none()
+    }
+    /// The interval as the tool-specific element TINA writes into PNML files
+    fn to_element(&self) -> XmlElement {
+        let closure = match (self.lower_open, self.upper_open) {
+            (false, false) => "closed",
+            (true, true) => "open",
+            (false, true) => "closed-open",
+            (true, false) => "open-closed",
+        };
+        let bound = |name: &str, text: &str| XmlElement { text: Some(text.to_string()), ..XmlElement::new(name) };
+        let mut interval = XmlElement::new("interval");
+        interval.attributes = vec![
+            ("xmlns".to_string(), "http://www.w3.org/1998/Math/MathML".to_string()),
+            ("closure".to_string(), closure.to_string()),
+        ];
+        interval.children = vec![
+            bound("cn", &self.lower),
+            self.upper.as_ref().map_or_else(|| bound("ci", "infty"), |upper| bound("cn", upper)),
+        ];
+        let mut delay = XmlElement::new("delay");
+        delay.children.push(interval);
+        let mut tool = XmlElement::new("toolspecific");
+        tool.attributes = vec![("tool".to_string(), TOOL.to_string()), ("version".to_string(), "1.0".to_string())];
+        tool.children.push(delay);
+        tool
+    }
+    /// Find the interval among the annotations of a transition
+    fn from_annotations(transition: &XmlElement) -> Option<Self> {
+        let interval = transition
+            .children
+            .iter()
+            .filter(|child| child.name == "toolspecific" && child.attribute("tool") == Some(TOOL))
+            .find_map(|tool| tool.child("delay")?.child("interval"))?;
+        let (lower_open, upper_open) = match interval.attribute("closure")? {
+            "closed" => (false, false),
+            "open" => (true, true),
+            "closed-open" => (false, true),
+            "open-closed" => (true, false),
+            _ => return None,
+        };
+        let [lower, upper] = interval.children.as_slice() else {
+            return None;
+        };
+        Some(Interval {
+            lower: lower.text.clone().filter(|_| lower.name == "cn")?,
+            lower_open,
+            upper: if upper.name == "ci" { None } else { Some(upper.text.clone()?) },
+            upper_open: upper_open || upper.name == "ci",
+        })
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}{},{}{}",
+            if self.lower_open { ']' } else { '[' },
+            self.lower,
+            self.upper.as_deref().unwrap_or("w"),
+            if self.upper_open { '[' } else { ']' }
+        )
+    }
+}
+
+/// The places, transitions and arcs of a TINA file, which are collected line by line
+#[derive(Default)]
+struct Declarations {
+    places: Vec<Place>,
+    place_ids: HashMap<String, PlaceId>,
+    transitions: Vec<Transition>,
+    transition_ids: HashMap<String, TransitionId>,
+    weights: Vec<(Arc, Weight)>,
+    initial_marking: Marking,
+    intervals: HashMap<TransitionId, Interval>,
+    /// The positions of the places and transitions of an `.ndr` file
+    place_positions: HashMap<PlaceId, Position>,
+    transition_positions: HashMap<TransitionId, Position>,
+}
+
+impl Declarations {
+    /// The place with the given name, which is created when it is first used
+    fn place(&mut self, name: String) -> PlaceId {
+        let next = PlaceId(self.places.len());
+        let id = *self.place_ids.entry(name.clone()).or_insert(next);
+        if id == next {
+            self.places.push(Place { id, name });
+        }
+        id
+    }
+    /// The transition with the given name, which is created when it is first used
+    fn transition(&mut self, name: String) -> TransitionId {
+        let next = TransitionId(self.transitions.len());
+        let id = *self.transition_ids.entry(name.clone()).or_insert(next);
+        if id == next {
+            self.transitions.push(Transition { id, name });
+        }
+        id
+    }
+    /// Add an arc, or add the weight to the arc if it was declared before
+    fn arc(&mut self, arc: Arc, weight: usize) {
+        match self.weights.iter_mut().find(|(existing, _)| *existing == arc) {
+            Some((_, existing)) => existing.0 += weight,
+            None => self.weights.push((arc, Weight(weight))),
+        }
+    }
+    /// Build the net, keeping the intervals and positions as annotations
+    fn into_net<C, W>(self, id: String, capacities: C) -> Result<PetriNet<C, W>, Error>
+    where
+        C: CapacityFn,
+        W: WeightFn + FromIterator<(Arc, Weight)>,
+    {
+        let weights: W = self.weights.iter().copied().collect();
+        if let Some((arc, weight)) = self.weights.iter().find(|(arc, weight)| weights.get_or_default(arc) != *weight) {
+            let transition = match arc {
+                Arc::PlaceTransition(_, transition) | Arc::TransitionPlace(transition, _) => transition,
+            };
+            let name = self.transition_ids.iter().find(|(_, id)| *id == transition).map_or("", |(name, _)| name);
+            return Err(Error::InvalidTina(format!("transition {}: {}", name, unsupported(format_args!("weight {}", weight.0)))));
+        }
+
+        let mut annotations = Annotations::default();
+        annotations.ids.places = self.place_ids.into_iter().map(|(name, id)| (id, name)).collect();
+        annotations.ids.transitions = self.transition_ids.into_iter().map(|(name, id)| (id, name)).collect();
+        for (place, position) in self.place_positions {
+            let mut element = XmlElement::new("place");
+            element.children.push(graphics(position));
+            annotations.places.insert(place, element);
+        }
+        for (transition, position) in self.transition_positions {
+            let mut element = XmlElement::new("transition");
+            element.children.push(graphics(position));
+            annotations.transitions.insert(transition, element);
+        }
+        for (transition, interval) in self.intervals {
+            if !interval.is_unrestricted() {
+                let element = annotations.transitions.entry(transition).or_insert_with(|| XmlElement::new("transition"));
+                element.children.push(interval.to_element());
+            }
+        }
+        Ok(PetriNet {
+            id,
+            places: self.places,
+            transitions: self.transitions,
+            arcs: self.weights.iter().map(|(arc, _)| *arc).collect(),
+            capacities,
+            weights,
+            initial_marking: self.initial_marking,
+            annotations,
+        })
+    }
+}
+
+/// The graphics of a place or transition at the given position, as in PNML files
+fn graphics(position: Position) -> XmlElement {
+    let mut element = XmlElement::new("position");
+    element.attributes = vec![("x".to_string(), position.x.to_string()), ("y".to_string(), position.y.to_string())];
+    let mut graphics = XmlElement::new("graphics");
+    graphics.children.push(element);
+    graphics
+}
+
+/// The position which the graphics among the annotations of a place or transition give it
+fn position(element: Option<&XmlElement>) -> Option<Position> {
+    let position = element?.child("graphics")?.child("position")?;
+    Some(Position { x: position.attribute("x")?.parse().ok()?, y: position.attribute("y")?.parse().ok()? })
+}
+
+/// A place as it is written to TINA files
+struct TinaPlace {
+    name: String,
+    label: Option<String>,
+    tokens: usize,
+    /// The place of the net, or the one whose free capacity this place holds if it is a complementary place
+    place: PlaceId,
+    complement: bool,
+}
+
+/// A transition as it is written to TINA files, with the places it consumes from and produces on and the weights
+struct TinaTransition {
+    name: String,
+    label: Option<String>,
+    transition: TransitionId,
+    interval: Option<Interval>,
+    inputs: Vec<(usize, usize)>,
+    outputs: Vec<(usize, usize)>,
+}
+
+impl<C, W> PetriNet<C, W>
+where
+    C: CapacityFn + FromIterator<(PlaceId, Capacity)>,
+    W: WeightFn + FromIterator<(Arc, Weight)>,
+{
+    /// Read a net in the TINA format
+    pub fn read_tina<In: Read>(mut input: In) -> Result<Self, Error> {
+        let mut text = String::new();
+        input.read_to_string(&mut text).map_err(Error::IO)?;
+        let mut id = String::new();
+        let mut net = Declarations::default();
+        let capacities = C::from_iter(std::iter::empty());
+
+        for (line, tokens) in tokenize(&text)? {
+            let mut parser = Parser { tokens: tokens.into_iter().peekable(), line };
+            match parser.name()?.as_str() {
+                "net" => id = parser.name()?,
+                "tr" => {
+                    let transition = net.transition(parser.name()?);
+                    if let Some(label) = parser.label()? {
+                        net.transitions[transition.0].name = label;
+                    }
+                    if parser.at(&Token::Symbol('[')) || parser.at(&Token::Symbol(']')) {
+                        net.intervals.insert(transition, Interval::parse(&mut parser)?);
+                    }
+                    let (inputs, outputs) = if parser.at_end() { Default::default() } else { parser.arcs()? };
+                    for (name, weight) in inputs {
+                        let place = net.place(name);
+                        net.arc(Arc::PlaceTransition(place, transition), weight);
+                    }
+                    for (name, weight) in outputs {
+                        let place = net.place(name);
+                        net.arc(Arc::TransitionPlace(transition, place), weight);
+                    }
+                }
+                "pl" => {
+                    let place = net.place(parser.name()?);
+                    if let Some(label) = parser.label()? {
+                        net.places[place.0].name = label;
+                    }
+                    if parser.at(&Token::Symbol('(')) {
+                        parser.next()?;
+                        let tokens = parser.number()?;
+                        parser.symbol(')')?;
+                        if tokens > capacities.get_or_default(&place).0 {
+                            return Err(invalid(line, unsupported(format_args!("{} tokens", tokens))));
+                        }
+                        net.initial_marking.set(place, Tokens(tokens));
+                    }
+                    if !parser.at_end() {
+                        let (inputs, outputs) = parser.arcs()?;
+                        for (name, weight) in inputs {
+                            let transition = net.transition(name);
+                            net.arc(Arc::TransitionPlace(transition, place), weight);
+                        }
+                        for (name, weight) in outputs {
+                            let transition = net.transition(name);
+                            net.arc(Arc::PlaceTransition(place, transition), weight);
+                        }
+                    }
+                }
+                "lb" => {
+                    let name = parser.name()?;
+                    let label = parser.name()?;
+                    if let Some(place) = net.place_ids.get(&name) {
+                        net.places[place.0].name = label;
+                    } else if let Some(transition) = net.transition_ids.get(&name) {
+                        net.transitions[transition.0].name = label;
+                    } else {
+                        return Err(invalid(line, format!("unknown place or transition {}", name)));
+                    }
+                    continue;
+                }
+                "nt" => continue, // Notes are comments
+                "pr" => return Err(invalid(line, "priorities are not supported")),
+                declaration => return Err(invalid(line, format!("unknown declaration {}", declaration))),
+            }
+            if !parser.at_end() {
+                return Err(invalid(line, "unexpected content at the end of the line"));
+            }
+        }
+
+        net.into_net(id, capacities)
+    }
+    /// Load a net from a file in the TINA format
+    pub fn load_tina<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).map(BufReader::new).map_err(Error::IO)?;
+        Self::read_tina(file)
+    }
+    /// Read a net in the `.ndr` format of the graphical editor of TINA
+    pub fn read_ndr<In: Read>(mut input: In) -> Result<Self, Error> {
+        let mut text = String::new();
+        input.read_to_string(&mut text).map_err(Error::IO)?;
+        let mut id = String::new();
+        let mut net = Declarations::default();
+        let capacities = C::from_iter(std::iter::empty());
+        // Edges are resolved once all places and transitions are known
+        let mut edges = Vec::new();
+
+        for (line, tokens) in tokenize(&text)? {
+            let mut parser = Parser { tokens: tokens.into_iter().peekable(), line };
+            match parser.name()?.as_str() {
+                "p" => {
+                    let position = parser.position()?;
+                    let name = parser.name()?;
+                    if net.place_ids.contains_key(&name) {
+                        return Err(invalid(line, format!("place {} is declared twice", name)));
+                    }
+                    let place = net.place(name);
+                    net.place_positions.insert(place, position);
+                    let tokens = parser.number()?;
+                    if tokens > capacities.get_or_default(&place).0 {
+                        return Err(invalid(line, unsupported(format_args!("{} tokens", tokens))));
+                    }
+                    net.initial_marking.set(place, Tokens(tokens));
+                    parser.anchor()?;
+                    if let Some(label) = parser.ndr_label()? {
+                        net.places[place.0].name = label;
+                    }
+                }
+                "t" => {
+                    let position = parser.position()?;
+                    let name = parser.name()?;
+                    if net.transition_ids.contains_key(&name) {
+                        return Err(invalid(line, format!("transition {} is declared twice", name)));
+                    }
+                    let transition = net.transition(name);
+                    net.transition_positions.insert(transition, position);
+                    // The anchor of the name may come before the interval
+                    if parser.at_anchor() {
+                        parser.next()?;
+                    }
+                    net.intervals.insert(transition, Interval::parse_bounds(&mut parser)?);
+                    parser.anchor()?;
+                    if let Some(label) = parser.ndr_label()? {
+                        net.transitions[transition.0].name = label;
+                    }
+                }
+                "e" => {
+                    edges.push(parser);
+                    continue;
+                }
+                "h" => id = parser.name()?,
+                declaration => return Err(invalid(line, format!("unknown declaration {}", declaration))),
+            }
+            if !parser.at_end() {
+                return Err(invalid(line, "unexpected content at the end of the line"));
+            }
+        }
+
+        for mut parser in edges {
+            let line = parser.line;
+            let mut items = Vec::new();
+            while !parser.at_end() {
+                if parser.at(&Token::Symbol('?')) || parser.at(&Token::Symbol('!')) {
+                    return Err(invalid(line, "test, inhibitor and stopwatch arcs are not supported"));
+                }
+                items.push(parser.signed()?);
+            }
+            // The angle and radius of the curve at the source, at the target, at both ends or at neither
+            let is_number = |item: &String| item.parse::<f64>().is_ok();
+            let (source, target, weight) = match items.as_slice() {
+                [source, target, weight, _] => (source, target, weight),
+                [source, angle, radius, target, weight, _] if is_number(angle) && is_number(radius) => (source, target, weight),
+                [source, target, _, _, weight, _] => (source, target, weight),
+                [source, _, _, target, _, _, weight, _] => (source, target, weight),
+                _ => return Err(invalid(line, "expected an edge like e <source> <target> <weight> <anchor>")),
+            };
+            if !ANCHORS.contains(&items[items.len() - 1].as_str()) {
+                return Err(invalid(line, "expected an anchor"));
+            }
+            let weight = match weight.parse::<usize>() {
+                Ok(0) => return Err(invalid(line, "weight must be at least 1")),
+                Ok(weight) => weight,
+                Err(_) => return Err(invalid(line, format!("expected a number instead of {}", weight))),
+            };
+            let arc = match (net.place_ids.get(source), net.transition_ids.get(target)) {
+                (Some(&place), Some(&transition)) => Arc::PlaceTransition(place, transition),
+                _ => match (net.transition_ids.get(source), net.place_ids.get(target)) {
+                    (Some(&transition), Some(&place)) => Arc::TransitionPlace(transition, place),
+                    _ => {
+                        let message = format!("edge from {} to {} does not connect a place and a transition", source, target);
+                        return Err(invalid(line, message));
+                    }
+                },
+            };
+            net.arc(arc, weight);
+        }
+
+        net.into_net(id, capacities)
+    }
+    /// Load a net from a file in the `.ndr` format of the graphical editor of TINA
+    pub fn load_ndr<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).map(BufReader::new).map_err(Error::IO)?;
+        Self::read_ndr(file)
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// The places and transitions as they are written to TINA files.
+    /// Places and transitions are named by their original IDs if they have one, and by their IDs otherwise,
+    /// and their names become labels. Places with a finite capacity get a complementary place holding their free capacity.
+    fn tina_nodes(&self) -> (Vec<TinaPlace>, Vec<TinaTransition>) {
+        let ids = &self.annotations.ids;
+        let mut allocator = IdAllocator::new(ids.places.values().chain(ids.transitions.values()));
+        let place_names: Vec<String> = self.places
+            .iter()
+            .map(|place| allocator.allocate(ids.places.get(&place.id), place.id.to_string()))
+            .collect();
+        let transition_names: Vec<String> = self.transitions
+            .iter()
+            .map(|transition| allocator.allocate(ids.transitions.get(&transition.id), transition.id.to_string()))
+            .collect();
+        let label = |name: &str, id: &str| (!name.is_empty() && name != id).then(|| name.to_string());
+
+        // The places of the net come in order of their IDs, each followed by its complementary place
+        let mut places = Vec::new();
+        let mut indices = Vec::with_capacity(self.places.len());
+        let mut complements = HashMap::new();
+        for place in &self.places {
+            let name = &place_names[place.id.0];
+            let tokens = self.initial_marking.get(&place.id).0;
+            indices.push(places.len());
+            places.push(TinaPlace { name: name.clone(), label: label(&place.name, name), tokens, place: place.id, complement: false });
+            let capacity = self.capacities.get_or_default(&place.id).0;
+            if capacity != usize::MAX {
+                complements.insert(place.id, places.len());
+                let name = allocator.allocate(None, format!("{}_free", name));
+                places.push(TinaPlace { name, label: None, tokens: capacity.saturating_sub(tokens), place: place.id, complement: true });
+            }
+        }
+
+        let weight = |arc: Arc| if self.arcs.contains(&arc) { self.weights.get_or_default(&arc).0 } else { 0 };
+        let transitions = self.transitions
+            .iter()
+            .map(|transition| {
+                let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+                for arc in &self.arcs {
+                    let weight = self.weights.get_or_default(arc).0;
+                    match *arc {
+                        Arc::PlaceTransition(place, id) if id == transition.id => inputs.push((indices[place.0], weight)),
+                        Arc::TransitionPlace(id, place) if id == transition.id => outputs.push((indices[place.0], weight)),
+                        _ => {}
+                    }
+                }
+                // The complementary place loses what the place gains and vice versa
+                for place in &self.places {
+                    if let Some(&complement) = complements.get(&place.id) {
+                        let consumed = weight(Arc::PlaceTransition(place.id, transition.id));
+                        let produced = weight(Arc::TransitionPlace(transition.id, place.id));
+                        if produced > consumed {
+                            inputs.push((complement, produced - consumed));
+                        } else if consumed > produced {
+                            outputs.push((complement, consumed - produced));
+                        }
+                    }
+                }
+                let name = &transition_names[transition.id.0];
+                TinaTransition {
+                    name: name.clone(),
+                    label: label(&transition.name, name),
+                    transition: transition.id,
+                    interval: self.annotations.transitions.get(&transition.id).and_then(Interval::from_annotations),
+                    inputs,
+                    outputs,
+                }
+            })
+            .collect();
+        (places, transitions)
+    }
+    /// Write the net in the TINA format.
+    /// Places and transitions are named by their original IDs if they have one, and by their IDs otherwise,
+    /// and their names are written as labels.
+    pub fn write_tina<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let (places, transitions) = self.tina_nodes();
+        let label = |label: &Option<String>| label.as_ref().map_or(String::new(), |label| format!(" : {}", quote(label)));
+        let node = |&(place, weight): &(usize, usize)| {
+            let name = quote(&places[place].name);
+            if weight == 1 { name } else { format!("{}*{}", name, weight) }
+        };
+
+        if !self.id.is_empty() {
+            writeln!(out, "net {}", quote(&self.id))?;
+        }
+        for transition in &transitions {
+            let interval = transition.interval.as_ref().map_or(String::new(), |interval| format!(" {}", interval));
+            let arcs: Vec<String> = transition.inputs
+                .iter()
+                .map(node)
+                .chain(Some("->".to_string()))
+                .chain(transition.outputs.iter().map(node))
+                .collect();
+            writeln!(out, "tr {}{}{} {}", quote(&transition.name), label(&transition.label), interval, arcs.join(" "))?;
+        }
+        for place in &places {
+            let marking = if place.tokens > 0 { format!(" ({})", place.tokens) } else { String::new() };
+            writeln!(out, "pl {}{}{}", quote(&place.name), label(&place.label), marking)?;
+        }
+        Ok(())
+    }
+    /// Write the net in the `.ndr` format of the graphical editor of TINA.
+    /// The nodes are named like in the TINA format, and placed by their graphics or by the layered layout.
+    /// Complementary places are drawn above their places. Intervals which are open at a finite bound cannot be written.
+    pub fn write_ndr<Out: Write>(&self, mut out: Out) -> IoResult<()> {
+        let (places, transitions) = self.tina_nodes();
+        let layout = self.layout();
+        let label = |label: &Option<String>| label.as_ref().map_or(String::new(), |label| format!(" {} s", quote(label)));
+
+        for place in &places {
+            let mut position = position(self.annotations.places.get(&place.place)).unwrap_or_else(|| layout.place(place.place));
+            if place.complement {
+                position.y -= NODE_SPACING / 2.0;
+            }
+            let name = quote(&place.name);
+            writeln!(out, "p {:.1} {:.1} {} {} n{}", position.x, position.y, name, place.tokens, label(&place.label))?;
+        }
+        for transition in &transitions {
+            let annotations = self.annotations.transitions.get(&transition.transition);
+            let position = position(annotations).unwrap_or_else(|| layout.transition(transition.transition));
+            let (lower, upper) = match &transition.interval {
+                None => ("0", "w"),
+                Some(interval) if interval.lower_open || (interval.upper_open && interval.upper.is_some()) => {
+                    let message = format!("transition {}: the interval {} cannot be written to an .ndr file", transition.name, interval);
+                    return Err(IoError::new(ErrorKind::InvalidInput, message));
+                }
+                Some(interval) => (interval.lower.as_str(), interval.upper.as_deref().unwrap_or("w")),
+            };
+            let name = quote(&transition.name);
+            writeln!(out, "t {:.1} {:.1} {} {} {} n{}", position.x, position.y, name, lower, upper, label(&transition.label))?;
+        }
+        for transition in &transitions {
+            for &(place, weight) in &transition.inputs {
+                writeln!(out, "e {} {} {} n", quote(&places[place].name), quote(&transition.name), weight)?;
+            }
+            for &(place, weight) in &transition.outputs {
+                writeln!(out, "e {} {} {} n", quote(&transition.name), quote(&places[place].name), weight)?;
+            }
+        }
+        if !self.id.is_empty() {
+            writeln!(out, "h {}", quote(&self.id))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, ECNet, PTNet, Pnml};
+
+    #[test]
+    fn test_tina() -> Result<(), Box<dyn std::error::Error>> {
+        let net = PTNet::read_tina(include_str!("../../models/handover.net").as_bytes())?;
+        assert_eq!(net.id, "handover");
+        assert_eq!(net.places.len(), 4);
+        assert_eq!(net.transitions.len(), 3);
+        let send = net.annotations.ids.find_transition("send").unwrap();
+        let channel = net.annotations.ids.find_place("channel").unwrap();
+        let idle = net.annotations.ids.find_place("idle").unwrap();
+        assert_eq!(net.transitions[send.0].name, "Send message");
+        assert_eq!(net.places[channel.0].name, "Message channel");
+        assert_eq!(net.weights.get_or_default(&Arc::TransitionPlace(send, channel)), Weight(2));
+        assert_eq!(net.initial_marking.get(&idle), Tokens(2));
+        assert_eq!(net.reachability_analysis().rows.len(), 6);
+
+        // Writing and reading the net again yields the same net with the same time intervals
+        let mut tina = Vec::new();
+        net.write_tina(&mut tina)?;
+        let tina = String::from_utf8(tina)?;
+        for fragment in [
+            "tr send : {Send message} [1,3] idle -> channel*2 waiting\n",
+            "tr receive ]0,2] channel*2 -> done\n",
+            "tr ack done waiting -> idle\n",
+            "pl idle (2)\n",
+        ] {
+            assert!(tina.contains(fragment), "{} is missing from\n{}", fragment, tina);
+        }
+        let reread = PTNet::read_tina(tina.as_bytes())?;
+        assert_eq!(reread.arcs, net.arcs);
+        assert_eq!(reread.initial_marking, net.initial_marking);
+
+        // The intervals are kept in PNML files
+        let xml = Pnml::from(net.clone()).to_string();
+        assert!(xml.contains(r#"<interval xmlns="http://www.w3.org/1998/Math/MathML" closure="open-closed">"#), "{}", xml);
+        let reloaded: PTNet = parse_xml::<_, Pnml>(xml.as_bytes())?.try_into()?;
+        let mut tina = Vec::new();
+        reloaded.write_tina(&mut tina)?;
+        assert!(String::from_utf8(tina)?.contains("tr receive ]0,2] channel*2 -> done\n"));
+
+        // Features which the net cannot represent are rejected
+        let error = ECNet::read_tina(include_str!("../../models/handover.net").as_bytes()).unwrap_err();
+        assert!(matches!(error, Error::InvalidTina(_)));
+        let error = PTNet::read_tina("tr t p?-1 -> q".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid TINA file: line 1: inhibitor arcs are not supported");
+        let error = PTNet::read_tina("tr t p -> q\n\npr t > u".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid TINA file: line 3: priorities are not supported");
+
+        // Capacities become complementary places, so TINA finds the same reachability graph
+        let pnml: Pnml = parse_xml(include_str!("../../models/ids.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let mut tina = Vec::new();
+        net.write_tina(&mut tina)?;
+        let tina = String::from_utf8(tina)?;
+        assert!(tina.contains("pl {p-idle_free}\n"), "{}", tina);
+        let reread = PTNet::read_tina(tina.as_bytes())?;
+        assert_eq!(reread.places.len(), 2 * net.places.len());
+        assert_eq!(reread.reachability_analysis().rows.len(), net.reachability_analysis().rows.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ndr() -> Result<(), Box<dyn std::error::Error>> {
+        // The drawing describes the same net as the TINA format, apart from a closed interval of receive
+        let net = PTNet::read_ndr(include_str!("../../models/handover.ndr").as_bytes())?;
+        let tina = PTNet::read_tina(include_str!("../../models/handover.net").as_bytes())?;
+        assert_eq!(net.id, "handover");
+        let arcs = |net: &PTNet| {
+            let ids = &net.annotations.ids;
+            let mut arcs: Vec<(String, String, usize)> = net.arcs
+                .iter()
+                .map(|arc| {
+                    let weight = net.weights.get_or_default(arc).0;
+                    match *arc {
+                        Arc::PlaceTransition(place, transition) => {
+                            (ids.places[&place].clone(), ids.transitions[&transition].clone(), weight)
+                        }
+                        Arc::TransitionPlace(transition, place) => {
+                            (ids.transitions[&transition].clone(), ids.places[&place].clone(), weight)
+                        }
+                    }
+                })
+                .collect();
+            arcs.sort();
+            arcs
+        };
+        assert_eq!(arcs(&net), arcs(&tina));
+        let send = net.annotations.ids.find_transition("send").unwrap();
+        let channel = net.annotations.ids.find_place("channel").unwrap();
+        assert_eq!(net.transitions[send.0].name, "Send message");
+        assert_eq!(net.places[channel.0].name, "Message channel");
+        assert_eq!(net.weights.get_or_default(&Arc::TransitionPlace(send, channel)), Weight(2));
+        assert_eq!(net.reachability_analysis().rows.len(), tina.reachability_analysis().rows.len());
+
+        // The positions become graphics, and the intervals are written to the TINA format
+        let xml = Pnml::from(net.clone()).to_string();
+        assert!(xml.contains(r#"<position x="260" y="60"/>"#), "{}", xml);
+        let mut text = Vec::new();
+        net.write_tina(&mut text)?;
+        let text = String::from_utf8(text)?;
+        assert!(text.contains("tr receive [0,2] channel*2 -> done\n"), "{}", text);
+
+        // Writing and reading the drawing again keeps the net, the positions and the intervals
+        let mut ndr = Vec::new();
+        net.write_ndr(&mut ndr)?;
+        let ndr = String::from_utf8(ndr)?;
+        for fragment in [
+            "p 260.0 60.0 channel 0 n {Message channel} s\n",
+            "t 160.0 100.0 send 1 3 n {Send message} s\n",
+            "t 360.0 180.0 ack 0 w n\n",
+            "e send channel 2 n\n",
+            "h handover\n",
+        ] {
+            assert!(ndr.contains(fragment), "{} is missing from\n{}", fragment, ndr);
+        }
+        let reread = PTNet::read_ndr(ndr.as_bytes())?;
+        assert_eq!(reread.arcs, net.arcs);
+        assert_eq!(reread.initial_marking, net.initial_marking);
+        assert_eq!(Pnml::from(reread).to_string(), xml);
+
+        // Intervals which are open at a finite bound cannot be drawn by the editor
+        let error = tina.write_ndr(Vec::new()).unwrap_err();
+        assert_eq!(error.to_string(), "transition receive: the interval ]0,2] cannot be written to an .ndr file");
+
+        // Capacities become complementary places, which are drawn next to their places
+        let pnml: Pnml = parse_xml(include_str!("../../models/ids.pnml").as_bytes())?;
+        let net: ECNet = pnml.try_into()?;
+        let mut ndr = Vec::new();
+        net.write_ndr(&mut ndr)?;
+        let reread = PTNet::read_ndr(ndr.as_slice())?;
+        assert_eq!(reread.places.len(), 2 * net.places.len());
+        assert_eq!(reread.reachability_analysis().rows.len(), net.reachability_analysis().rows.len());
+
+        // Features which the net cannot represent are rejected
+        let error = ECNet::read_ndr(include_str!("../../models/handover.ndr").as_bytes()).unwrap_err();
+        assert!(matches!(error, Error::InvalidTina(_)));
+        let error = PTNet::read_ndr("p 0 0 p 1 n\nt 0 0 t 0 w n\ne p t ?-1 n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid TINA file: line 3: test, inhibitor and stopwatch arcs are not supported");
+        let error = PTNet::read_ndr("p 0 0 p 1 n\ne p q 1 n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid TINA file: line 2: edge from p to q does not connect a place and a transition");
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+//! This module unfolds safe Petri nets into a complete finite prefix of their branching process,
+//! following [J. Esparza, S. Römer, W. Vogler. An Improvement of McMillan's Unfolding Algorithm. Formal Methods in System Design, 20(3):285–310, 2002.](https://doi.org/10.1023/A:1014746130920)
+//!
+//! The unfolding represents the behavior of the net as a partial order: conditions are occurrences of tokens on places,
+//! events are occurrences of transitions, and concurrent events are not interleaved.
+//! For highly concurrent nets the prefix is therefore much smaller than the reachability graph.
+//!
+//! Events are added in the total adequate order of Esparza, Römer and Vogler on their local configurations
+//! (by size, then by the sorted sequence of their transitions, then by their Foata normal forms).
+//! An event is a cut-off event if an event which came before it in this order leads to the same marking;
+//! the prefix is not extended beyond cut-off events.
+//!
+//! Every reachable marking is the marking of some configuration of the prefix without cut-off events.
+//! Such configurations correspond to the maximal sets of pairwise concurrent conditions which are not produced by cut-off events,
+//! which is what the deadlock and reachability checks search for.
+//!
+//! Unfolding requires the net to be safe. Capacities are ignored,
+//! so an EC net whose transitions can be blocked by a marked output place is reported as unsafe.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+
+use derive_more::Display as DeriveDisplay;
+
+use super::{Arc, CapacityFn, Marking, MarkingFn, PetriNet, PlaceId, Tokens, TransitionId, WeightFn};
+use crate::Error;
+
+/// A unique ID for a condition in the unfolding, displayed as b0, b1, b2, ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, DeriveDisplay)]
+#[display(fmt = "b{}", _0)]
+pub struct ConditionId(usize);
+
+/// A unique ID for an event in the unfolding, displayed as e0, e1, e2, ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, DeriveDisplay)]
+#[display(fmt = "e{}", _0)]
+pub struct EventId(usize);
+
+/// An occurrence of a token on a place
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub place: PlaceId,
+    /// The event which produced the token, or None for the tokens of the initial marking
+    pub producer: Option<EventId>,
+}
+
+/// An occurrence of a transition
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub transition: TransitionId,
+    pub preset: Vec<ConditionId>,
+    pub postset: Vec<ConditionId>,
+    /// For cut-off events, the event whose local configuration leads to the same marking,
+    /// or None if that is the initial marking
+    pub cutoff: Option<Option<EventId>>,
+}
+
+/// A complete finite prefix of the unfolding of a safe Petri net
+#[derive(Debug, Clone)]
+pub struct Unfolding<'net, C: CapacityFn, W: WeightFn> {
+    petri_net: &'net PetriNet<C, W>,
+    pub conditions: Vec<Condition>,
+    pub events: Vec<Event>,
+    /// The conditions which are concurrent to every condition (ID = index)
+    co: Vec<BTreeSet<usize>>,
+    /// The local configuration of every event (ID = index), i.e. the event and all its causal predecessors, sorted by ID
+    configurations: Vec<Vec<usize>>,
+}
+
+/// Events are added to the prefix in the order of this key of their local configuration
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct AdequateOrder {
+    size: usize,
+    /// The transitions of the configuration, sorted
+    transitions: Vec<TransitionId>,
+    /// The transitions in each level of the Foata normal form, sorted
+    foata: Vec<Vec<TransitionId>>,
+}
+
+/// An event which can be added to the prefix, but has not been yet
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Extension {
+    order: AdequateOrder,
+    transition: TransitionId,
+    preset: Vec<usize>,
+}
+
+/// The state of the unfolding algorithm
+struct Unfolder<'net, C: CapacityFn, W: WeightFn> {
+    unfolding: Unfolding<'net, C, W>,
+    /// The input and output places of every transition (ID = index); transitions which can never fire in a safe net are left out
+    presets: Vec<Option<Vec<PlaceId>>>,
+    postsets: Vec<Vec<PlaceId>>,
+    /// The conditions on every place (ID = index)
+    by_place: Vec<Vec<usize>>,
+    /// The depth of every event in the causal order, starting at 1
+    depths: Vec<usize>,
+    queue: BinaryHeap<Reverse<Extension>>,
+    known: HashSet<(TransitionId, Vec<usize>)>,
+    /// The markings reached so far, with the event that reached them first
+    marks: HashMap<Marking, Option<EventId>>,
+}
+
+impl<C: CapacityFn, W: WeightFn> Unfolder<'_, C, W> {
+    /// Add a condition, failing if it shares its place with a concurrent condition
+    fn add_condition(&mut self, place: PlaceId, producer: Option<EventId>, co: BTreeSet<usize>) -> Result<usize, Error> {
+        if let Some(&other) = self.by_place[place.0].iter().find(|other| co.contains(other)) {
+            let marking = self.unfolding.marking_of(&self.unfolding.past(&[other]));
+            return Err(Error::UnsafeNet(format!("{} can hold two tokens, e.g. after reaching {:?}", place, marking)));
+        }
+        let id = self.unfolding.conditions.len();
+        // Concurrent conditions which are created after this one link back to it themselves
+        for &other in co.range(..id) {
+            self.unfolding.co[other].insert(id);
+        }
+        self.unfolding.conditions.push(Condition { place, producer });
+        self.unfolding.co.push(co);
+        self.by_place[place.0].push(id);
+        Ok(id)
+    }
+    /// Queue all extensions which consume the given condition
+    fn find_extensions(&mut self, condition: usize) {
+        let place = self.unfolding.conditions[condition].place;
+        for (index, preset) in self.presets.iter().enumerate() {
+            let Some(preset) = preset.as_ref().filter(|preset| preset.contains(&place)) else { continue };
+            let others: Vec<PlaceId> = preset.iter().copied().filter(|&other| other != place).collect();
+            let mut chosen = vec![condition];
+            let mut found = Vec::new();
+            self.choose(&others, &mut chosen, &mut found);
+            for mut preset in found {
+                preset.sort_unstable();
+                let transition = TransitionId(index);
+                if self.known.insert((transition, preset.clone())) {
+                    let order = self.order(transition, &preset);
+                    self.queue.push(Reverse(Extension { order, transition, preset }));
+                }
+            }
+        }
+    }
+    /// Choose pairwise concurrent, usable conditions for the remaining places
+    fn choose(&self, places: &[PlaceId], chosen: &mut Vec<usize>, found: &mut Vec<Vec<usize>>) {
+        let Some((place, rest)) = places.split_first() else {
+            found.push(chosen.clone());
+            return;
+        };
+        for &candidate in &self.by_place[place.0] {
+            let concurrent = chosen.iter().all(|other| self.unfolding.co[*other].contains(&candidate));
+            if concurrent && self.unfolding.is_usable(candidate) {
+                chosen.push(candidate);
+                self.choose(rest, chosen, found);
+                chosen.pop();
+            }
+        }
+    }
+    /// The position of an event with the given transition and preset in the adequate order
+    fn order(&self, transition: TransitionId, preset: &[usize]) -> AdequateOrder {
+        let past = self.unfolding.past(preset);
+        let depth = 1 + past.iter().map(|&event| self.depths[event]).max().unwrap_or(0);
+        let mut transitions: Vec<TransitionId> = past.iter().map(|&event| self.unfolding.events[event].transition).collect();
+        transitions.push(transition);
+        transitions.sort_unstable();
+        let mut foata = vec![Vec::new(); depth];
+        for &event in &past {
+            foata[self.depths[event] - 1].push(self.unfolding.events[event].transition);
+        }
+        foata[depth - 1].push(transition);
+        for level in &mut foata {
+            level.sort_unstable();
+        }
+        AdequateOrder { size: transitions.len(), transitions, foata }
+    }
+    /// Add the next extension to the prefix
+    fn add_event(&mut self, extension: Extension) -> Result<(), Error> {
+        let id = self.unfolding.events.len();
+        let mut configuration = self.unfolding.past(&extension.preset);
+        configuration.push(id);
+        self.depths.push(extension.order.foata.len());
+        self.unfolding.events.push(Event {
+            transition: extension.transition,
+            preset: extension.preset.iter().map(|&condition| ConditionId(condition)).collect(),
+            postset: Vec::new(),
+            cutoff: None,
+        });
+        self.unfolding.configurations.push(configuration);
+
+        // The new conditions are concurrent to each other, and to everything which is concurrent to the whole preset
+        let mut concurrent = extension.preset
+            .iter()
+            .map(|&condition| self.unfolding.co[condition].clone())
+            .reduce(|a, b| a.intersection(&b).copied().collect())
+            .unwrap_or_default();
+        let first = self.unfolding.conditions.len();
+        let postset = self.postsets[extension.transition.0].clone();
+        concurrent.extend(first..first + postset.len());
+        for (offset, &place) in postset.iter().enumerate() {
+            let mut co = concurrent.clone();
+            co.remove(&(first + offset));
+            let condition = self.add_condition(place, Some(EventId(id)), co)?;
+            self.unfolding.events[id].postset.push(ConditionId(condition));
+        }
+
+        let marking = self.unfolding.marking_of(&self.unfolding.configurations[id]);
+        if let Some(&companion) = self.marks.get(&marking) {
+            self.unfolding.events[id].cutoff = Some(companion);
+        } else {
+            self.marks.insert(marking, Some(EventId(id)));
+            for condition in first..self.unfolding.conditions.len() {
+                self.find_extensions(condition);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> PetriNet<C, W> {
+    /// Build a complete finite prefix of the unfolding of this net, which must be safe
+    pub fn unfold(&self) -> Result<Unfolding<'_, C, W>, Error> {
+        let transition_io = self.transition_io();
+        let weight_one = |arc: Arc| self.weights.get_or_default(&arc).0 == 1;
+        let mut presets = Vec::with_capacity(transition_io.len());
+        let mut postsets = Vec::with_capacity(transition_io.len());
+        for transition in &transition_io {
+            if transition.inputs.is_empty() {
+                return Err(Error::UnsafeNet(format!("{} has no input places and can fire without end", transition.id)));
+            }
+            if !transition.outputs.iter().all(|&output| weight_one(Arc::TransitionPlace(transition.id, output))) {
+                return Err(Error::UnsafeNet(format!("{} puts more than one token on a place", transition.id)));
+            }
+            // A transition which needs more than one token from a place can never fire in a safe net
+            let fireable = transition.inputs.iter().all(|&input| weight_one(Arc::PlaceTransition(input, transition.id)));
+            presets.push(fireable.then(|| transition.inputs.clone()));
+            postsets.push(transition.outputs.clone());
+        }
+
+        let mut unfolder = Unfolder {
+            unfolding: Unfolding {
+                petri_net: self,
+                conditions: Vec::new(),
+                events: Vec::new(),
+                co: Vec::new(),
+                configurations: Vec::new(),
+            },
+            presets,
+            postsets,
+            by_place: vec![Vec::new(); self.places.len()],
+            depths: Vec::new(),
+            queue: BinaryHeap::new(),
+            known: HashSet::new(),
+            marks: HashMap::from([(self.initial_marking.clone(), None)]),
+        };
+        // The tokens of the initial marking are all concurrent to each other
+        let initial: Vec<PlaceId> = self.initial_marking.iter().filter(|(_, tokens)| tokens.0 > 0).map(|(place, _)| place).collect();
+        for (index, &place) in initial.iter().enumerate() {
+            if self.initial_marking.get(&place).0 > 1 {
+                return Err(Error::UnsafeNet(format!("{} holds more than one token initially", place)));
+            }
+            let co = (0..initial.len()).filter(|&other| other != index).collect();
+            unfolder.add_condition(place, None, co)?;
+        }
+        for condition in 0..initial.len() {
+            unfolder.find_extensions(condition);
+        }
+        while let Some(Reverse(extension)) = unfolder.queue.pop() {
+            unfolder.add_event(extension)?;
+        }
+        Ok(unfolder.unfolding)
+    }
+}
+
+impl<C: CapacityFn, W: WeightFn> Unfolding<'_, C, W> {
+    /// The number of cut-off events in the prefix
+    pub fn cutoffs(&self) -> usize {
+        self.events.iter().filter(|event| event.cutoff.is_some()).count()
+    }
+    /// The events which must occur before the given conditions can be marked, sorted by ID
+    fn past(&self, conditions: &[usize]) -> Vec<usize> {
+        let mut past = BTreeSet::new();
+        for &condition in conditions {
+            if let Some(producer) = self.conditions[condition].producer {
+                past.extend(&self.configurations[producer.0]);
+            }
+        }
+        past.into_iter().collect()
+    }
+    /// The marking reached by firing a configuration
+    fn marking_of(&self, configuration: &[usize]) -> Marking {
+        let mut marking = self.petri_net.initial_marking.clone();
+        for &event in configuration {
+            let event = &self.events[event];
+            for condition in &event.preset {
+                let place = self.conditions[condition.0].place;
+                marking.set(place, Tokens(marking.get(&place).0 - 1));
+            }
+            for condition in &event.postset {
+                let place = self.conditions[condition.0].place;
+                marking.set(place, Tokens(marking.get(&place).0 + 1));
+            }
+        }
+        marking
+    }
+    /// A firing sequence which marks the given conditions.
+    /// Events are added in an order which respects causality, so sorting them by ID yields a valid sequence.
+    fn firing_sequence(&self, conditions: &[usize]) -> Vec<TransitionId> {
+        self.past(conditions).into_iter().map(|event| self.events[event].transition).collect()
+    }
+    /// Conditions produced by cut-off events are not part of any configuration needed to reach a marking
+    fn is_usable(&self, condition: usize) -> bool {
+        self.conditions[condition]
+            .producer
+            .is_none_or(|event| self.events[event.0].cutoff.is_none())
+    }
+    /// Find a reachable deadlock, returning a firing sequence which leads to it
+    pub fn deadlock(&self) -> Option<Vec<TransitionId>> {
+        let transition_io = self.petri_net.transition_io();
+        let candidates: BTreeSet<usize> = (0..self.conditions.len()).filter(|&condition| self.is_usable(condition)).collect();
+        let mut deadlock = None;
+        // Every maximal set of concurrent usable conditions is the cut of a reachable marking
+        self.bron_kerbosch(&mut Vec::new(), candidates, BTreeSet::new(), &mut |cut| {
+            let places: HashSet<PlaceId> = cut.iter().map(|&condition| self.conditions[condition].place).collect();
+            let enabled = transition_io
+                .iter()
+                .any(|transition| transition.inputs.iter().all(|input| places.contains(input)));
+            if !enabled {
+                deadlock = Some(self.firing_sequence(cut));
+            }
+            deadlock.is_none()
+        });
+        deadlock
+    }
+    /// Returns true if no reachable marking is a deadlock
+    pub fn is_deadlock_free(&self) -> bool {
+        self.deadlock().is_none()
+    }
+    /// Enumerate the maximal cliques of the concurrency relation, until the visitor returns false
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: BTreeSet<usize>,
+        mut excluded: BTreeSet<usize>,
+        visit: &mut impl FnMut(&[usize]) -> bool,
+    ) -> bool {
+        let Some(&pivot) = candidates.iter().chain(&excluded).max_by_key(|&&pivot| {
+            candidates.iter().filter(|candidate| self.co[pivot].contains(candidate)).count()
+        }) else {
+            return visit(clique);
+        };
+        let branches: Vec<usize> = candidates.iter().copied().filter(|candidate| !self.co[pivot].contains(candidate)).collect();
+        for condition in branches {
+            let co = &self.co[condition];
+            clique.push(condition);
+            let proceed = self.bron_kerbosch(
+                clique,
+                candidates.iter().copied().filter(|other| co.contains(other)).collect(),
+                excluded.iter().copied().filter(|other| co.contains(other)).collect(),
+                visit,
+            );
+            clique.pop();
+            if !proceed {
+                return false;
+            }
+            candidates.remove(&condition);
+            excluded.insert(condition);
+        }
+        true
+    }
+    /// Find a firing sequence which reaches the given marking, if it is reachable
+    pub fn reach(&self, marking: &Marking) -> Option<Vec<TransitionId>> {
+        if marking.iter().any(|(_, tokens)| tokens.0 > 1) {
+            return None; // The net is safe
+        }
+        let places: Vec<PlaceId> = marking.iter().filter(|(_, tokens)| tokens.0 > 0).map(|(place, _)| place).collect();
+        let mut cut = Vec::new();
+        self.find_cut(&places, &mut cut).then(|| self.firing_sequence(&cut))
+    }
+    /// Search for a maximal set of concurrent usable conditions which marks exactly the given places
+    fn find_cut(&self, places: &[PlaceId], cut: &mut Vec<usize>) -> bool {
+        let Some((place, rest)) = places.split_first() else {
+            // The cut must be maximal, otherwise the marking has more tokens
+            return !(0..self.conditions.len()).any(|condition| {
+                self.is_usable(condition) && cut.iter().all(|other| self.co[*other].contains(&condition))
+            });
+        };
+        for condition in 0..self.conditions.len() {
+            if self.conditions[condition].place == *place
+                && self.is_usable(condition)
+                && cut.iter().all(|other| self.co[*other].contains(&condition))
+            {
+                cut.push(condition);
+                if self.find_cut(rest, cut) {
+                    return true;
+                }
+                cut.pop();
+            }
+        }
+        false
+    }
+    /// Returns true if the marking is reachable
+    pub fn is_reachable(&self, marking: &Marking) -> bool {
+        self.reach(marking).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_xml, PTNet, Pnml};
+
+    #[test]
+    fn test_unfold() -> Result<(), Box<dyn std::error::Error>> {
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let unfolding = net.unfold()?;
+        assert_eq!(unfolding.events.len(), 5);
+        assert_eq!(unfolding.cutoffs(), 1);
+        assert!(unfolding.is_deadlock_free());
+        let both_done: Marking = [(PlaceId(3), Tokens(1)), (PlaceId(4), Tokens(1))].into_iter().collect();
+        assert_eq!(unfolding.reach(&both_done), Some(vec![TransitionId(0), TransitionId(1), TransitionId(2)]));
+        let start_and_done: Marking = [(PlaceId(0), Tokens(1)), (PlaceId(3), Tokens(1))].into_iter().collect();
+        assert!(!unfolding.is_reachable(&start_and_done));
+
+        // Taking the resource before using it leaves the prepared token stuck
+        let pnml: Pnml = parse_xml(include_str!("../../models/confusion.pnml").as_bytes())?;
+        let net: PTNet = pnml.try_into()?;
+        let deadlock = net.unfold()?.deadlock().expect("net should have a deadlock");
+        let transition_io = net.transition_io();
+        let marking = deadlock.iter().fold(net.initial_marking.clone(), |marking, transition| {
+            let fired = net.fire_transitions(&transition_io, &marking);
+            fired.into_iter().find(|(id, _)| id == transition).expect("transition should be enabled").1
+        });
+        assert!(net.fire_transitions(&transition_io, &marking).is_empty());
+
+        // If the split already marks the left branch as done, working on it marks it done a second time
+        let pnml: Pnml = parse_xml(include_str!("../../models/parallel.pnml").as_bytes())?;
+        let mut unsafe_net: PTNet = pnml.try_into()?;
+        unsafe_net.arcs.push(Arc::TransitionPlace(TransitionId(0), PlaceId(3)));
+        assert!(matches!(unsafe_net.unfold(), Err(Error::UnsafeNet(_))));
+        Ok(())
+    }
+}